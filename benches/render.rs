@@ -0,0 +1,66 @@
+// Benchmarks for the headless render path (view::render), which draws an
+// image into a pixel buffer with the same fit, zoom and pan math as the
+// window. Run with
+//
+//     cargo bench
+//
+// Throughput is reported per output pixel, so "Melem/s" reads as
+// megapixels of view drawn per second. The source images are generated
+// rather than loaded from disk, so the numbers don't depend on a decoder
+// and every run uses exactly the same pixels.
+//
+// This is a binary crate with no library to link against, so the module is
+// compiled straight into the benchmark. Not everything in it is used here.
+#[allow(dead_code)]
+#[path = "../src/view.rs"]
+mod view;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use eframe::egui::Vec2;
+use image::{Rgba, RgbaImage};
+use view::{Alignment, FitMode};
+
+// A smooth gradient with a little per-pixel variation, like a photo,
+// rather than a flat color.
+fn sample_image(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let noise = (x.wrapping_mul(31) ^ y.wrapping_mul(17)) as u8 & 0x0f;
+        Rgba([
+            (x * 255 / width) as u8 ^ noise,
+            (y * 255 / height) as u8 ^ noise,
+            128 ^ noise,
+            255,
+        ])
+    })
+}
+
+fn bench_render(c: &mut Criterion) {
+    // (name, source size, view size)
+    let cases = [
+        ("downscale 12MP to 1080p", [4000, 3000], [1920, 1080]),
+        ("downscale 12MP to 4K", [4000, 3000], [3840, 2160]),
+        ("upscale 1MP to 1080p", [1200, 800], [1920, 1080]),
+        ("upscale 1MP to 4K", [1200, 800], [3840, 2160]),
+        ("small window", [4000, 3000], [640, 480]),
+    ];
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(20);
+    for (name, [src_w, src_h], view_size) in cases {
+        let source = sample_image(src_w, src_h);
+        group.throughput(Throughput::Elements(view_size[0] as u64 * view_size[1] as u64));
+
+        group.bench_with_input(BenchmarkId::new("fit", name), &source, |b, source| {
+            b.iter(|| view::render(source, view_size, 1.0, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+        });
+        // Zoomed in and panned into a corner: most source pixels are
+        // clipped, which exercises the per-pixel bounds checks.
+        group.bench_with_input(BenchmarkId::new("zoom 3x panned", name), &source, |b, source| {
+            b.iter(|| view::render(source, view_size, 3.0, FitMode::Window, Vec2::new(1e6, 1e6), Alignment::CENTER));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+// Records the version of the image crate we were built against, for
+// `gallerust --version` (see cli::version). Cargo gives a crate its own
+// version but not its dependencies', so it's read from Cargo.lock.
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let packages: Vec<&str> = lock.split("[[package]]").collect();
+    let field = |package: &str, name: &str| {
+        package.lines().find_map(|line| line.trim().strip_prefix(name)?.strip_prefix(" = ")).map(|value| value.trim_matches('"').to_string())
+    };
+
+    // Other dependencies may use an older image crate of their own. Our
+    // own entry names the version when there's more than one to choose
+    // from, and just "image" when there's only one.
+    let ours = packages.iter().find(|package| field(package, "name").as_deref() == Some("gallerust"));
+    let dependency = ours.and_then(|package| {
+        package.lines().map(|line| line.trim().trim_matches(|c| c == '"' || c == ',')).find(|line| *line == "image" || line.starts_with("image "))
+    });
+    let version = match dependency.and_then(|dependency| dependency.strip_prefix("image ")) {
+        Some(version) => Some(version.to_string()),
+        None => packages.iter().find(|package| field(package, "name").as_deref() == Some("image")).and_then(|package| field(package, "version")),
+    };
+    println!("cargo:rustc-env=GALLERUST_IMAGE_VERSION={}", version.as_deref().unwrap_or("unknown"));
+}
@@ -0,0 +1,204 @@
+// Playing animated GIFs, APNGs and WebPs.
+//
+// Every frame is decoded up front on the loader thread and kept in memory,
+// since an animation is usually small and looping it shouldn't mean
+// decoding it over and over. Each frame is then uploaded over the image's
+// texture when its turn comes.
+//
+// Playback can be paused (Space) to look at one frame, and stepped a frame
+// at a time with the arrow keys or , and . while paused.
+//
+// A Playback belongs to the image on screen and lives exactly as long as
+// it does:
+//
+// - it starts, playing from the first frame, when an animated image has
+//   finished loading;
+// - it's dropped as soon as another image is asked for (load_texture in
+//   main.rs), which freezes the old animation on the frame it was showing
+//   while the next image decodes. Its frames and timer go with it, so
+//   nothing of it can be drawn over, or keep waking up, the next image;
+// - reloading the same image starts it again from the beginning.
+//
+// So the arrow keys go to the next or previous file mid-animation just as
+// they do on a still image. Only a paused animation keeps them for its
+// frames, and Space (or stepping with , and .) is how it gets paused.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, ColorType, Frames, ImageDecoder, ImageFormat, ImageReader, RgbaImage};
+
+// Animations whose frames would take more memory than this are shown as
+// a still of their first frame instead.
+const MAX_BYTES: u64 = 512 * 1024 * 1024;
+
+// Browsers play frames with a delay under 20ms at 100ms, since a delay of
+// 0 or 10ms is almost always someone's shortcut for "as fast as possible"
+// and playing it literally races through the animation. We do the same so
+// animations run at the speed everyone expects.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const SHORT_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
+pub struct Frame {
+    pub pixels: RgbaImage,
+    pub delay: Duration,
+}
+
+// An animation's frames, and what the file says about them for the info
+// panel. The first frame doubles as the still shown before playback.
+pub struct Animation {
+    pub frames: Vec<Frame>,
+    pub color: ColorType,
+    // The embedded color profile, which isn't applied to the frames (see
+    // color.rs).
+    pub icc: Option<Vec<u8>>,
+}
+
+// All the frames of the animation at `path`, or None if it isn't animated
+// (or is too big to keep, or fails partway).
+pub fn decode_frames(path: &Path) -> Option<Animation> {
+    let reader = || File::open(path).ok().map(BufReader::new);
+    let (frames, color, icc) = match format(path)? {
+        ImageFormat::Gif => {
+            let mut decoder = GifDecoder::new(reader()?).ok()?;
+            let (color, icc) = (decoder.color_type(), decoder.icc_profile().ok().flatten());
+            (decoder.into_frames(), color, icc)
+        }
+        ImageFormat::Png => {
+            let mut decoder = PngDecoder::new(reader()?).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            let (color, icc) = (decoder.color_type(), decoder.icc_profile().ok().flatten());
+            (decoder.apng().ok()?.into_frames(), color, icc)
+        }
+        ImageFormat::WebP => {
+            let mut decoder = WebPDecoder::new(reader()?).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            let (color, icc) = (decoder.color_type(), decoder.icc_profile().ok().flatten());
+            (decoder.into_frames(), color, icc)
+        }
+        _ => return None,
+    };
+    Some(Animation { frames: collect(path, frames)?, color, icc })
+}
+
+// Whether `path` is an animation, however big, and even if some of its
+// frames are broken: what decode_frames would play if it could. Only the
+// headers are read, or for a GIF, which doesn't say, the first two frames.
+pub fn is_animated(path: &Path) -> bool {
+    let Some(reader) = File::open(path).ok().map(BufReader::new) else { return false; };
+    match format(path) {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader).is_ok_and(|decoder| decoder.into_frames().nth(1).is_some()),
+        Some(ImageFormat::Png) => PngDecoder::new(reader).is_ok_and(|decoder| decoder.is_apng().unwrap_or(false)),
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader).is_ok_and(|decoder| decoder.has_animation()),
+        _ => false,
+    }
+}
+
+// The format of the file at `path`, from its first few bytes where
+// they're recognisable and its extension otherwise, as decode::open goes
+// by.
+fn format(path: &Path) -> Option<ImageFormat> {
+    ImageReader::open(path).ok()?.with_guessed_format().ok()?.format()
+}
+
+fn collect(path: &Path, frames: Frames) -> Option<Vec<Frame>> {
+    let mut collected = Vec::new();
+    let mut bytes = 0u64;
+    for frame in frames {
+        let frame = frame.ok()?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_secs_f64(numer as f64 / denom.max(1) as f64 / 1000.0);
+        let pixels = frame.into_buffer();
+        bytes += pixels.as_raw().len() as u64;
+        if bytes > MAX_BYTES {
+            log::warn!("{}: animation too large to play, showing its first frame", path.display());
+            return None;
+        }
+        collected.push(Frame { pixels, delay: if delay < MIN_DELAY { SHORT_DELAY } else { delay } });
+    }
+    (collected.len() > 1).then_some(collected)
+}
+
+pub struct Playback {
+    frames: Vec<Frame>,
+    current: usize,
+    // When the current frame went up, while playing.
+    shown_at: Instant,
+    paused: bool,
+}
+
+impl Playback {
+    pub fn new(frames: Vec<Frame>) -> Self {
+        Self { frames, current: 0, shown_at: Instant::now(), paused: false }
+    }
+
+    pub fn frame(&self) -> &RgbaImage {
+        &self.frames[self.current].pixels
+    }
+
+    // (current, total), counting from 1, for the title.
+    pub fn position(&self) -> (usize, usize) {
+        (self.current + 1, self.frames.len())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        // Resuming gives the frame on screen its full time again.
+        self.shown_at = Instant::now();
+    }
+
+    // Carry on from the frame that was showing before `paused` with the
+    // window out of sight, as if no time had passed.
+    pub fn resume_after(&mut self, paused: Duration) {
+        self.shown_at += paused;
+    }
+
+    // Go `by` frames forward (or back, if negative), wrapping around.
+    // Pauses, since stepping is for looking at one frame at a time.
+    pub fn step(&mut self, by: isize) {
+        self.paused = true;
+        let len = self.frames.len() as isize;
+        self.current = (self.current as isize + by).rem_euclid(len) as usize;
+    }
+
+    // Move on to whichever frame is due now. Returns whether the frame
+    // changed, and how long until the next one is due (None while paused).
+    pub fn advance(&mut self) -> (bool, Option<Duration>) {
+        if self.paused {
+            return (false, None);
+        }
+        let mut changed = false;
+        let mut elapsed = self.shown_at.elapsed();
+        // After a stall (the window hidden, a slow frame) skip ahead rather
+        // than replaying every missed frame in a rush, but never loop round
+        // more than once.
+        for _ in 0..self.frames.len() {
+            let delay = self.frames[self.current].delay;
+            if elapsed < delay {
+                break;
+            }
+            elapsed -= delay;
+            self.shown_at += delay;
+            self.current = (self.current + 1) % self.frames.len();
+            changed = true;
+        }
+        if self.shown_at.elapsed() > self.frames[self.current].delay {
+            self.shown_at = Instant::now();
+        }
+        let remaining = self.frames[self.current].delay.saturating_sub(self.shown_at.elapsed());
+        (changed, Some(remaining))
+    }
+}
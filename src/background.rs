@@ -0,0 +1,135 @@
+// What's drawn behind the image.
+//
+// Black suits photos, but a transparent PNG on black is unreadable (a black
+// logo just disappears), so by default images that actually have
+// transparent pixels get the checkerboard image editors use instead. For
+// presenting photos there's also the image's own average color, which
+// frames it in something that matches rather than a hard black border.
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::ImageInfo;
+
+// Size of one checkerboard square, in points.
+const CELL: f32 = 8.0;
+
+// The checkerboard's two shades of gray.
+const LIGHT: u8 = 200;
+const DARK: u8 = 150;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Background {
+    // Checkerboard for images with transparency, black for the rest.
+    Auto,
+    Black,
+    Checkerboard,
+    // The current image's average color, all around it.
+    AverageColor,
+}
+
+impl Background {
+    pub const ALL: [Background; 4] = [Background::Auto, Background::Black, Background::Checkerboard, Background::AverageColor];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Background::Auto => "Checkerboard if transparent",
+            Background::Black => "Black",
+            Background::Checkerboard => "Checkerboard",
+            Background::AverageColor => "Image's average color",
+        }
+    }
+
+    pub fn shows_checkerboard(self, has_transparency: bool) -> bool {
+        match self {
+            Background::Auto => has_transparency,
+            Background::Black => false,
+            Background::Checkerboard => true,
+            Background::AverageColor => false,
+        }
+    }
+
+    // The color to fill the whole view with instead of black, if any.
+    // None until the image's info has been worked out, so the preview of a
+    // large photo is still shown on black.
+    pub fn surround(self, info: Option<&ImageInfo>) -> Option<egui::Color32> {
+        match (self, info) {
+            (Background::AverageColor, Some(info)) => {
+                let [r, g, b] = info.average_color;
+                Some(egui::Color32::from_rgb(r, g, b))
+            }
+            _ => None,
+        }
+    }
+}
+
+// A thin line around the image, so its edges stand out from a background
+// of a similar color (a white page on a light surround, a night sky on
+// black). Drawn just outside the image, never over any of its pixels.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Border {
+    pub show: bool,
+    // In points.
+    pub width: f32,
+    pub color: [u8; 3],
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self {
+            show: false,
+            width: 1.0,
+            color: [128, 128, 128],
+        }
+    }
+}
+
+impl Border {
+    pub fn paint(self, painter: &egui::Painter, image_rect: egui::Rect) {
+        if !self.show || self.width <= 0.0 {
+            return;
+        }
+        let [r, g, b] = self.color;
+        // A stroke is centered on its rectangle, so push it out by half
+        // its width to keep it off the image.
+        painter.rect_stroke(
+            image_rect.expand(self.width / 2.0),
+            0.0,
+            egui::Stroke::new(self.width, egui::Color32::from_rgb(r, g, b)),
+        );
+    }
+}
+
+// The checkerboard's shade at `pos`, in points from the view's top-left
+// corner, matching what paint_checkerboard draws there.
+pub fn checker_gray(pos: egui::Pos2) -> u8 {
+    let cell = (pos.to_vec2() / CELL).floor();
+    if (cell.x + cell.y) as i64 % 2 == 0 { LIGHT } else { DARK }
+}
+
+// A 2x2 checker tile. Drawn with nearest filtering and repeat wrapping, so
+// one tiny texture covers any area without painting thousands of squares.
+pub fn checkerboard_texture(ctx: &egui::Context) -> egui::TextureHandle {
+    let light = egui::Color32::from_gray(LIGHT);
+    let dark = egui::Color32::from_gray(DARK);
+    let tile = egui::ColorImage {
+        size: [2, 2],
+        pixels: vec![light, dark, dark, light],
+    };
+    let options = egui::TextureOptions {
+        wrap_mode: egui::TextureWrapMode::Repeat,
+        ..egui::TextureOptions::NEAREST
+    };
+    ctx.load_texture("checkerboard", tile, options)
+}
+
+// Fill `rect` with the checkerboard. The squares are fixed to the screen
+// rather than the image, so they hold still while panning and zooming.
+pub fn paint_checkerboard(painter: &egui::Painter, rect: egui::Rect, texture: &egui::TextureHandle) {
+    let tile = 2.0 * CELL;
+    let uv = egui::Rect::from_min_max(
+        (rect.min.to_vec2() / tile).to_pos2(),
+        (rect.max.to_vec2() / tile).to_pos2(),
+    );
+    painter.image(texture.id(), rect, uv, egui::Color32::WHITE);
+}
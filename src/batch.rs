@@ -0,0 +1,100 @@
+// Long-running operations over many files, run on a background thread.
+//
+// The worker thread sends progress messages over a channel and pokes egui
+// to repaint after each one; the UI thread drains the channel once per
+// frame in poll(). This keeps the window responsive while, say, a few
+// hundred JPEGs are re-encoded.
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+use eframe::egui;
+
+use crate::progress::Progress;
+
+enum Message {
+    // One file finished, successfully or not.
+    Progress { failure: Option<(PathBuf, String)> },
+    Finished,
+}
+
+pub struct BatchJob {
+    // Short description shown in the status bar, e.g. "Rotating".
+    label: String,
+    total: usize,
+    done: usize,
+    failures: Vec<(PathBuf, String)>,
+    finished: bool,
+    receiver: Receiver<Message>,
+}
+
+impl BatchJob {
+    // Run `work` on every path on a background thread. Errors from
+    // individual files are collected rather than aborting the batch.
+    pub fn spawn<F>(ctx: &egui::Context, label: &str, paths: Vec<PathBuf>, work: F) -> Self
+    where
+        F: Fn(&std::path::Path) -> Result<(), String> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let total = paths.len();
+        let ctx = ctx.clone();
+
+        std::thread::spawn(move || {
+            for path in paths {
+                let failure = work(&path).err().map(|e| (path, e));
+                // The receiver only goes away if the app is closing, in
+                // which case there's no point finishing the batch.
+                if sender.send(Message::Progress { failure }).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            let _ = sender.send(Message::Finished);
+            ctx.request_repaint();
+        });
+
+        Self {
+            label: label.to_string(),
+            total,
+            done: 0,
+            failures: Vec::new(),
+            finished: false,
+            receiver,
+        }
+    }
+
+    // Drain pending progress messages. Returns true once the job is done.
+    pub fn poll(&mut self) -> bool {
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                Message::Progress { failure } => {
+                    self.done += 1;
+                    if let Some(failure) = failure {
+                        log::warn!("{} {}: {}", self.label.to_lowercase(), failure.0.display(), failure.1);
+                        self.failures.push(failure);
+                    }
+                }
+                Message::Finished => self.finished = true,
+            }
+        }
+        self.finished
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress::new(self.done, self.total)
+    }
+
+    // e.g. "Rotating 12/40…"
+    pub fn status(&self) -> String {
+        format!("{} {}/{}…", self.label, self.done, self.total)
+    }
+
+    // e.g. "Rotating finished: 38 done, 2 failed"
+    pub fn summary(&self) -> String {
+        let succeeded = self.done - self.failures.len();
+        if self.failures.is_empty() {
+            format!("{} finished: {succeeded} done", self.label)
+        } else {
+            format!("{} finished: {succeeded} done, {} failed", self.label, self.failures.len())
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// A little nudge of the image when navigation runs into the end of the
+// folder, so pressing Next on the last image (with nothing set to happen
+// there) visibly does *something* instead of nothing at all.
+//
+// The image shifts a few points the way it was asked to go and springs
+// back. It's only an offset added to where the image is drawn; the pan
+// itself is untouched, so it can't leave the view shifted.
+use std::time::{Duration, Instant};
+
+const DURATION: Duration = Duration::from_millis(280);
+
+// How far the image moves at most, in points.
+const DISTANCE: f32 = 24.0;
+
+pub struct Bounce {
+    started: Instant,
+    // -1.0 to push left (Next was blocked), 1.0 to push right (Previous).
+    direction: f32,
+}
+
+impl Bounce {
+    // Next (forward) goes left, the way the images would scroll.
+    pub fn forward() -> Self {
+        Self { started: Instant::now(), direction: -1.0 }
+    }
+
+    pub fn backward() -> Self {
+        Self { started: Instant::now(), direction: 1.0 }
+    }
+
+    // The same bounce the other way, for reading right to left, where
+    // Next goes right.
+    pub fn mirrored(self) -> Self {
+        Self { direction: -self.direction, ..self }
+    }
+
+    // The horizontal offset to draw the image at now, or None once the
+    // bounce is over.
+    pub fn offset(&self) -> Option<f32> {
+        let t = self.started.elapsed().as_secs_f32() / DURATION.as_secs_f32();
+        if t >= 1.0 {
+            return None;
+        }
+        // Out fast and back slower: half a sine wave, fading as it goes.
+        Some(self.direction * DISTANCE * (std::f32::consts::PI * t).sin() * (1.0 - t))
+    }
+}
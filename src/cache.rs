@@ -0,0 +1,219 @@
+// Sharing decoded images between identical files, when it's turned on
+// (Settings::share_identical_files).
+//
+// The same photo often turns up in more than one folder: copied into an
+// album, exported twice, or on two backups of a camera card merged into
+// one session. Each copy would otherwise be decoded on its own as it
+// comes round. With this on, the loader first hashes the file's bytes and
+// looks for a recently decoded image with the same hash, whatever its
+// path, and hands over a copy of that instead of decoding again.
+//
+// Hashing means reading the whole file before decoding it, which on a
+// slow network drive is extra waiting, so it's off by default. Each
+// file's hash is remembered along with its size and modification time:
+// a file seen before isn't read again just to hash it, and a file that
+// has changed since is hashed afresh, so it can never match its old self.
+//
+// Only the most recently decoded images are kept, up to MAX_BYTES of
+// pixels between them. They're shared with the loader rather than copied
+// (see Decoded::pixels), so keeping one costs nothing until it's the only
+// thing left holding on to it. Animations aren't kept: their frames are
+// far bigger than their first frame suggests, and rarely duplicated.
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::loader::{DecodeOptions, Decoded};
+
+// How many bytes of decoded pixels are kept to be shared.
+const MAX_BYTES: usize = 256 * 1024 * 1024;
+
+// How many files' hashes are remembered. Past this the oldest are
+// forgotten, and hashed again if they come round.
+const MAX_HASHES: usize = 50_000;
+
+#[derive(Default)]
+pub struct ContentCache {
+    hashes: Mutex<Hashes>,
+    decoded: Mutex<Kept<(u64, DecodeOptions), Decoded>>,
+}
+
+// A file's hash, and how the file was when it was worked out.
+#[derive(Clone, Copy)]
+struct Stamped {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl ContentCache {
+    // The hash of the contents of the file at `path`, or None if it
+    // couldn't be read.
+    pub fn hash(&self, path: &Path) -> Option<u64> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let (len, modified) = (metadata.len(), metadata.modified().ok());
+        if let Some(hash) = self.hashes.lock().unwrap().get(path, len, modified) {
+            return Some(hash);
+        }
+        let hash = hash_file(path).ok()?;
+        self.hashes.lock().unwrap().insert(path, Stamped { len, modified, hash }, MAX_HASHES);
+        Some(hash)
+    }
+
+    // A copy of the image decoded from a file with this `hash`, the same
+    // way, if it's still kept.
+    pub fn get(&self, hash: u64, options: DecodeOptions) -> Option<Decoded> {
+        self.decoded.lock().unwrap().get(&(hash, options)).cloned()
+    }
+
+    pub fn insert(&self, hash: u64, options: DecodeOptions, decoded: &Decoded) {
+        if decoded.frames.is_some() {
+            return;
+        }
+        let bytes = decoded.pixels.as_raw().len();
+        self.decoded.lock().unwrap().insert((hash, options), decoded.clone(), bytes, MAX_BYTES);
+    }
+}
+
+// The hashes worked out so far, by path, and the order the paths were
+// first hashed in, to know which to forget first.
+#[derive(Default)]
+struct Hashes {
+    stamped: HashMap<PathBuf, Stamped>,
+    order: VecDeque<PathBuf>,
+}
+
+impl Hashes {
+    // The hash of `path`, if it was worked out while the file had this
+    // length and modification time.
+    fn get(&self, path: &Path, len: u64, modified: Option<SystemTime>) -> Option<u64> {
+        self.stamped.get(path).filter(|stamped| stamped.len == len && stamped.modified == modified).map(|stamped| stamped.hash)
+    }
+
+    fn insert(&mut self, path: &Path, stamped: Stamped, limit: usize) {
+        if self.stamped.insert(path.to_path_buf(), stamped).is_some() {
+            return;
+        }
+        self.order.push_back(path.to_path_buf());
+        while self.order.len() > limit
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.stamped.remove(&oldest);
+        }
+    }
+}
+
+// Values kept under a key, each with its size, most recently inserted
+// last, dropping the oldest to stay within a budget.
+struct Kept<K, V> {
+    entries: VecDeque<(K, V, usize)>,
+    bytes: usize,
+}
+
+impl<K, V> Default for Kept<K, V> {
+    fn default() -> Self {
+        Self { entries: VecDeque::new(), bytes: 0 }
+    }
+}
+
+impl<K: PartialEq, V> Kept<K, V> {
+    fn get(&self, key: &K) -> Option<&V> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, value, _)| value)
+    }
+
+    // Keep `value`, replacing anything under the same key. One bigger than
+    // the whole budget isn't kept at all, rather than emptying everything
+    // else out for it.
+    fn insert(&mut self, key: K, value: V, bytes: usize, budget: usize) {
+        if let Some(i) = self.entries.iter().position(|(k, _, _)| *k == key) {
+            let (_, _, old) = self.entries.remove(i).unwrap();
+            self.bytes -= old;
+        }
+        if bytes > budget {
+            return;
+        }
+        while self.bytes + bytes > budget
+            && let Some((_, _, oldest)) = self.entries.pop_front()
+        {
+            self.bytes -= oldest;
+        }
+        self.entries.push_back((key, value, bytes));
+        self.bytes += bytes;
+    }
+}
+
+// FNV-1a over the file's length and every byte in it. Not a cryptographic
+// hash, but plenty to tell photos apart, and quick to work out.
+fn hash_file(path: &Path) -> std::io::Result<u64> {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    let mut file = std::fs::File::open(path)?;
+    feed(&file.metadata()?.len().to_le_bytes());
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        feed(&buffer[..read]);
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_drops_the_oldest_to_stay_in_budget() {
+        let mut kept = Kept::default();
+        kept.insert(1, "a", 40, 100);
+        kept.insert(2, "b", 40, 100);
+        kept.insert(3, "c", 40, 100);
+        assert_eq!(kept.get(&1), None);
+        assert_eq!((kept.get(&2), kept.get(&3)), (Some(&"b"), Some(&"c")));
+        assert_eq!(kept.bytes, 80);
+        // Replacing a key frees what it held first.
+        kept.insert(2, "B", 60, 100);
+        assert_eq!((kept.get(&2), kept.get(&3)), (Some(&"B"), Some(&"c")));
+        assert_eq!(kept.bytes, 100);
+        kept.insert(3, "C", 50, 100);
+        assert_eq!((kept.get(&2), kept.get(&3)), (None, Some(&"C")));
+        assert_eq!(kept.bytes, 50);
+        // Too big for the whole budget: not kept, and nothing else dropped.
+        kept.insert(4, "huge", 101, 100);
+        assert_eq!((kept.get(&4), kept.get(&3)), (None, Some(&"C")));
+    }
+
+    #[test]
+    fn hashes_are_only_reused_for_an_unchanged_file() {
+        let mut hashes = Hashes::default();
+        let when = Some(SystemTime::UNIX_EPOCH);
+        hashes.insert(Path::new("a.jpg"), Stamped { len: 10, modified: when, hash: 7 }, 10);
+        assert_eq!(hashes.get(Path::new("a.jpg"), 10, when), Some(7));
+        assert_eq!(hashes.get(Path::new("a.jpg"), 11, when), None);
+        assert_eq!(hashes.get(Path::new("a.jpg"), 10, None), None);
+        assert_eq!(hashes.get(Path::new("b.jpg"), 10, when), None);
+    }
+
+    #[test]
+    fn hashes_forget_the_oldest_past_the_limit() {
+        let mut hashes = Hashes::default();
+        for (i, name) in ["a", "b", "c"].into_iter().enumerate() {
+            hashes.insert(Path::new(name), Stamped { len: 0, modified: None, hash: i as u64 }, 2);
+        }
+        // Hashing a remembered file again doesn't make it count twice.
+        hashes.insert(Path::new("c"), Stamped { len: 0, modified: None, hash: 9 }, 2);
+        assert_eq!(hashes.get(Path::new("a"), 0, None), None);
+        assert_eq!(hashes.get(Path::new("b"), 0, None), Some(1));
+        assert_eq!(hashes.get(Path::new("c"), 0, None), Some(9));
+        assert_eq!(hashes.order.len(), 2);
+    }
+}
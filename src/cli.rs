@@ -0,0 +1,137 @@
+// Command line arguments.
+//
+//     gallerust [--console] [--present] [IMAGE_OR_FOLDER... | URL]
+//     gallerust [--console] --quiet IMAGE
+//     gallerust [--console] --list IMAGE_OR_FOLDER...
+//     gallerust --version
+//
+// Kept deliberately simple: a handful of flags and some paths, so there's
+// no argument parsing library to pull in.
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use image::ImageFormat;
+
+use crate::{remote, scan};
+
+pub const USAGE: &str = "usage: gallerust [--console] [--present] [IMAGE_OR_FOLDER... | URL]\n       gallerust [--console] --quiet IMAGE\n       gallerust [--console] --list IMAGE_OR_FOLDER...\n       gallerust --version";
+
+pub struct Args {
+    // The images, folders or URL to open at startup. Several folders are
+    // merged into one sequence; a URL has to be on its own.
+    pub paths: Vec<PathBuf>,
+
+    // Show diagnostics in a console window on Windows (see attach_console).
+    pub console: bool,
+
+    // Print what this build can open (see version) and exit.
+    pub version: bool,
+
+    // Show the one image given and nothing else: no folder around it, no
+    // toolbar or panels, and no shortcuts beyond zooming, panning and
+    // fullscreen. For a quick look, or for other programs to show an
+    // image with.
+    pub quiet: bool,
+
+    // Start in presentation mode (see presentation.rs).
+    pub present: bool,
+
+    // Print the images in the given folders as JSON and exit, without
+    // opening a window (see listing.rs).
+    pub list: bool,
+}
+
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Args, String> {
+    let mut parsed = Args { paths: Vec::new(), console: false, version: false, quiet: false, present: false, list: false };
+    let mut only_paths = false;
+
+    for arg in args {
+        // Anything after "--" is a path, even if it starts with a dash.
+        if !only_paths && arg.to_str().is_some_and(|a| a.starts_with("--")) {
+            match arg.to_str().unwrap_or_default() {
+                "--" => only_paths = true,
+                "--console" => parsed.console = true,
+                "--version" => parsed.version = true,
+                "--quiet" => parsed.quiet = true,
+                "--present" => parsed.present = true,
+                "--list" => parsed.list = true,
+                other => return Err(format!("unknown option {other}")),
+            }
+            continue;
+        }
+
+        parsed.paths.push(PathBuf::from(arg));
+    }
+
+    let urls = parsed.paths.iter().filter(|p| p.to_str().is_some_and(remote::is_url)).count();
+    if urls > 0 && parsed.paths.len() > 1 {
+        return Err("a URL can't be opened together with other paths".to_string());
+    }
+    if parsed.quiet && (parsed.paths.len() != 1 || urls > 0) {
+        return Err("--quiet needs exactly one image file".to_string());
+    }
+    if parsed.quiet && parsed.present {
+        return Err("--quiet and --present can't be used together".to_string());
+    }
+    if parsed.list && (parsed.quiet || parsed.present) {
+        return Err("--list doesn't open a window, so it can't be used with --quiet or --present".to_string());
+    }
+    if parsed.list && (parsed.paths.is_empty() || urls > 0) {
+        return Err("--list needs one or more images or folders to list".to_string());
+    }
+
+    Ok(parsed)
+}
+
+// Gallerust's version, the image crate's, and which formats this build
+// can decode. The image crate leaves most formats to optional features, so
+// a build made with different ones than ours may be missing some; this is
+// the quickest way to tell that apart from a broken file.
+//
+// "Opens" is what the folder scan lists (see scan::FORMATS). The image
+// crate's other decoders are listed too, since they say what could be
+// added without pulling in anything new.
+pub fn version() -> String {
+    let names = |formats: &mut dyn Iterator<Item = ImageFormat>| {
+        let names: Vec<String> = formats.map(|format| format!("{format:?}").to_uppercase()).collect();
+        if names.is_empty() { "none".to_string() } else { names.join(", ") }
+    };
+    let mut text = format!(
+        "gallerust {}\nimage {}\nopens: {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("GALLERUST_IMAGE_VERSION"),
+        names(&mut scan::FORMATS.iter().copied().filter(|format| format.reading_enabled())),
+    );
+    if scan::FORMATS.iter().any(|format| !format.reading_enabled()) {
+        text += &format!(
+            "\nmissing from this build: {}",
+            names(&mut scan::FORMATS.iter().copied().filter(|format| !format.reading_enabled())),
+        );
+    }
+    text += &format!(
+        "\nother decoders built in: {}",
+        names(&mut ImageFormat::all().filter(|format| format.reading_enabled() && !scan::FORMATS.contains(format))),
+    );
+    text
+}
+
+// Release builds on Windows are GUI-subsystem programs (see the top of
+// main.rs), which means Windows gives them no console and anything written
+// to stderr silently disappears, even when started from a terminal. With
+// --console we attach to the terminal we were started from, or open a new
+// console window if there isn't one, so error messages can be read.
+#[cfg(target_os = "windows")]
+pub fn attach_console() {
+    use windows_sys::Win32::System::Console::{ATTACH_PARENT_PROCESS, AllocConsole, AttachConsole};
+    // SAFETY: both calls take no pointers and only fail if a console is
+    // already attached, in which case stderr already goes somewhere.
+    unsafe {
+        if AttachConsole(ATTACH_PARENT_PROCESS) == 0 {
+            AllocConsole();
+        }
+    }
+}
+
+// Everywhere else stderr is always visible, so there's nothing to do.
+#[cfg(not(target_os = "windows"))]
+pub fn attach_console() {}
@@ -0,0 +1,139 @@
+// Color management: showing images with an embedded ICC profile in the
+// colors they were meant to have, and what we assume about the ones
+// without.
+//
+// The numbers in an image file only mean something together with the
+// color space they're in. Almost everything is sRGB, which is what the
+// screen is assumed to show, so the numbers can go straight to it. But a
+// photo saved in Adobe RGB or Display P3 looks dull and slightly off as
+// raw numbers, and the profile embedded in the file says how to convert
+// them. We convert to sRGB, using moxcms, right after decoding.
+//
+// sRGB is as far as it goes: egui has no way to tell us the monitor's own
+// profile, so on a wide-gamut monitor the colors are right for an sRGB
+// monitor, which is what browsers do by default too.
+//
+// Only RGB and grayscale profiles are applied. A CMYK JPEG has already been
+// turned into RGB by the decoder by the time we see it, so its profile no
+// longer describes the pixels. Animations play their frames unconverted.
+//
+// Every decoded image goes through to_display on its way to the screen,
+// which is where the Policy below is applied. With Policy::Srgb:
+//
+// - an embedded profile is converted from, as above;
+// - floating point images (some TIFFs) are taken to hold linear light,
+//   which is what floating point samples conventionally mean, and given
+//   sRGB's gamma curve, instead of coming out far too dark;
+// - everything else is assumed to be sRGB already and passed through.
+//   That includes PNG's older gAMA and cHRM chunks, which the image
+//   crate doesn't hand on to us.
+//
+// Policy::Raw passes the decoded numbers through exactly as they are,
+// scaled to 8 bits, so it shows what the file literally contains.
+use image::{DynamicImage, RgbaImage};
+use moxcms::{ColorProfile, DataColorSpace, Layout, ProfileText, TransformOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::decode;
+use crate::metadata;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Policy {
+    // Convert everything we can to sRGB, so images look the same whatever
+    // format they came in.
+    Srgb,
+    // Show the decoded values untouched.
+    Raw,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 2] = [Policy::Srgb, Policy::Raw];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Policy::Srgb => "Convert to sRGB",
+            Policy::Raw => "Show the raw values",
+        }
+    }
+}
+
+// Turn a decoded image into the 8-bit sRGB pixels we show, following
+// `policy` (see the top of this file), and say what was done about its
+// embedded `profile` for the info panel. `name` is for the log.
+pub fn to_display(img: &DynamicImage, profile: Option<&[u8]>, policy: Policy, dither: bool, name: &str) -> (RgbaImage, Option<metadata::ColorProfile>) {
+    // A profile says what the numbers mean, so a floating point image with
+    // one follows it instead of the linear light assumption.
+    let linear = policy == Policy::Srgb && profile.is_none() && decode::is_floating_point(img);
+    let mut pixels = decode::to_rgba8(img, dither, linear);
+    let Some(icc) = profile else {
+        return (pixels, None);
+    };
+    let applied = policy == Policy::Srgb && match to_srgb(&mut pixels, icc) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("{name}: couldn't apply its color profile: {e}");
+            false
+        }
+    };
+    (pixels, Some(metadata::ColorProfile { name: describe(icc), applied }))
+}
+
+// sRGB's gamma curve, for a linear light value from 0 to 1.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// What an embedded profile is called, e.g. "Display P3", for the info
+// panel. Falls back to the kind of profile when it has no name.
+pub fn describe(icc: &[u8]) -> String {
+    let Ok(profile) = ColorProfile::new_from_slice(icc) else {
+        return "Unreadable".to_string();
+    };
+    let name = match &profile.description {
+        Some(ProfileText::PlainString(text)) => Some(text.clone()),
+        Some(ProfileText::Localizable(texts)) => texts.first().map(|text| text.value.clone()),
+        Some(ProfileText::Description(text)) => Some(text.ascii_string.clone()),
+        None => None,
+    };
+    match name.map(|name| name.trim_matches(char::from(0)).trim().to_string()) {
+        Some(name) if !name.is_empty() => name,
+        _ => format!("{:?} profile", profile.color_space),
+    }
+}
+
+// Convert `pixels` from the color space `icc` describes to sRGB, in place.
+// Transparency is left as it is.
+pub fn to_srgb(pixels: &mut RgbaImage, icc: &[u8]) -> Result<(), String> {
+    let profile = ColorProfile::new_from_slice(icc).map_err(|e| format!("{e:?}"))?;
+    let srgb = ColorProfile::new_srgb();
+    match profile.color_space {
+        DataColorSpace::Rgb => {
+            let transform = profile
+                .create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgba, TransformOptions::default())
+                .map_err(|e| format!("{e:?}"))?;
+            let source = pixels.as_raw().clone();
+            transform.transform(&source, pixels).map_err(|e| format!("{e:?}"))
+        }
+        // A grayscale image has been expanded to RGBA with equal channels,
+        // so any one of them is the gray value. moxcms's gray-with-alpha
+        // conversion mixes up its pixel sizes, so just the gray goes through
+        // it and the transparency is put back afterwards.
+        DataColorSpace::Gray => {
+            let transform = profile
+                .create_transform_8bit(Layout::Gray, &srgb, Layout::Rgba, TransformOptions::default())
+                .map_err(|e| format!("{e:?}"))?;
+            let gray: Vec<u8> = pixels.pixels().map(|p| p.0[0]).collect();
+            let alpha: Vec<u8> = pixels.pixels().map(|p| p.0[3]).collect();
+            transform.transform(&gray, pixels).map_err(|e| format!("{e:?}"))?;
+            for (pixel, alpha) in pixels.pixels_mut().zip(alpha) {
+                pixel.0[3] = alpha;
+            }
+            Ok(())
+        }
+        other => Err(format!("{other:?} profiles aren't supported")),
+    }
+}
@@ -0,0 +1,171 @@
+// Running other programs on the current image from a key of your own
+// choosing: "Ctrl+G runs gimp {path}", "U runs ~/bin/upload.sh {path}".
+//
+// This is about as far as extending Gallerust goes: there's no scripting
+// engine inside it, but any script or program can be hooked up to a key.
+// Each command is a line for the system shell (sh on Unix, cmd on
+// Windows), so pipes, redirection and `&&` all work, with {path} replaced
+// by the current file's path. The path is quoted for the shell, so it's
+// written without quotes of its own, and spaces and quotes in file names
+// are safe.
+//
+// Commands run in the background and Gallerust carries on. Their output
+// goes to the log (see RUST_LOG in the readme), and if one can't be
+// started or exits with an error, that's also shown in the toolbar.
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+// One binding, as stored in the settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyCommand {
+    // The key, with any modifiers in front, e.g. "E" or "Ctrl+Shift+E".
+    pub key: String,
+    // The shell command, with {path} for the current file.
+    pub command: String,
+}
+
+impl KeyCommand {
+    // The key combination `key` describes, or None if it doesn't name one.
+    // Ctrl means Cmd on a Mac, as with the built-in shortcuts. The plus
+    // key itself is spelled "Plus".
+    pub fn shortcut(&self) -> Option<(egui::Modifiers, egui::Key)> {
+        let mut parts: Vec<&str> = self.key.split('+').map(str::trim).collect();
+        let name = parts.pop()?;
+        let mut modifiers = egui::Modifiers::NONE;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => modifiers.command = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                _ => return None,
+            }
+        }
+        // egui's names are capitalized ("E", "F2", "Plus"), but nobody
+        // should have to remember that.
+        let capitalized: String = name.chars().take(1).flat_map(char::to_uppercase).chain(name.chars().skip(1)).collect();
+        let key = egui::Key::from_name(name)
+            .or_else(|| egui::Key::from_name(&name.to_ascii_uppercase()))
+            .or_else(|| egui::Key::from_name(&capitalized))?;
+        Some((modifiers, key))
+    }
+}
+
+pub struct Commands {
+    // Failures of commands that have finished since the last poll.
+    sender: Sender<String>,
+    receiver: Receiver<String>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver }
+    }
+
+    // The command whose key was pressed this frame, if any. The key press
+    // is used up, so a binding on a key Gallerust already uses replaces
+    // the built-in shortcut rather than doing both. Modifiers have to
+    // match exactly: a binding on E doesn't fire for Shift+E.
+    pub fn pressed<'a>(&self, ctx: &egui::Context, bindings: &'a [KeyCommand]) -> Option<&'a KeyCommand> {
+        ctx.input_mut(|i| {
+            for binding in bindings {
+                let Some((modifiers, key)) = binding.shortcut() else { continue; };
+                let index = i.events.iter().position(|event| {
+                    matches!(event, egui::Event::Key { key: k, pressed: true, modifiers: m, .. } if *k == key && m.matches_exact(modifiers))
+                });
+                if let Some(index) = index {
+                    i.events.remove(index);
+                    return Some(binding);
+                }
+            }
+            None
+        })
+    }
+
+    // Start `binding`'s command on `path`. Only failing to start it is
+    // reported here; how it ends is reported by poll.
+    pub fn run(&self, ctx: &egui::Context, binding: &KeyCommand, path: &Path) -> Result<(), String> {
+        let line = binding.command.replace("{path}", &quote(path));
+        log::info!("{}: running {line}", binding.key);
+        let child = shell(&line)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                log::error!("{}: couldn't run {line}: {e}", binding.key);
+                e.to_string()
+            })?;
+
+        let key = binding.key.clone();
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let failure = match child.wait_with_output() {
+                Ok(output) => {
+                    for line in String::from_utf8_lossy(&output.stdout).lines() {
+                        log::info!("{key}: {line}");
+                    }
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    for line in stderr.lines() {
+                        log::warn!("{key}: {line}");
+                    }
+                    // The last line of the error output usually says what
+                    // went wrong; the log has the rest.
+                    (!output.status.success()).then(|| match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+                        Some(last) => format!("{} ({})", output.status, last.trim()),
+                        None => output.status.to_string(),
+                    })
+                }
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(failure) = failure {
+                log::error!("{key}: command failed: {failure}");
+                let _ = sender.send(format!("The command on {key} failed: {failure}"));
+                ctx.request_repaint();
+            }
+        });
+        Ok(())
+    }
+
+    // The next failure of a finished command to report, if any.
+    pub fn poll(&self) -> Option<String> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+
+// cmd has its own idea of quoting, which the standard library's argument
+// escaping would only get in the way of, so the line is passed as is.
+#[cfg(target_os = "windows")]
+fn shell(line: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    let mut command = Command::new("cmd");
+    command.arg("/C").raw_arg(line);
+    command
+}
+
+// `path` as one word for the shell. In single quotes sh treats everything
+// literally, and a single quote itself is closed, escaped and reopened.
+#[cfg(not(target_os = "windows"))]
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+// Windows file names can't contain double quotes, so wrapping the path in
+// them is enough.
+#[cfg(target_os = "windows")]
+fn quote(path: &Path) -> String {
+    format!("\"{}\"", path.display())
+}
@@ -0,0 +1,203 @@
+// Cropping: drag out a rectangle over the image, fine-tune its edges with
+// the mouse or the keyboard, and save just that part of the image.
+//
+// The selection is kept in source pixels rather than screen points, so it
+// stays on the same part of the image while zooming and panning to check
+// an edge up close, and the size shown is exactly the size that's saved.
+use eframe::egui::{self, Align2, Color32, CursorIcon, Painter, Pos2, Rect, Stroke};
+
+use crate::{overlay, view};
+
+// How close the pointer has to be to an edge to grab it, in points.
+const GRAB_DISTANCE: f32 = 6.0;
+
+const HANDLE_SIZE: f32 = 8.0;
+const SHADE: Color32 = Color32::from_black_alpha(150);
+
+// A crop in progress.
+pub struct Crop {
+    // The image's size in pixels.
+    size: [u32; 2],
+    selection: Selection,
+    // The edges being moved by a mouse drag, while there is one.
+    grab: Option<Grab>,
+}
+
+// Left/right and top/bottom edges of the selection, as pixel boundaries
+// (see view::source_edge), always with [0] <= [1].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub x: [u32; 2],
+    pub y: [u32; 2],
+}
+
+impl Selection {
+    pub fn width(self) -> u32 {
+        self.x[1] - self.x[0]
+    }
+
+    pub fn height(self) -> u32 {
+        self.y[1] - self.y[0]
+    }
+
+    fn screen_rect(self, image_rect: Rect, size: [u32; 2]) -> Rect {
+        Rect::from_min_max(
+            view::screen_point(image_rect, size, [self.x[0], self.y[0]]),
+            view::screen_point(image_rect, size, [self.x[1], self.y[1]]),
+        )
+    }
+}
+
+// Which edges a drag moves: on each axis, the index into Selection::x or
+// Selection::y of the edge being dragged, or None to leave that axis alone.
+// A corner moves one edge on each axis.
+#[derive(Clone, Copy)]
+struct Grab {
+    x: Option<usize>,
+    y: Option<usize>,
+}
+
+impl Crop {
+    // Start with the whole image selected, so the keyboard alone can crop
+    // by pulling the edges in.
+    pub fn new(size: [u32; 2]) -> Self {
+        Self {
+            size,
+            selection: Selection { x: [0, size[0]], y: [0, size[1]] },
+            grab: None,
+        }
+    }
+
+    // The selected area, or None if it's empty or still the whole image,
+    // in which case there's nothing to crop.
+    pub fn selection(&self) -> Option<Selection> {
+        let s = self.selection;
+        let whole = s.width() == self.size[0] && s.height() == self.size[1];
+        (s.width() > 0 && s.height() > 0 && !whole).then_some(s)
+    }
+
+    // The selected area as fractions of the image's size, from 0 to 1, for
+    // zooming the view to it. None when there's nothing selected to zoom
+    // to, as for selection().
+    pub fn fraction(&self) -> Option<Rect> {
+        let s = self.selection()?;
+        let [width, height] = self.size.map(|side| side as f32);
+        Some(Rect::from_min_max(
+            Pos2::new(s.x[0] as f32 / width, s.y[0] as f32 / height),
+            Pos2::new(s.x[1] as f32 / width, s.y[1] as f32 / height),
+        ))
+    }
+
+    // A drag starting at `pos` grabs the edge or corner it starts on, or
+    // otherwise draws a new rectangle from there.
+    pub fn drag_started(&mut self, image_rect: Rect, pos: Pos2) {
+        if let Some(grab) = self.grab_at(image_rect, pos) {
+            self.grab = Some(grab);
+            return;
+        }
+        let [x, y] = view::source_edge(image_rect, self.size, pos);
+        self.selection = Selection { x: [x, x], y: [y, y] };
+        self.grab = Some(Grab { x: Some(1), y: Some(1) });
+    }
+
+    pub fn dragged(&mut self, image_rect: Rect, pos: Pos2) {
+        let Some(grab) = &mut self.grab else { return; };
+        let point = view::source_edge(image_rect, self.size, pos);
+        let selection = &mut self.selection;
+        for (edges, grabbed, to) in [(&mut selection.x, &mut grab.x, point[0]), (&mut selection.y, &mut grab.y, point[1])] {
+            let Some(i) = *grabbed else { continue; };
+            edges[i] = to;
+            // Dragged past the opposite edge: that edge is now the one
+            // being moved the other way, as in any drawing program.
+            if edges[0] > edges[1] {
+                edges.swap(0, 1);
+                *grabbed = Some(1 - i);
+            }
+        }
+    }
+
+    pub fn drag_stopped(&mut self) {
+        self.grab = None;
+    }
+
+    // Move the right and bottom edges by `delta` pixels, or the left and
+    // top edges with `near_edges`. An edge can't pass the one opposite it
+    // or leave the image.
+    pub fn nudge(&mut self, delta: [i64; 2], near_edges: bool) {
+        let selection = &mut self.selection;
+        for (edges, delta, size) in [(&mut selection.x, delta[0], self.size[0]), (&mut selection.y, delta[1], self.size[1])] {
+            if near_edges {
+                edges[0] = (edges[0] as i64 + delta).clamp(0, edges[1] as i64) as u32;
+            } else {
+                edges[1] = (edges[1] as i64 + delta).clamp(edges[0] as i64, size as i64) as u32;
+            }
+        }
+    }
+
+    // The resize cursor for the edge or corner under the pointer, if any.
+    pub fn cursor(&self, image_rect: Rect, pos: Pos2) -> Option<CursorIcon> {
+        let grab = self.grab.or_else(|| self.grab_at(image_rect, pos))?;
+        Some(match (grab.x, grab.y) {
+            (Some(0), Some(0)) | (Some(1), Some(1)) => CursorIcon::ResizeNwSe,
+            (Some(_), Some(_)) => CursorIcon::ResizeNeSw,
+            (Some(_), None) => CursorIcon::ResizeHorizontal,
+            _ => CursorIcon::ResizeVertical,
+        })
+    }
+
+    fn grab_at(&self, image_rect: Rect, pos: Pos2) -> Option<Grab> {
+        let rect = self.selection.screen_rect(image_rect, self.size);
+        if !rect.expand(GRAB_DISTANCE).contains(pos) {
+            return None;
+        }
+        let near = |edges: [f32; 2], at: f32| {
+            if (at - edges[0]).abs() <= GRAB_DISTANCE {
+                Some(0)
+            } else if (at - edges[1]).abs() <= GRAB_DISTANCE {
+                Some(1)
+            } else {
+                None
+            }
+        };
+        let grab = Grab {
+            x: near([rect.left(), rect.right()], pos.x),
+            y: near([rect.top(), rect.bottom()], pos.y),
+        };
+        (grab.x.is_some() || grab.y.is_some()).then_some(grab)
+    }
+
+    // Shade the parts of the image that will be cut away, and outline the
+    // selection with a handle on each corner and edge.
+    pub fn paint(&self, painter: &Painter, image_rect: Rect, text_size: f32) {
+        let rect = self.selection.screen_rect(image_rect, self.size);
+        for shade in [
+            Rect::from_min_max(image_rect.min, egui::pos2(image_rect.right(), rect.top())),
+            Rect::from_min_max(egui::pos2(image_rect.left(), rect.bottom()), image_rect.max),
+            Rect::from_min_max(egui::pos2(image_rect.left(), rect.top()), egui::pos2(rect.left(), rect.bottom())),
+            Rect::from_min_max(egui::pos2(rect.right(), rect.top()), egui::pos2(image_rect.right(), rect.bottom())),
+        ] {
+            painter.rect_filled(shade, 0.0, SHADE);
+        }
+        painter.rect_stroke(rect, 0.0, Stroke::new(1.5, Color32::WHITE));
+
+        for (i, x) in [rect.left(), rect.center().x, rect.right()].into_iter().enumerate() {
+            for (j, y) in [rect.top(), rect.center().y, rect.bottom()].into_iter().enumerate() {
+                if (i, j) == (1, 1) {
+                    continue;
+                }
+                let handle = Rect::from_center_size(egui::pos2(x, y), egui::Vec2::splat(HANDLE_SIZE));
+                painter.rect_filled(handle, 1.0, Color32::WHITE);
+            }
+        }
+
+        let s = self.selection;
+        overlay::draw_text(
+            painter,
+            rect.center_top() - egui::vec2(0.0, HANDLE_SIZE),
+            Align2::CENTER_BOTTOM,
+            &format!("{} × {}", s.width(), s.height()),
+            text_size,
+            Color32::WHITE,
+        );
+    }
+}
@@ -0,0 +1,255 @@
+// Reading image files, and turning a decoded image into the 8-bit RGBA
+// pixels we upload to the GPU.
+//
+// Cameras usually save photos in the sensor's orientation and record how
+// the camera was held in an EXIF Orientation tag, so a portrait shot is a
+// landscape image plus "rotate 90°". Everything that decodes a file goes
+// through open() or load_from_memory() below, which apply that tag, so the
+// pixels (and with them the dimensions, the fit and the thumbnails) are the
+// right way up everywhere.
+//
+// Most files are already 8 bits per channel and this is a plain conversion.
+// 16-bit PNGs/TIFFs and floating point images carry more precision than the
+// display can show, and simply truncating them to 8 bits can leave visible
+// steps ("banding") in smooth gradients like skies. Ordered dithering adds a
+// tiny, structured offset before rounding so those steps break up into a
+// fine pattern the eye averages out.
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, RgbaImage};
+
+use crate::color;
+
+// Like image::open(), but upright. The format comes from the file's first
+// few bytes where they're recognisable, and only falls back to the
+// extension otherwise, so a JPEG saved as ".png" still opens.
+pub fn open(path: &Path) -> ImageResult<DynamicImage> {
+    open_with_profile(path).map(|(img, _)| img)
+}
+
+// Like open(), along with the ICC color profile embedded in the file, if
+// it has one (see color.rs).
+pub fn open_with_profile(path: &Path) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    decode_upright(ImageReader::open(path)?.with_guessed_format()?.into_decoder()?)
+}
+
+// Like image::load_from_memory(), but upright, and with the embedded ICC
+// profile as for open_with_profile.
+pub fn load_from_memory(bytes: &[u8]) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    decode_upright(ImageReader::new(Cursor::new(bytes)).with_guessed_format()?.into_decoder()?)
+}
+
+fn decode_upright(mut decoder: impl ImageDecoder) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    // A malformed orientation tag or profile isn't worth failing the whole
+    // image over.
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let profile = decoder.icc_profile().ok().flatten();
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok((img, profile))
+}
+
+// An image's width and height, the right way up, read from its header
+// without decoding any pixels. For sorting by dimensions, which has to
+// look at every image in the folder.
+pub fn dimensions(path: &Path) -> ImageResult<[u32; 2]> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    // The image crate's JPEG decoder reads the whole file in before it
+    // looks at the header, which for a folder of 20 MB photos is most of
+    // the work of decoding them. The header is easy enough to find
+    // ourselves. Every other decoder only reads as far as it needs to.
+    if reader.format() == Some(ImageFormat::Jpeg)
+        && let Some(size) = jpeg_dimensions(&mut BufReader::new(File::open(path)?))
+    {
+        return Ok(size);
+    }
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let (width, height) = decoder.dimensions();
+    Ok(match orientation {
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH => [height, width],
+        _ => [width, height],
+    })
+}
+
+// Walk a JPEG's segments up to its frame header, which holds the size, and
+// pick up the EXIF orientation on the way (it's in an APP segment before
+// the frame header). None if the file ends or the image data starts first,
+// which the image crate then gets to report on.
+fn jpeg_dimensions(reader: &mut impl Read) -> Option<[u32; 2]> {
+    let mut byte = [0; 1];
+    let mut next = |reader: &mut dyn Read| reader.read_exact(&mut byte).ok().map(|()| byte[0]);
+    if (next(reader)?, next(reader)?) != (0xFF, 0xD8) {
+        return None;
+    }
+    let mut sideways = false;
+    loop {
+        if next(reader)? != 0xFF {
+            return None;
+        }
+        // Any number of 0xFF bytes can pad out the space before a marker.
+        let mut marker = next(reader)?;
+        while marker == 0xFF {
+            marker = next(reader)?;
+        }
+        // Markers without a segment after them.
+        if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+            continue;
+        }
+        let length = u16::from_be_bytes([next(reader)?, next(reader)?]) as usize;
+        let mut segment = vec![0; length.checked_sub(2)?];
+        reader.read_exact(&mut segment).ok()?;
+        match marker {
+            // SOF0 to SOF15, except the three that share the range: DHT,
+            // JPG and DAC. Precision, then height and width.
+            0xC0..=0xCF if ![0xC4, 0xC8, 0xCC].contains(&marker) => {
+                let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]) as u32;
+                let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]) as u32;
+                return Some(if sideways { [height, width] } else { [width, height] });
+            }
+            0xE1 if segment.starts_with(b"Exif\0\0") => {
+                // Orientations 5 to 8 all turn the image on its side.
+                let exif = exif::Reader::new().read_raw(segment[6..].to_vec()).ok();
+                let orientation = exif.as_ref()
+                    .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY))
+                    .and_then(|field| field.value.get_uint(0));
+                sideways = orientation.is_some_and(|orientation| (5..=8).contains(&orientation));
+            }
+            // Start of scan: the pixels, with no frame header before them.
+            0xDA => return None,
+            _ => {}
+        }
+    }
+}
+
+// 4x4 Bayer threshold matrix. Each entry is a rank in 0..16; neighbouring
+// pixels get very different ranks, which is what makes the pattern fine
+// grained rather than blotchy.
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+// Convert to 8-bit RGBA, dithering the color channels if requested and the
+// source actually has more than 8 bits of precision to lose. With `linear`
+// the color channels are taken to be linear light and given sRGB's gamma
+// curve on the way (see color::to_display).
+pub fn to_rgba8(img: &DynamicImage, dither: bool, linear: bool) -> RgbaImage {
+    let dither = dither && is_high_bit_depth(img);
+    if !dither && !linear {
+        return img.to_rgba8();
+    }
+
+    let src = img.to_rgba32f();
+    let (width, height) = src.dimensions();
+    let mut out = RgbaImage::new(width, height);
+
+    for (x, y, pixel) in src.enumerate_pixels() {
+        // Offset in the range (-0.5, 0.5) of one 8-bit step.
+        let offset = if dither {
+            let rank = BAYER_4X4[(y % 4) as usize][(x % 4) as usize];
+            (rank as f32 + 0.5) / 16.0 - 0.5
+        } else {
+            0.0
+        };
+
+        let [r, g, b, a] = pixel.0;
+        let encode = |v: f32| if linear { color::linear_to_srgb(v.max(0.0)) } else { v };
+        let quantize = |v: f32| (encode(v) * 255.0 + offset).round().clamp(0.0, 255.0) as u8;
+        // Alpha is left undithered; a noisy edge on a transparent
+        // region looks worse than a slightly stepped one.
+        let alpha = (a * 255.0).round().clamp(0.0, 255.0) as u8;
+        out.put_pixel(x, y, image::Rgba([quantize(r), quantize(g), quantize(b), alpha]));
+    }
+
+    out
+}
+
+// Make sure `pixels` can be drawn: egui panics on a texture whose buffer
+// isn't exactly width × height × 4 bytes, or that has no pixels at all.
+// The image crate lets a buffer be longer than its dimensions need, so a
+// mistake in a transform could leave one like that, which is a bug here
+// (hence the assertion) but still shouldn't take the window down with it.
+// An image with no pixels can come straight from a file.
+pub fn check_pixels(pixels: &RgbaImage) -> Result<(), String> {
+    let (width, height) = pixels.dimensions();
+    let expected = width as usize * height as usize * 4;
+    let actual = pixels.as_raw().len();
+    debug_assert_eq!(actual, expected, "{width}x{height} image with a buffer of the wrong size");
+    if width == 0 || height == 0 {
+        Err(format!("The image has no pixels ({width}×{height})"))
+    } else if actual != expected {
+        Err(format!("Decoded to {actual} bytes, but a {width}×{height} image has {expected}"))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_high_bit_depth(img: &DynamicImage) -> bool {
+    img.color().bytes_per_pixel() / img.color().channel_count() > 1
+}
+
+pub fn is_floating_point(img: &DynamicImage) -> bool {
+    matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A real 6x4 baseline JPEG, from the image crate's encoder.
+    fn baseline() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbImage::new(6, 4).write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg).unwrap();
+        bytes
+    }
+
+    // `jpeg` with a segment put in straight after its start marker.
+    fn with_segment(jpeg: &[u8], marker: u8, contents: &[u8]) -> Vec<u8> {
+        let length = (contents.len() + 2) as u16;
+        [&jpeg[..2], &[0xFF, marker], &length.to_be_bytes(), contents, &jpeg[2..]].concat()
+    }
+
+    fn size_of(bytes: &[u8]) -> Option<[u32; 2]> {
+        jpeg_dimensions(&mut Cursor::new(bytes))
+    }
+
+    #[test]
+    fn jpeg_size_from_the_frame_header() {
+        let jpeg = baseline();
+        assert_eq!(size_of(&jpeg), Some([6, 4]));
+        // The same header marked progressive (SOF2), which is all there is
+        // to tell them apart by before the scans.
+        let sof0 = jpeg.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        let mut progressive = jpeg.clone();
+        progressive[sof0 + 1] = 0xC2;
+        assert_eq!(size_of(&progressive), Some([6, 4]));
+        // Behind an application segment of someone else's.
+        assert_eq!(size_of(&with_segment(&jpeg, 0xE2, b"ICC_PROFILE\0 whatever")), Some([6, 4]));
+    }
+
+    #[test]
+    fn jpeg_size_turned_by_exif_orientation() {
+        // Big-endian TIFF with one IFD entry: Orientation (0x0112), SHORT,
+        // 1 value, 6 (turned a quarter clockwise).
+        let exif = [b"Exif\0\0MM\0\x2a\0\0\0\x08".as_slice(), &[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]].concat();
+        assert_eq!(size_of(&with_segment(&baseline(), 0xE1, &exif)), Some([4, 6]));
+    }
+
+    #[test]
+    fn truncated_jpeg_has_no_size() {
+        let jpeg = baseline();
+        let sof0 = jpeg.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        // Cut off anywhere before the frame header's width: no size, and
+        // no panic or endless loop either.
+        for end in 0..sof0 + 9 {
+            assert_eq!(size_of(&jpeg[..end]), None, "cut at {end}");
+        }
+        assert_eq!(size_of(b"not a jpeg"), None);
+    }
+}
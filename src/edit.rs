@@ -0,0 +1,234 @@
+// Operations that modify image files on disk.
+//
+// Everything in here works on paths rather than on the texture we're
+// displaying, so the same functions can be called from the UI thread for a
+// single image or from a background thread for a whole folder.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{self, PngEncoder};
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::{animation, decode, pages};
+
+// Encoder settings for when we write an image back to disk. Defaults lean
+// toward quality: rotating a photo shouldn't quietly recompress it at the
+// encoder's much lower default (75 for JPEG).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveQuality {
+    // 1-100. Re-encoding is lossy every time, so keep this high.
+    pub jpeg_quality: u8,
+    pub png_compression: PngCompression,
+}
+
+impl Default for SaveQuality {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 95,
+            png_compression: PngCompression::Balanced,
+        }
+    }
+}
+
+// PNG is always lossless; this only trades save time against file size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PngCompression {
+    Fast,
+    Balanced,
+    Smallest,
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] = [PngCompression::Fast, PngCompression::Balanced, PngCompression::Smallest];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PngCompression::Fast => "Fast",
+            PngCompression::Balanced => "Balanced",
+            PngCompression::Smallest => "Smallest file",
+        }
+    }
+
+    fn to_image(self) -> png::CompressionType {
+        match self {
+            PngCompression::Fast => png::CompressionType::Fast,
+            PngCompression::Balanced => png::CompressionType::Default,
+            PngCompression::Smallest => png::CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise,
+    CounterClockwise,
+    HalfTurn,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 3] = [Rotation::Clockwise, Rotation::CounterClockwise, Rotation::HalfTurn];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Rotation::Clockwise => "90° clockwise",
+            Rotation::CounterClockwise => "90° counter-clockwise",
+            Rotation::HalfTurn => "180°",
+        }
+    }
+
+    pub fn apply(self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::Clockwise => img.rotate90(),
+            Rotation::CounterClockwise => img.rotate270(),
+            Rotation::HalfTurn => img.rotate180(),
+        }
+    }
+}
+
+// A change to files on disk, which the settings may want confirmed before
+// it's made (see settings::Confirm).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Rotate(Rotation),
+    RotateAll(Rotation),
+    // Saving the crop selection over the file.
+    Crop,
+}
+
+impl Change {
+    // Whether Ctrl+Z can take it back (see undo.rs). A whole folder's
+    // worth of copies is more than undo keeps.
+    pub fn undoable(self) -> bool {
+        !matches!(self, Change::RotateAll(_))
+    }
+}
+
+// Rotate an image file and write it back over the original.
+//
+// The rotation is relative to how the image is shown, i.e. after any EXIF
+// orientation. We don't write EXIF back out, so the orientation is baked
+// into the saved pixels instead of being applied twice.
+pub fn rotate_file(path: &Path, rotation: Rotation, quality: SaveQuality) -> Result<(), String> {
+    let img = open_single_page(path)?;
+    let rotated = rotation.apply(&img);
+    save_replacing(&rotated, path, quality)
+}
+
+// Cut an image file down to the `width` x `height` area whose top-left
+// corner is at (`x`, `y`), and write it back over the original. Like
+// rotation, the coordinates are in the image as shown.
+pub fn crop_file(path: &Path, [x, y]: [u32; 2], [width, height]: [u32; 2], quality: SaveQuality) -> Result<(), String> {
+    let img = open_single_page(path)?;
+    save_replacing(&img.crop_imm(x, y, width, height), path, quality)
+}
+
+// Copy an image file to a new file next to it, "photo - copy.jpg", and
+// return the copy's path. If that name is taken the copies are numbered,
+// "photo - copy 2.jpg" and so on. The bytes are copied as they are,
+// nothing is decoded.
+//
+// The new name is claimed by creating the file before copying into it, so
+// two copies made at once (or a file appearing in between) can't end up
+// writing over each other or over something else.
+pub fn duplicate_file(path: &Path) -> Result<PathBuf, String> {
+    for number in 1..10_000 {
+        let copy = copy_name(path, number);
+        match File::options().write(true).create_new(true).open(&copy) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+        return match std::fs::copy(path, &copy) {
+            Ok(_) => Ok(copy),
+            Err(e) => {
+                let _ = std::fs::remove_file(&copy);
+                Err(e.to_string())
+            }
+        };
+    }
+    Err("too many copies already".to_string())
+}
+
+// The `number`th name for a copy of `path`, keeping its extension so the
+// copy opens the same way.
+fn copy_name(path: &Path, number: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(" - copy");
+    if number > 1 {
+        name.push(format!(" {number}"));
+    }
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+// Decode `path` for editing. We can only write one page back, so files
+// with more than one are refused rather than losing the rest, and the same
+// goes for an animation's frames.
+fn open_single_page(path: &Path) -> Result<DynamicImage, String> {
+    if pages::count(path) > 1 {
+        return Err("files with several pages can't be edited".to_string());
+    }
+    if animation::is_animated(path) {
+        return Err("animated images can't be edited".to_string());
+    }
+    decode::open(path).map_err(|e| e.to_string())
+}
+
+// Encode `img` in the format implied by `path`'s extension and replace the
+// file at `path` with it.
+//
+// The image is written to a temporary file next to the original and then
+// renamed over it, so a crash or a full disk halfway through the encode can
+// never leave a truncated file in place of the user's photo.
+pub fn save_replacing(img: &DynamicImage, path: &Path, quality: SaveQuality) -> Result<(), String> {
+    let format = ImageFormat::from_path(path).map_err(|e| e.to_string())?;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".gallerust-tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    if let Err(e) = encode(img, &tmp_path, format, quality) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e.to_string());
+    }
+
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        e.to_string()
+    })
+}
+
+// Formats with quality knobs get their own encoder; everything else uses
+// the image crate's defaults. (WebP in particular: the image crate only
+// has a lossless WebP encoder, so there's nothing to configure.)
+fn encode(img: &DynamicImage, path: &Path, format: ImageFormat, quality: SaveQuality) -> image::ImageResult<()> {
+    match format {
+        ImageFormat::Jpeg => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            let encoder = JpegEncoder::new_with_quality(&mut writer, quality.jpeg_quality.clamp(1, 100));
+            // JPEG has no alpha channel, so the encoder rejects RGBA input.
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)?;
+            writer.flush()?;
+            Ok(())
+        }
+        ImageFormat::Png => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            let encoder = PngEncoder::new_with_quality(
+                &mut writer,
+                quality.png_compression.to_image(),
+                png::FilterType::Adaptive,
+            );
+            img.write_with_encoder(encoder)?;
+            writer.flush()?;
+            Ok(())
+        }
+        _ => img.save_with_format(path, format),
+    }
+}
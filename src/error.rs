@@ -0,0 +1,50 @@
+// The ways getting started goes wrong, which the rest of the program needs
+// to tell apart rather than just show: each has its own exit code (see
+// exit_code).
+//
+// Everything that can fail once the window is open (loading an image, a
+// rotation, a crop, a copy) only ever ends up on screen, and stays a
+// plain message.
+use std::path::PathBuf;
+
+use crate::scan;
+
+// Exit codes, so scripts launching Gallerust can tell why it stopped.
+// 0 is a normal exit after the user closes the window.
+pub const EXIT_WINDOW_FAILED: u8 = 1; // the window or graphics context couldn't be created
+pub const EXIT_BAD_PATH: u8 = 2;      // the path argument doesn't exist or can't be read
+pub const EXIT_NO_IMAGES: u8 = 3;     // the path argument has no supported images
+pub const EXIT_USAGE: u8 = 4;         // unknown option or too many arguments
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    // Something wrong with the command line; the message says what.
+    #[error("{0}")]
+    Usage(String),
+    #[error("{}: --quiet opens a single image, not a folder", .0.display())]
+    QuietFolder(PathBuf),
+    #[error("{}: no such file or folder", .0.display())]
+    NoSuchPath(PathBuf),
+    #[error("{}: can't determine its folder", .0.display())]
+    NoFolder(PathBuf),
+    #[error("{}: {source}", path.display())]
+    Unreadable { path: PathBuf, source: std::io::Error },
+    #[error("{}: not a supported image (opens {})", .0.display(), scan::supported_formats())]
+    NotAnImage(PathBuf),
+    #[error("{}: no supported images found", .0.display())]
+    NoImages(PathBuf),
+    // eframe's error, as its message: it's only ever printed.
+    #[error("failed to create window: {0}")]
+    Window(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Usage(_) | Error::QuietFolder(_) => EXIT_USAGE,
+            Error::NoSuchPath(_) | Error::NoFolder(_) | Error::Unreadable { .. } => EXIT_BAD_PATH,
+            Error::NotAnImage(_) | Error::NoImages(_) => EXIT_NO_IMAGES,
+            Error::Window(_) => EXIT_WINDOW_FAILED,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Exporting the view: saving what's on screen as a PNG, composed the way
+// it's shown, rather than the image file itself. The zoom, pan, fit,
+// background, border and vignette all carry over, so a close-up of one
+// corner, or a transparent logo on its checkerboard, comes out as seen.
+//
+// It's drawn again on the CPU rather than read back from the screen, which
+// means the toolbar and panels never get in, and it can be drawn at a
+// multiple of the window's resolution for a sharper copy. The drawing
+// follows the GPU's: the layout is view::image_rect's, a shrunk image is
+// first averaged down (as in smoothing.rs) and then everything is sampled
+// bilinearly.
+use std::path::Path;
+
+use eframe::egui::{Pos2, Rect, Vec2};
+use image::{Rgba, RgbaImage};
+
+use crate::background::{self, Border};
+use crate::view::{self, Alignment, FitMode};
+use crate::vignette::Vignette;
+
+// Where the image sits in the view, as on screen. All in points.
+pub struct Framing {
+    pub view_size: Vec2,
+    pub zoom: f32,
+    pub fit: FitMode,
+    pub fit_percent: f32,
+    pub pan: Vec2,
+    pub alignment: Alignment,
+}
+
+// What's drawn around and behind the image, and the vignette over it all.
+pub struct Backdrop {
+    pub surround: [u8; 3],
+    pub checkerboard: bool,
+    pub border: Border,
+    pub vignette: Vignette,
+}
+
+// Draw `source` framed as on screen, at `scale` pixels per point.
+pub fn render(source: &RgbaImage, framing: &Framing, backdrop: &Backdrop, scale: f32) -> RgbaImage {
+    let img_size = Vec2::new(source.width() as f32, source.height() as f32);
+    let fit_area = view::fit_area(framing.view_size, framing.fit_percent);
+    let display = view::display_size(fit_area, img_size, framing.zoom, framing.fit);
+    let pan = view::clamp_pan(framing.view_size, display, framing.pan);
+    let rect = view::image_rect(Rect::from_min_size(Pos2::ZERO, framing.view_size), display, pan, framing.alignment);
+
+    // Shrunk on screen: average it down to about the size it's drawn at
+    // first, or bilinear sampling would skip most of its pixels.
+    let drawn = rect.size() * scale;
+    let shrunk;
+    let mut source = source;
+    if drawn.x < img_size.x / 2.0 && drawn.y < img_size.y / 2.0 {
+        shrunk = image::imageops::thumbnail(source, (drawn.x.ceil() as u32).max(1), (drawn.y.ceil() as u32).max(1));
+        source = &shrunk;
+    }
+
+    let size = (framing.view_size * scale).round();
+    let border = backdrop.border.show.then(|| rect.expand(backdrop.border.width));
+    RgbaImage::from_fn(size.x as u32, size.y as u32, |x, y| {
+        // The center of this output pixel, in points.
+        let pos = Pos2::new(x as f32 + 0.5, y as f32 + 0.5) / scale;
+        let pixel = if !rect.contains(pos) {
+            let [r, g, b] = match border {
+                Some(border) if border.contains(pos) => backdrop.border.color,
+                _ => backdrop.surround,
+            };
+            Rgba([r, g, b, 255])
+        } else {
+            let behind = if backdrop.checkerboard { [background::checker_gray(pos); 3] } else { backdrop.surround };
+            let uv = (pos - rect.min) / rect.size();
+            over(sample(source, uv), behind)
+        };
+        darken(pixel, backdrop.vignette.darkness(pos.to_vec2() / framing.view_size))
+    })
+}
+
+// `source` at `uv` (0 to 1 across the image), blending the four nearest
+// pixels like a GPU's linear filter. Blended premultiplied, so a fully
+// transparent pixel's color never bleeds into its neighbours.
+fn sample(source: &RgbaImage, uv: Vec2) -> [f32; 4] {
+    let x = (uv.x * source.width() as f32 - 0.5).clamp(0.0, (source.width() - 1) as f32);
+    let y = (uv.y * source.height() as f32 - 0.5).clamp(0.0, (source.height() - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(source.width() - 1), (y0 + 1).min(source.height() - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+
+    let mut sum = [0.0; 4];
+    for (px, py, weight) in [(x0, y0, (1.0 - fx) * (1.0 - fy)), (x1, y0, fx * (1.0 - fy)), (x0, y1, (1.0 - fx) * fy), (x1, y1, fx * fy)] {
+        let [r, g, b, a] = source.get_pixel(px, py).0.map(|c| c as f32 / 255.0);
+        sum[0] += r * a * weight;
+        sum[1] += g * a * weight;
+        sum[2] += b * a * weight;
+        sum[3] += a * weight;
+    }
+    sum
+}
+
+// A premultiplied color laid over an opaque one.
+fn over([r, g, b, a]: [f32; 4], behind: [u8; 3]) -> Rgba<u8> {
+    let [br, bg, bb] = behind.map(|c| c as f32 / 255.0);
+    let mix = |top: f32, below: f32| ((top + below * (1.0 - a)) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([mix(r, br), mix(g, bg), mix(b, bb), 255])
+}
+
+// `pixel` with black laid over it at `darkness` opacity, as the vignette's
+// shading is drawn on screen.
+fn darken(pixel: Rgba<u8>, darkness: f32) -> Rgba<u8> {
+    let [r, g, b, a] = pixel.0;
+    let shade = |c: u8| (c as f32 * (1.0 - darkness)).round() as u8;
+    Rgba([shade(r), shade(g), shade(b), a])
+}
+
+pub fn save(img: &RgbaImage, path: &Path) -> Result<(), String> {
+    img.save_with_format(path, image::ImageFormat::Png).map_err(|e| e.to_string())
+}
@@ -0,0 +1,60 @@
+// Kinetic panning: letting go of a drag while the image is still moving
+// sends it gliding on, slowing to a stop, the way a list scrolls on a
+// phone. Off by default (see Settings::kinetic_panning), since it makes
+// stopping at an exact spot harder, and never with reduced motion (see
+// motion.rs).
+//
+// The slowing down is worked out from the time between frames rather than
+// counted in frames, so the image glides the same distance at any frame
+// rate. It stops short at the image's edges.
+use std::time::Instant;
+
+use eframe::egui::Vec2;
+
+// The fraction of its speed the glide keeps after a second. The rest is
+// lost smoothly along the way, so it eases to a stop.
+const KEPT_PER_SECOND: f32 = 0.02;
+
+// Slower than this, in points a second, and it's over, rather than
+// creeping on for a pixel or two more.
+const MIN_SPEED: f32 = 30.0;
+
+pub struct Glide {
+    // In points a second.
+    velocity: Vec2,
+    last: Instant,
+}
+
+impl Glide {
+    // A glide at `velocity`, the pointer's speed as the drag was let go, if
+    // it was moving fast enough to be worth one.
+    pub fn start(velocity: Vec2) -> Option<Self> {
+        (velocity.length() >= MIN_SPEED).then(|| Self { velocity, last: Instant::now() })
+    }
+
+    // How far the image moves since the last step, or None once it's
+    // stopped.
+    pub fn step(&mut self) -> Option<Vec2> {
+        let now = Instant::now();
+        // A long pause (the window hidden, say) shouldn't fling it far.
+        let elapsed = now.duration_since(self.last).as_secs_f32().min(0.1);
+        self.last = now;
+        // The speed falls off exponentially, so the distance covered is
+        // its integral over the time that's passed.
+        let decay = KEPT_PER_SECOND.powf(elapsed);
+        let distance = self.velocity * (1.0 - decay) / -KEPT_PER_SECOND.ln();
+        self.velocity *= decay;
+        (self.velocity.length() >= MIN_SPEED).then_some(distance)
+    }
+
+    // Stop moving along the axes where the image has reached its edge, so
+    // a glide along one edge carries on along it.
+    pub fn stop_at_edges(&mut self, x: bool, y: bool) {
+        if x {
+            self.velocity.x = 0.0;
+        }
+        if y {
+            self.velocity.y = 0.0;
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// A 2x2 grid of the current image and the three after it, for picking the
+// best of a burst of similar shots at a glance.
+//
+// The current image keeps its usual texture in the top-left cell, so
+// stepping through the folder works as normal and simply slides the window
+// of four along. The other three are decoded here, on a thread each, and
+// shrunk on the way: a quarter of the screen never needs a full 40
+// megapixels, and keeping four full copies on the GPU would be wasteful.
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use eframe::egui;
+
+use crate::workers::{self, Priority};
+use crate::{color, decode};
+
+// Longest edge of an image shown in one of the other cells, in pixels.
+// Enough for a quarter of a 4K screen.
+const CELL_SIZE: u32 = 2048;
+
+pub enum Cell {
+    Loading,
+    Ready(egui::TextureHandle),
+    Failed(String),
+}
+
+pub struct Grid {
+    // The images in the three cells after the current one, in order. Fewer
+    // near the end of the folder.
+    cells: Vec<(PathBuf, Cell)>,
+    sender: Sender<(PathBuf, Result<egui::ColorImage, String>)>,
+    receiver: Receiver<(PathBuf, Result<egui::ColorImage, String>)>,
+}
+
+impl Grid {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { cells: Vec::new(), sender, receiver }
+    }
+
+    // Make the cells show `paths`. Images already in a cell are kept, so
+    // stepping forward only has to load the one new image at the end.
+    // They're converted for display by `policy`, like the current image.
+    pub fn show(&mut self, ctx: &egui::Context, paths: &[PathBuf], policy: color::Policy) {
+        if self.cells.iter().map(|(path, _)| path).eq(paths) {
+            return;
+        }
+        let mut old = std::mem::take(&mut self.cells);
+        for path in paths {
+            let cell = match old.iter().position(|(p, _)| p == path) {
+                Some(index) => old.swap_remove(index).1,
+                None => {
+                    self.load(ctx, path.clone(), policy);
+                    Cell::Loading
+                }
+            };
+            self.cells.push((path.clone(), cell));
+        }
+    }
+
+    fn load(&self, ctx: &egui::Context, path: PathBuf, policy: color::Policy) {
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _turn = workers::turn(Priority::Wanted);
+            let result = decode::open_with_profile(&path).map_err(|e| e.to_string()).map(|(img, profile)| {
+                let (mut pixels, _) = color::to_display(&img, profile.as_deref(), policy, false, &path.display().to_string());
+                let (width, height) = pixels.dimensions();
+                if width.max(height) > CELL_SIZE {
+                    let scale = CELL_SIZE as f32 / width.max(height) as f32;
+                    let size = [((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1)];
+                    pixels = image::imageops::thumbnail(&pixels, size[0], size[1]);
+                }
+                let size = [pixels.width() as usize, pixels.height() as usize];
+                egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_raw())
+            });
+            let _ = sender.send((path, result));
+            ctx.request_repaint();
+        });
+    }
+
+    // Upload whatever has finished decoding. Images that have since left
+    // the grid are dropped.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        while let Ok((path, result)) = self.receiver.try_recv() {
+            let Some((_, cell)) = self.cells.iter_mut().find(|(p, _)| p == &path) else { continue; };
+            *cell = match result {
+                Ok(image) => Cell::Ready(ctx.load_texture(format!("grid {}", path.display()), image, egui::TextureOptions::LINEAR)),
+                Err(e) => Cell::Failed(e),
+            };
+        }
+    }
+
+    pub fn cells(&self) -> &[(PathBuf, Cell)] {
+        &self.cells
+    }
+}
+
+// The four cells of `rect`, left to right and top to bottom, with a small
+// gap between them.
+pub fn quadrants(rect: egui::Rect, gap: f32) -> [egui::Rect; 4] {
+    let size = (rect.size() - egui::Vec2::splat(gap)) / 2.0;
+    let offset = size + egui::Vec2::splat(gap);
+    [
+        egui::Rect::from_min_size(rect.min, size),
+        egui::Rect::from_min_size(rect.min + egui::vec2(offset.x, 0.0), size),
+        egui::Rect::from_min_size(rect.min + egui::vec2(0.0, offset.y), size),
+        egui::Rect::from_min_size(rect.min + offset, size),
+    ]
+}
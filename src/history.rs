@@ -0,0 +1,67 @@
+// Browser-style Back/Forward through the images viewed this session.
+//
+// Previous/Next move through the folder in sort order; Back/Forward
+// retrace the order images were actually looked at, which is what you
+// want after jumping around with the filmstrip or a random pick. Entries
+// are paths rather than indices so they survive re-sorting, and can lead
+// back into a folder that has since been left.
+use std::path::{Path, PathBuf};
+
+// Oldest entries are dropped beyond this, so an all-day slideshow doesn't
+// grow the list forever.
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<PathBuf>,
+    // Index of the entry being shown. Only meaningful when entries isn't
+    // empty.
+    position: usize,
+    // The image being shown, and the one shown before it, however it was
+    // got to: Back and Forward move along the entries, but flipping
+    // between two images (Backspace) only needs these.
+    current: Option<PathBuf>,
+    previous: Option<PathBuf>,
+}
+
+impl History {
+    // Record that `path` is now being shown. Showing the entry we're
+    // already on (a reload, or arriving via back()/forward()) changes
+    // nothing; anything else drops the forward entries, like a browser.
+    pub fn visit(&mut self, path: &Path) {
+        if self.current.as_deref() != Some(path) {
+            self.previous = self.current.replace(path.to_path_buf());
+        }
+        if self.entries.get(self.position).is_some_and(|p| p == path) {
+            return;
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.position + 1);
+        }
+        self.entries.push(path.to_path_buf());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.position = self.entries.len() - 1;
+    }
+
+    // The image shown just before this one, to flip back to.
+    pub fn previous(&self) -> Option<&Path> {
+        self.previous.as_deref()
+    }
+
+    // Step back, returning the path to show, or None at the start.
+    pub fn back(&mut self) -> Option<&Path> {
+        self.position = self.position.checked_sub(1)?;
+        Some(&self.entries[self.position])
+    }
+
+    // Step forward, returning the path to show, or None at the end.
+    pub fn forward(&mut self) -> Option<&Path> {
+        if self.position + 1 >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        Some(&self.entries[self.position])
+    }
+}
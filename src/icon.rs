@@ -0,0 +1,125 @@
+// The window's icon, shown in the taskbar, the dock and the alt-tab
+// switcher.
+//
+// The default is drawn here in code rather than shipped as an image file,
+// so there's nothing to install alongside the binary and nothing to go
+// missing: a photo of mountains under a sun, on a rounded orange tile.
+// Settings can point at an image file to use instead.
+use std::path::Path;
+
+use eframe::egui;
+
+// Size of the default icon, in pixels. Window managers scale it down for
+// the title bar; 128 still looks sharp in a large dock.
+const SIZE: u32 = 128;
+
+// Custom icons larger than this are shrunk first. Nothing shows an icon
+// bigger, and some platforms reject huge ones.
+const MAX_CUSTOM_SIZE: u32 = 256;
+
+// Samples per pixel along each axis, for smooth edges.
+const SUPERSAMPLE: u32 = 4;
+
+pub fn window_icon(custom: Option<&Path>) -> egui::IconData {
+    match custom.map(load) {
+        Some(Ok(icon)) => icon,
+        Some(Err(e)) => {
+            log::warn!("couldn't use {} as the window icon: {e}", custom.unwrap_or(Path::new("")).display());
+            default_icon()
+        }
+        None => default_icon(),
+    }
+}
+
+fn load(path: &Path) -> Result<egui::IconData, String> {
+    let mut img = image::open(path).map_err(|e| e.to_string())?;
+    if img.width().max(img.height()) > MAX_CUSTOM_SIZE {
+        img = img.thumbnail(MAX_CUSTOM_SIZE, MAX_CUSTOM_SIZE);
+    }
+    let rgba = img.to_rgba8();
+    Ok(egui::IconData { width: rgba.width(), height: rgba.height(), rgba: rgba.into_raw() })
+}
+
+fn default_icon() -> egui::IconData {
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            // Average the shapes' colors over a grid of points inside the
+            // pixel. Alpha-weighted, so the tile's edge fades out cleanly
+            // instead of darkening.
+            let mut sum = [0.0f32; 4];
+            for sy in 0..SUPERSAMPLE {
+                for sx in 0..SUPERSAMPLE {
+                    let point = [
+                        (x as f32 + (sx as f32 + 0.5) / SUPERSAMPLE as f32) / SIZE as f32,
+                        (y as f32 + (sy as f32 + 0.5) / SUPERSAMPLE as f32) / SIZE as f32,
+                    ];
+                    if let Some([r, g, b]) = color_at(point) {
+                        sum[0] += r as f32;
+                        sum[1] += g as f32;
+                        sum[2] += b as f32;
+                        sum[3] += 1.0;
+                    }
+                }
+            }
+            let covered = sum[3];
+            if covered == 0.0 {
+                rgba.extend([0, 0, 0, 0]);
+            } else {
+                let alpha = covered / (SUPERSAMPLE * SUPERSAMPLE) as f32;
+                rgba.extend([
+                    (sum[0] / covered) as u8,
+                    (sum[1] / covered) as u8,
+                    (sum[2] / covered) as u8,
+                    (alpha * 255.0).round() as u8,
+                ]);
+            }
+        }
+    }
+    egui::IconData { rgba, width: SIZE, height: SIZE }
+}
+
+// The icon's color at `point`, in 0..1 across and down, or None where it's
+// transparent.
+fn color_at([x, y]: [f32; 2]) -> Option<[u8; 3]> {
+    const TILE: [u8; 3] = [0xE0, 0x5A, 0x2B];
+    const SKY: [u8; 3] = [0xFF, 0xF1, 0xDE];
+    const SUN: [u8; 3] = [0xF6, 0xB7, 0x26];
+    const FAR_HILL: [u8; 3] = [0x8A, 0x4B, 0x63];
+    const NEAR_HILL: [u8; 3] = [0x3B, 0x24, 0x3F];
+
+    if !in_rounded_rect([x, y], [0.04, 0.04], [0.96, 0.96], 0.2) {
+        return None;
+    }
+    // The photo: a lighter panel inset in the tile.
+    let (left, top, right, bottom) = (0.2, 0.24, 0.8, 0.76);
+    if !in_rounded_rect([x, y], [left, top], [right, bottom], 0.05) {
+        return Some(TILE);
+    }
+    // Across the photo from 0 to 1, and down it.
+    let u = (x - left) / (right - left);
+    let v = (y - top) / (bottom - top);
+    // Two hills, each a triangle with its peak at (peak, height) and
+    // sides sloping at `slope`.
+    let under = |peak: f32, height: f32, slope: f32| v > height + (u - peak).abs() * slope;
+    if under(0.68, 0.42, 1.1) {
+        return Some(NEAR_HILL);
+    }
+    if under(0.3, 0.3, 0.9) {
+        return Some(FAR_HILL);
+    }
+    if (u - 0.72).powi(2) + (v - 0.26).powi(2) < 0.12f32.powi(2) {
+        return Some(SUN);
+    }
+    Some(SKY)
+}
+
+fn in_rounded_rect([x, y]: [f32; 2], min: [f32; 2], max: [f32; 2], radius: f32) -> bool {
+    if x < min[0] || x > max[0] || y < min[1] || y > max[1] {
+        return false;
+    }
+    // Distance into the corner region, if the point is in one.
+    let dx = (min[0] + radius - x).max(x - (max[0] - radius)).max(0.0);
+    let dy = (min[1] + radius - y).max(y - (max[1] - radius)).max(0.0);
+    dx * dx + dy * dy <= radius * radius
+}
@@ -0,0 +1,69 @@
+// --list: what Gallerust would show from the given folders, as JSON on
+// stdout, for scripts and for checking over a folder without clicking
+// through it.
+//
+// The folders are scanned and sorted just as they would be for browsing,
+// with the saved settings, and each image is probed for its format and
+// size from its header. That's quick even for a big folder, and notices
+// most broken files: a file that's truncated further in only shows up
+// once it's decoded in full. The output is an array, in viewing order:
+//
+//     [
+//       { "path": "/photos/a.jpg", "format": "JPEG", "width": 4000, "height": 3000, "readable": true, "error": null },
+//       { "path": "/photos/b.png", "format": null, "width": null, "height": null, "readable": false, "error": "…" }
+//     ]
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::settings::Settings;
+use crate::{decode, metadata, scan};
+
+#[derive(Serialize)]
+struct Entry {
+    path: String,
+    // The format the contents are in, whatever the extension says.
+    format: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    readable: bool,
+    error: Option<String>,
+}
+
+// The JSON for the images in the folders of `paths` (the folder itself,
+// or the one a file is in), merged into one sequence as in the viewer.
+pub fn list(paths: &[PathBuf], settings: &Settings) -> Result<String, Error> {
+    let options = settings.scan_options();
+    let mut images = Vec::new();
+    for path in paths {
+        let folder = scan::folder_of(path).ok_or_else(|| Error::NoFolder(path.clone()))?;
+        let found = scan::scan_folder(folder, &options).map_err(|source| Error::Unreadable { path: folder.to_path_buf(), source })?;
+        for image in found {
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+    }
+    let mut keys = scan::SortKeys::default();
+    scan::sort_images(&mut images, settings.sort_order, settings.name_order, settings.include_subfolders, &mut keys);
+
+    let entries: Vec<Entry> = images.iter().map(|path| probe(path)).collect();
+    Ok(serde_json::to_string_pretty(&entries).expect("plain data always serializes"))
+}
+
+fn probe(path: &Path) -> Entry {
+    let format = scan::content_format(path).map(metadata::format_name);
+    let (size, error) = match decode::dimensions(path) {
+        Ok(size) => (Some(size), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    Entry {
+        path: path.display().to_string(),
+        format,
+        width: size.map(|[width, _]| width),
+        height: size.map(|[_, height]| height),
+        readable: error.is_none(),
+        error,
+    }
+}
@@ -0,0 +1,433 @@
+// Decoding images off the UI thread.
+//
+// A 40 megapixel JPEG can take a good fraction of a second to decode, and
+// doing that inside update() freezes the whole window. Instead each load
+// runs on its own thread and reports back over a channel, and the UI polls
+// for results once per frame.
+//
+// For large photos we also send an early, low resolution preview: most
+// cameras embed a small JPEG thumbnail in the EXIF data, which decodes in a
+// millisecond or two. Showing that (scaled up, so a little soft) while the
+// full image decodes makes big files feel instant. Formats without an
+// embedded thumbnail simply skip straight to the full image.
+//
+// The loader can also decode a couple of images ahead of time (see
+// prefetch.rs for which), so asking for one of those hands it over at
+// once instead of decoding it again.
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Instant, SystemTime};
+
+use eframe::egui;
+use image::RgbaImage;
+use image::metadata::Orientation;
+
+use crate::animation::{self, Frame};
+use crate::cache::ContentCache;
+use crate::color;
+use crate::decode;
+use crate::metadata::{self, ImageInfo};
+use crate::pages;
+use crate::remote;
+use crate::tiles;
+use crate::workers::{self, Priority};
+
+// Only bother with a preview above this many pixels; smaller images decode
+// quickly enough that a preview would just be a flash of blur.
+const PREVIEW_MIN_PIXELS: u64 = 8_000_000;
+
+pub enum LoadResult {
+    // A quick low resolution stand-in. The full image is still coming.
+    Preview(RgbaImage),
+    // The final result of the load.
+    Full(Result<Decoded, String>),
+}
+
+#[derive(Clone)]
+pub struct Decoded {
+    // Shared, so that a copy kept to share between identical files (see
+    // cache.rs) costs nothing.
+    pub pixels: Arc<RgbaImage>,
+    pub info: ImageInfo,
+    // How many pages the file has (see pages.rs). 1 for almost everything.
+    pub page_count: usize,
+    // The image's real size. Larger than `pixels` when it was shrunk to
+    // DecodeOptions::max_size.
+    pub full_size: [u32; 2],
+    // Every frame, for an animation (see animation.rs). `pixels` is then
+    // the first frame.
+    pub frames: Option<Vec<Frame>>,
+    // Whether the image is too big to decode whole, and `pixels` is an
+    // overview to draw tiles over (see tiles.rs).
+    pub tiled: bool,
+}
+
+// What to do with the decoded pixels. Copied into the worker thread, so it
+// must be cheap to clone.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    pub dither: bool,
+    // Shrink images far larger than this (in pixels) to about this size
+    // once decoded, for quicker browsing. See shrink_to.
+    pub max_size: Option<[u32; 2]>,
+    // What to do about color spaces (see color.rs).
+    pub color: color::Policy,
+}
+
+pub struct Loader {
+    // Incremented for every request, so results from a load the user has
+    // already navigated away from can be recognised and dropped.
+    generation: u64,
+    sender: Sender<(u64, PathBuf, LoadResult)>,
+    receiver: Receiver<(u64, PathBuf, LoadResult)>,
+    // The path currently being loaded, if the full image hasn't arrived yet.
+    pending: Option<PathBuf>,
+
+    // Images decoded ahead of time, waiting to be asked for.
+    prefetched: Vec<Prefetched>,
+    // What the last prefetch() asked for. Anything else that finishes is
+    // no longer wanted and is dropped.
+    wanted: Vec<PathBuf>,
+    // Paths a prefetch thread is working on, so they aren't started twice.
+    prefetching: Vec<PathBuf>,
+    prefetch_sender: Sender<Prefetched>,
+    prefetch_receiver: Receiver<Prefetched>,
+
+    // Decoded images shared between identical files, when that's on (see
+    // cache.rs).
+    cache: Option<Arc<ContentCache>>,
+}
+
+struct Prefetched {
+    path: PathBuf,
+    options: DecodeOptions,
+    // The file's modification time when it was read, so a file that has
+    // changed since isn't shown as it was.
+    modified: Option<SystemTime>,
+    result: Result<Decoded, String>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (prefetch_sender, prefetch_receiver) = mpsc::channel();
+        Self {
+            generation: 0,
+            sender,
+            receiver,
+            pending: None,
+            prefetched: Vec::new(),
+            wanted: Vec::new(),
+            prefetching: Vec::new(),
+            prefetch_sender,
+            prefetch_receiver,
+            cache: None,
+        }
+    }
+
+    // Turn sharing decoded images between identical files on or off.
+    pub fn share_identical_files(&mut self, on: bool) {
+        if on != self.cache.is_some() {
+            self.cache = on.then(|| Arc::new(ContentCache::default()));
+        }
+    }
+
+    // Start loading page `page` of `path`, superseding any load already in
+    // flight.
+    pub fn request(&mut self, ctx: &egui::Context, path: PathBuf, page: usize, options: DecodeOptions) {
+        self.start(ctx, path, page, options, true);
+    }
+
+    // Decode the image on screen again at full resolution, after it was
+    // shrunk for browsing. No preview first: the shrunk copy already on
+    // screen is better than the preview.
+    pub fn request_full_resolution(&mut self, ctx: &egui::Context, path: PathBuf, page: usize, options: DecodeOptions) {
+        self.start(ctx, path, page, DecodeOptions { max_size: None, ..options }, false);
+    }
+
+    fn start(&mut self, ctx: &egui::Context, path: PathBuf, page: usize, options: DecodeOptions, preview: bool) {
+        let prefetched = (page == 0).then(|| self.take_prefetched(&path, options)).flatten();
+        let (generation, sender, ctx) = self.begin(ctx, &path);
+        let cache = self.cache.clone();
+        if let Some(decoded) = prefetched {
+            log::debug!("{} was decoded ahead of time", path.display());
+            let _ = sender.send((generation, path, LoadResult::Full(Ok(decoded))));
+            ctx.request_repaint();
+            return;
+        }
+
+        std::thread::spawn(move || {
+            let send = |result| {
+                // Failing to send only means the app is shutting down.
+                let _ = sender.send((generation, path.clone(), result));
+                ctx.request_repaint();
+            };
+
+            let started = Instant::now();
+            // The embedded preview is of the first page.
+            if preview
+                && page == 0
+                && let Some(preview) = embedded_preview(&path)
+            {
+                log::debug!("preview for {} ready in {:.1?}", path.display(), started.elapsed());
+                send(LoadResult::Preview(preview));
+            }
+
+            let turn = workers::turn(Priority::Wanted);
+            let full = decode_shared(&path, page, options, cache.as_deref());
+            drop(turn);
+            if let Ok(decoded) = &full {
+                let (width, height) = decoded.pixels.dimensions();
+                log::debug!("decoded {} ({width}x{height}) in {:.1?}", path.display(), started.elapsed());
+            }
+            send(LoadResult::Full(full));
+        });
+    }
+
+    // Decode the first page of each of `paths` in the background, ready
+    // for a later request(), and forget any prefetched image that isn't
+    // among them.
+    pub fn prefetch(&mut self, paths: Vec<PathBuf>, options: DecodeOptions) {
+        self.collect_prefetched();
+        self.prefetched.retain(|prefetched| paths.contains(&prefetched.path) && prefetched.options == options);
+        for path in &paths {
+            if self.prefetching.contains(path) || self.prefetched.iter().any(|prefetched| &prefetched.path == path) {
+                continue;
+            }
+            self.prefetching.push(path.clone());
+            let sender = self.prefetch_sender.clone();
+            let path = path.clone();
+            let cache = self.cache.clone();
+            std::thread::spawn(move || {
+                let _turn = workers::turn(Priority::Ahead);
+                let started = Instant::now();
+                let modified = modified(&path);
+                let result = decode_shared(&path, 0, options, cache.as_deref());
+                log::trace!("prefetched {} in {:.1?}", path.display(), started.elapsed());
+                let _ = sender.send(Prefetched { path, options, modified, result });
+            });
+        }
+        self.wanted = paths;
+    }
+
+    // Drop anything prefetched from `path`, e.g. because it's being
+    // reloaded after a change made elsewhere.
+    pub fn forget(&mut self, path: &Path) {
+        self.collect_prefetched();
+        self.prefetched.retain(|prefetched| prefetched.path != path);
+    }
+
+    // Move finished prefetches over from their threads.
+    fn collect_prefetched(&mut self) {
+        while let Ok(prefetched) = self.prefetch_receiver.try_recv() {
+            self.prefetching.retain(|path| path != &prefetched.path);
+            // Failures aren't kept: loading the file for real reports the
+            // error as usual.
+            if self.wanted.contains(&prefetched.path) && prefetched.result.is_ok() {
+                self.prefetched.push(prefetched);
+            }
+        }
+    }
+
+    // The prefetched decode of `path`, if there's one that's still up to
+    // date. One that's still being decoded isn't waited for; the request
+    // starts over instead, which is no slower than if it hadn't been
+    // prefetched.
+    fn take_prefetched(&mut self, path: &Path, options: DecodeOptions) -> Option<Decoded> {
+        self.collect_prefetched();
+        let index = self.prefetched.iter().position(|prefetched| prefetched.path == path)?;
+        let prefetched = self.prefetched.remove(index);
+        if prefetched.options != options || prefetched.modified != modified(path) {
+            return None;
+        }
+        prefetched.result.ok()
+    }
+
+    // Download and decode the image at `url`. Results are reported under
+    // the URL as if it were a path, so the app can treat it like any other
+    // single image.
+    pub fn request_url(&mut self, ctx: &egui::Context, url: String, options: DecodeOptions) {
+        let path = PathBuf::from(&url);
+        let (generation, sender, ctx) = self.begin(ctx, &path);
+
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let full = remote::download(&url).and_then(|bytes| {
+                log::debug!("downloaded {url}: {} bytes in {:.1?}", bytes.len(), started.elapsed());
+                let _turn = workers::turn(Priority::Wanted);
+                let (img, profile) = decode::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+                let (pixels, color_profile) = color::to_display(&img, profile.as_deref(), options.color, options.dither, &url);
+                decode::check_pixels(&pixels)?;
+                let mut info = ImageInfo::from_bytes(&bytes, &pixels);
+                info.color_profile = color_profile;
+                info.color_type = Some(img.color());
+                let full_size = [pixels.width(), pixels.height()];
+                Ok(Decoded { pixels: Arc::new(pixels), info, page_count: 1, full_size, frames: None, tiled: false })
+            });
+            let _ = sender.send((generation, path, LoadResult::Full(full)));
+            ctx.request_repaint();
+        });
+    }
+
+    // Bookkeeping shared by every kind of request: supersede the current
+    // one and hand back what the worker thread needs to report in.
+    fn begin(&mut self, ctx: &egui::Context, path: &Path) -> (u64, Sender<(u64, PathBuf, LoadResult)>, egui::Context) {
+        self.generation += 1;
+        self.pending = Some(path.to_path_buf());
+        (self.generation, self.sender.clone(), ctx.clone())
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // The next result for the current request, if one has arrived.
+    // Results from superseded requests are discarded.
+    pub fn poll(&mut self) -> Option<(PathBuf, LoadResult)> {
+        while let Ok((generation, path, result)) = self.receiver.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+            if matches!(result, LoadResult::Full(_)) {
+                self.pending = None;
+            }
+            return Some((path, result));
+        }
+        None
+    }
+}
+
+// decode_page, but reusing an identical file's decoded image from `cache`
+// if there's one (see cache.rs). First pages only: the cache is for
+// browsing, and other pages are rarely duplicated on their own.
+fn decode_shared(path: &Path, page: usize, options: DecodeOptions, cache: Option<&ContentCache>) -> Result<Decoded, String> {
+    let Some((cache, hash)) = cache.filter(|_| page == 0).and_then(|cache| Some((cache, cache.hash(path)?))) else {
+        return decode_page(path, page, options);
+    };
+    if let Some(decoded) = cache.get(hash, options) {
+        log::debug!("{} is identical to an image decoded already", path.display());
+        return Ok(decoded);
+    }
+    let decoded = decode_page(path, page, options)?;
+    cache.insert(hash, options, &decoded);
+    Ok(decoded)
+}
+
+fn decode_page(path: &Path, page: usize, options: DecodeOptions) -> Result<Decoded, String> {
+    if page == 0
+        && let Some(overview) = tiles::overview(path, options)
+    {
+        let (pixels, full_size) = overview?;
+        decode::check_pixels(&pixels)?;
+        let info = ImageInfo::without_exif(path, &pixels);
+        return Ok(Decoded { pixels: Arc::new(pixels), info, page_count: pages::count(path), full_size, frames: None, tiled: true });
+    }
+    // An animation's first frame is the still, so it isn't decoded twice.
+    // Animations are played from their frames at full size and unconverted
+    // (see color.rs), so it isn't shrunk or converted either, which would
+    // only make it jump when playback starts.
+    if page == 0
+        && let Some(animation) = animation::decode_frames(path)
+    {
+        let pixels = animation.frames[0].pixels.clone();
+        decode::check_pixels(&pixels)?;
+        let mut info = ImageInfo::read(path, &pixels);
+        info.color_profile = animation.icc.map(|icc| metadata::ColorProfile { name: color::describe(&icc), applied: false });
+        info.color_type = Some(animation.color);
+        let full_size = [pixels.width(), pixels.height()];
+        return Ok(Decoded { pixels: Arc::new(pixels), info, page_count: pages::count(path), full_size, frames: Some(animation.frames), tiled: false });
+    }
+    // Pages after the first come from the tiff crate, which doesn't
+    // collect profiles.
+    let opened = match page {
+        0 => decode::open_with_profile(path).map_err(|e| e.to_string()),
+        _ => pages::open(path, page).map(|img| (img, None)),
+    };
+    opened.and_then(|(img, profile)| {
+        let full_size = [img.width(), img.height()];
+        let (mut pixels, color_profile) = color::to_display(&img, profile.as_deref(), options.color, options.dither, &path.display().to_string());
+        if let Some(max_size) = options.max_size {
+            pixels = shrink_to(pixels, max_size);
+        }
+        // Checked last, after everything that's done to the pixels.
+        decode::check_pixels(&pixels)?;
+        let mut info = ImageInfo::read(path, &pixels);
+        info.color_profile = color_profile;
+        info.color_type = Some(img.color());
+        Ok(Decoded { pixels: Arc::new(pixels), info, page_count: pages::count(path), full_size, frames: None, tiled: false })
+    })
+}
+
+// Shrink `pixels` if it's more than twice as big as needed to cover
+// `max_size`. Covering rather than fitting keeps every fit mode sharp at
+// zoom 1.0, fit to width included.
+//
+// The image crate can't decode straight to a smaller size (there's no
+// hook for a JPEG decoder's DCT scaling), so this still pays for the full
+// decode. What it saves is everything after: uploading to the GPU, the
+// histogram, and the memory for a full size copy, which for a 100
+// megapixel scan is most of the wait.
+fn shrink_to(pixels: RgbaImage, max_size: [u32; 2]) -> RgbaImage {
+    let (width, height) = pixels.dimensions();
+    let scale = (max_size[0] as f32 / width as f32).max(max_size[1] as f32 / height as f32);
+    if scale >= 0.5 {
+        return pixels;
+    }
+    let size = [((width as f32 * scale).ceil() as u32).max(1), ((height as f32 * scale).ceil() as u32).max(1)];
+    image::imageops::thumbnail(&pixels, size[0], size[1])
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// The thumbnail embedded in a large photo's EXIF data, if it has one.
+fn embedded_preview(path: &Path) -> Option<RgbaImage> {
+    // Reading the header is cheap and tells us whether the image is big
+    // enough for a preview to be worth it.
+    let (width, height) = image::image_dimensions(path).ok()?;
+    if (width as u64) * (height as u64) < PREVIEW_MIN_PIXELS {
+        return None;
+    }
+    // Finding the EXIF data means reading a whole TIFF into memory, which
+    // for one shown in tiles is what they're there to avoid.
+    if pages::is_tiff(path) && (width as u64) * (height as u64) >= tiles::MIN_PIXELS {
+        return None;
+    }
+
+    let file = std::fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    // The thumbnail lives in the second IFD as a byte range of the raw
+    // EXIF block, given as an offset and a length.
+    let offset = exif.get_field(exif::Tag::JPEGInterchangeFormat, exif::In::THUMBNAIL)?
+        .value.get_uint(0)? as usize;
+    let length = exif.get_field(exif::Tag::JPEGInterchangeFormatLength, exif::In::THUMBNAIL)?
+        .value.get_uint(0)? as usize;
+    let bytes = exif.buf().get(offset..offset.checked_add(length)?)?;
+
+    let mut thumbnail = image::load_from_memory(bytes).ok()?;
+
+    // Some cameras store a thumbnail with a different aspect ratio (e.g.
+    // letterboxed 4:3 for a 3:2 photo). Stretching it would make the swap
+    // to the full image jump visibly, so reject those.
+    let ratio = width as f32 / height as f32;
+    let thumb_ratio = thumbnail.width() as f32 / thumbnail.height() as f32;
+    if (ratio - thumb_ratio).abs() > 0.02 * ratio {
+        return None;
+    }
+
+    // The thumbnail is stored the same way up as the full image, so it
+    // needs the same orientation applied to match it.
+    let orientation = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .and_then(|value| Orientation::from_exif(value as u8))
+        .unwrap_or(Orientation::NoTransforms);
+    thumbnail.apply_orientation(orientation);
+
+    Some(thumbnail.to_rgba8())
+}
@@ -14,15 +14,172 @@ use egui::ColorImage;
 // We wrap it in Option because we don't have an image loaded at startup.
 use egui::TextureHandle;
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use rfd::FileDialog;
 
-fn main() -> eframe::Result<()> {
+mod animation;
+mod background;
+mod batch;
+mod bounce;
+mod cache;
+mod cli;
+mod color;
+mod commands;
+mod crop;
+mod decode;
+mod edit;
+mod error;
+mod export;
+mod glide;
+mod grid;
+mod history;
+mod icon;
+mod listing;
+mod loader;
+mod metadata;
+mod motion;
+mod notes;
+mod overlay;
+mod pages;
+mod prefetch;
+mod presentation;
+mod progress;
+mod remote;
+mod scan;
+mod session;
+mod settings;
+mod shuffle;
+mod slideshow;
+mod smoothing;
+mod text;
+mod thumbs;
+mod tiles;
+mod totals;
+mod undo;
+mod view;
+mod vignette;
+mod workers;
+
+use animation::Playback;
+use batch::BatchJob;
+use bounce::Bounce;
+use commands::Commands;
+use crop::Crop;
+use edit::{Change, Rotation};
+use error::Error;
+use glide::Glide;
+use grid::Grid;
+use history::History;
+use loader::{DecodeOptions, LoadResult, Loader};
+use metadata::ImageInfo;
+use prefetch::Direction;
+use presentation::Presentation;
+use progress::Progress;
+use session::Session;
+use settings::{EndAction, OtherFiles, Settings};
+use shuffle::Shuffle;
+use slideshow::Slideshow;
+use smoothing::Filter;
+use thumbs::Thumbnails;
+use tiles::Tiles;
+use totals::Totals;
+use undo::Undo;
+use view::FitMode;
+
+// Passed to run_native; eframe also names its storage folder after it.
+const APP_ID: &str = "Gallerust";
+
+// Width and height of one filmstrip cell, in points.
+const FILMSTRIP_CELL: f32 = 72.0;
+
+// Space between the cells of the comparison grid, in points.
+const GRID_GAP: f32 = 4.0;
+
+// Height of the position bar, in points.
+const POSITION_BAR_HEIGHT: f32 = 6.0;
+
+// The window's size the first time it opens without being maximized, in
+// points. After that it comes back the size it was left.
+const WINDOW_SIZE: [f32; 2] = [1280.0, 800.0];
+
+fn main() -> ExitCode {
+    // Attach the console before anything else, so even a complaint about
+    // the other arguments can be seen.
+    // --version and --list need one too, or their output goes nowhere.
+    if std::env::args_os().skip(1).any(|arg| arg == "--console" || arg == "--version" || arg == "--list") {
+        cli::attach_console();
+    }
+
+    // Diagnostics go through the log crate. By default only warnings and
+    // errors are shown, which is what used to be printed anyway; set
+    // RUST_LOG (e.g. RUST_LOG=gallerust=debug) to see load and scan
+    // timings, or =trace for every navigation.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("gallerust=warn")).init();
+    let args = match cli::parse(std::env::args_os().skip(1)).map_err(Error::Usage) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("gallerust: {e}");
+            eprintln!("{}", cli::USAGE);
+            return ExitCode::from(e.exit_code());
+        }
+    };
+    if args.version {
+        println!("{}", cli::version());
+        return ExitCode::SUCCESS;
+    }
+
+    // An optional image or folder to open straight away, e.g. from a file
+    // manager's "Open with" (the .desktop file passes it as %f).
+    // Made absolute so it compares equal to the paths the folder scan
+    // produces, which is how we find which image to start on.
+    // A URL is passed through untouched and fetched once the window is up.
+    let initial_paths: Vec<PathBuf> = args.paths.into_iter()
+        .map(|p| if is_url(&p) { p } else { std::path::absolute(&p).unwrap_or(p) })
+        .collect();
+
+    // Check the argument before creating a window, so a bad path fails
+    // fast with a message instead of opening an empty viewer.
+    let settings = Settings::load_before_window(APP_ID);
+    for path in initial_paths.iter().filter(|p| !is_url(p)) {
+        if let Err(e) = check_initial_path(path, &settings, args.quiet) {
+            eprintln!("gallerust: {e}");
+            return ExitCode::from(e.exit_code());
+        }
+    }
+    if args.list {
+        return match listing::list(&initial_paths, &settings) {
+            Ok(json) => {
+                println!("{json}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("gallerust: {e}");
+                ExitCode::from(e.exit_code())
+            }
+        };
+    }
+
     // NativeOptions configures the native window that eframe creates.
     let options = eframe::NativeOptions {
+        vsync: settings.vsync,
         viewport: egui::ViewportBuilder::default()
             .with_title("Gallerust")
-            .with_maximized(true),
+            .with_icon(icon::window_icon(settings.window_icon.as_deref()))
+            // Matches gallerust.desktop, so Linux desktops group the
+            // window under the installed launcher's name.
+            .with_app_id("gallerust")
+            .with_inner_size(WINDOW_SIZE)
+            .with_maximized(settings.start_maximized),
+        // A maximized window is maximized whatever size it was left, so
+        // eframe only needs to remember the size and position of one that
+        // isn't. (The last one it saved, if any, still gets restored under
+        // a maximized window, which is the size it goes back to when it's
+        // unmaximized.)
+        persist_window: !settings.start_maximized,
         ..Default::default()
     };
 
@@ -31,16 +188,63 @@ fn main() -> eframe::Result<()> {
     // 2. The window options
     // 3. A boxed closure that constructs our App struct
     // This call blocks and runs the event loop until the window is closed.
-    eframe::run_native(
-        "Gallerust",
+    let result = eframe::run_native(
+        APP_ID,
         options,
-        Box::new(|cc| {
+        Box::new(move |cc| {
             // Enable image support in egui. Without this, egui won't know
             // how to load image bytes into textures.
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Box::new(Gallerust::new())
+            Box::new(Gallerust::new(cc, initial_paths, args.quiet, args.present))
         }),
-    )
+    );
+
+    match result.map_err(|e| Error::Window(e.to_string())) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("gallerust: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(remote::is_url)
+}
+
+// Make sure the path given on the command line has something to show.
+// Hidden files count here: a folder of nothing but hidden images still
+// isn't an "empty folder" error. With --quiet the folder isn't looked at,
+// since it'll never be scanned. A file that isn't an image stands for its
+// folder if the settings say so (see settings::OtherFiles).
+fn check_initial_path(path: &Path, settings: &Settings, quiet: bool) -> Result<(), Error> {
+    let options = scan::ScanOptions {
+        skip_hidden: false,
+        sniff_content: settings.sniff_file_contents,
+        follow_folder_links: false,
+        include_subfolders: settings.include_subfolders,
+    };
+    if !path.exists() {
+        return Err(Error::NoSuchPath(path.to_path_buf()));
+    }
+    if path.is_file() && !scan::is_image(path, &options) && (quiet || settings.other_files == OtherFiles::Refuse) {
+        return Err(Error::NotAnImage(path.to_path_buf()));
+    }
+    if quiet {
+        if path.is_dir() {
+            return Err(Error::QuietFolder(path.to_path_buf()));
+        }
+        return Ok(());
+    }
+
+    let Some(folder) = scan::folder_of(path) else {
+        return Err(Error::NoFolder(path.to_path_buf()));
+    };
+    match scan::scan_folder(folder, &options) {
+        Err(source) => Err(Error::Unreadable { path: folder.to_path_buf(), source }),
+        Ok(images) if images.is_empty() => Err(Error::NoImages(folder.to_path_buf())),
+        Ok(_) => Ok(()),
+    }
 }
 
 // Our main application struct. Holds all state that persists between frames.
@@ -48,74 +252,416 @@ struct Gallerust {
     // Sorted list of image file paths found in the selected folder.
     images: Vec<PathBuf>,
 
+    // The folders `images` came from: usually one, more when extra folders
+    // have been merged into the session.
+    folders: Vec<PathBuf>,
+
     // Index into `images` for the currently displayed image.
     current_index: usize,
 
+    // Which page of the current file is shown, and how many it has. Only
+    // TIFFs have more than one (see pages.rs); page_count is 1 until the
+    // file has loaded and we know otherwise.
+    page: usize,
+    page_count: usize,
+
     // The current zoom level. 1.0 means "fit to available space".
     // We use a multiplicative zoom model now (see zoom section below)
-    // so 2.0 means twice the fit size, 0.5 means half, etc.
+    // so 2.0 means twice the fit size, 0.5 means half, etc. Kept within
+    // view::clamp_zoom's limits for the image and fit on screen.
     zoom: f32,
 
+    // How far the image is panned from center, in screen points. Only
+    // meaningful on an axis where the zoomed image is larger than the
+    // view; see view.rs for the exact rules.
+    pan: egui::Vec2,
+
+    // Set by reset_view until the pan has been put where the alignment
+    // says (see view::initial_pan), which needs the image's size on screen
+    // and so has to wait for it to load.
+    align_pending: bool,
+
+    // Set for a moment when Next or Previous hits the end of the folder
+    // (see bounce.rs).
+    bounce: Option<Bounce>,
+
+    // Some while a flung image is still gliding (see glide.rs).
+    glide: Option<Glide>,
+
+    // Whether the desktop asks for reduced motion, read at startup (see
+    // motion.rs), and how long egui's own animations take when it doesn't.
+    system_reduced_motion: bool,
+    animation_time: f32,
+
     // The GPU texture for the currently displayed image.
     // None means no image is loaded yet (before the user picks a file).
     texture: Option<TextureHandle>,
+
+    // The filter `texture` was last uploaded with: Nearest or Linear.
+    texture_filter: Filter,
+
+    // A smaller copy of the image with whole blocks of pixels averaged,
+    // drawn instead of `texture` when it's shrunk a long way, along with
+    // how many times it was halved (see smoothing.rs).
+    averaged_texture: Option<(u32, TextureHandle)>,
+
+    // A tiny tile drawn repeated behind transparent images.
+    checkerboard: TextureHandle,
+
+    // The decoded pixels behind `texture`. The GPU copy can't be read back
+    // cheaply, so we keep the CPU copy around for things like the
+    // eyedropper that need to look at individual pixels.
+    source_image: Option<Arc<image::RgbaImage>>,
+
+    // The current image's real size when `source_image` is a smaller copy
+    // (see Settings::reduced_decode), or None when it's the real thing.
+    reduced_from: Option<[u32; 2]>,
+
+    // Some when the current image is too big to decode whole, and
+    // `source_image` is only its overview (see tiles.rs).
+    tiles: Option<Tiles>,
+
+    // Some while the current image is an animation (see animation.rs).
+    playback: Option<Playback>,
+
+    // File details, EXIF and histogram for the current image, shown in
+    // the info panel. None until the full image has loaded.
+    info: Option<ImageInfo>,
+    // The image `info` is about. While the next image loads, the last
+    // one's info stays for its background color, but the panel mustn't
+    // show it under the new name.
+    info_for: Option<PathBuf>,
+
+    // When on, the mouse wheel and pinch gestures don't change the zoom, so
+    // incidental trackpad movement can't shift the image while looking.
+    zoom_locked: bool,
+
+    // When on, hovering the image shows the color of the source pixel
+    // under the cursor and clicking copies it as hex.
+    eyedropper: bool,
+
+    // The source pixel under the cursor this frame, as (x, y) and RGBA.
+    hovered_pixel: Option<([u32; 2], [u8; 4])>,
+
+    // Copies of files from before they were rotated or cropped (Ctrl+Z).
+    undo: Undo,
+
+    // Runs the user's own commands bound to keys (see commands.rs).
+    commands: Commands,
+
+    // When on, Next and Previous only visit flagged images (Shift+F).
+    flagged_only: bool,
+
+    // Some while comparing the current image with the three after it in
+    // a 2x2 grid (G; see grid.rs).
+    grid: Option<Grid>,
+
+    // Some while cropping the current image (see crop.rs). Dragging on the
+    // image and the arrow keys adjust the selection instead of panning and
+    // navigating until it's saved or cancelled.
+    crop: Option<Crop>,
+
+    // Some when showing an image downloaded from a URL rather than one
+    // from a local folder. `images` then holds just the URL, and anything
+    // that works on files (rotating, the filmstrip) is unavailable.
+    url: Option<String>,
+
+    // Started with --quiet: just the one image, fitted to the window, with
+    // no panels, overlays or navigation (see cli::Args::quiet).
+    quiet: bool,
+
+    // Set while in presentation mode (see presentation.rs).
+    presentation: Option<Presentation>,
+
+    // Since when the window has been minimized, while it is. The slideshow
+    // and animations stand still meanwhile (see track_visibility).
+    hidden_since: Option<Instant>,
+
+    // When the previous frame started, for the frame rate limit.
+    last_frame: Instant,
+
+    // The random order Next follows when shuffle is on. Made on first use
+    // and thrown away whenever the folder's contents or order change.
+    shuffle: Option<Shuffle>,
+
+    // The note attached to the current image (see notes.rs), if any.
+    note: Option<String>,
+
+    // While the note editor is open: the image being annotated and the
+    // text typed so far. The image is kept alongside the text so that
+    // navigating away mid-edit can't save the note onto the wrong image.
+    note_draft: Option<(PathBuf, String)>,
+
+    // Images viewed so far, for Back/Forward (Alt+Left/Alt+Right).
+    history: History,
+
+    // User preferences, loaded from and saved to eframe's storage.
+    settings: Settings,
+
+    // Where the user left off last time (fullscreen, etc.).
+    session: Session,
+
+    // Whether the settings window is open.
+    show_settings: bool,
+
+    // Some while a slideshow is running. Dropping it stops the slideshow
+    // and releases any keep-awake request it holds.
+    slideshow: Option<Slideshow>,
+
+    // Decodes images on background threads.
+    loader: Loader,
+
+    // Recent steps through the folder, to guess which images to decode
+    // ahead of time.
+    direction: Direction,
+
+    // The number and combined size of the open images, for the info
+    // panel. Refreshed whenever `images` changes.
+    totals: Totals,
+
+    // Image sizes read when sorting by them (see scan::Dimensions).
+    sort_keys: scan::SortKeys,
+
+    // Thumbnails for the filmstrip, cached on disk between launches.
+    thumbnails: Thumbnails,
+
+    // The image the filmstrip last scrolled to, so it only scrolls when
+    // the current image changes and otherwise leaves the user's own
+    // scrolling alone.
+    filmstrip_scrolled_to: Option<usize>,
+
+    // A background operation over many files, if one is running.
+    batch: Option<BatchJob>,
+
+    // A change to files the user has asked for and is being asked to
+    // confirm (see settings::Confirm).
+    pending_change: Option<Change>,
+
+    // Files in the current folder that have failed to decode so far (we
+    // only find out when we visit them), with the error.
+    // Kept so we can tell the user some images were skipped rather than
+    // failing silently. A BTreeMap keeps the listing in folder order.
+    failed: BTreeMap<PathBuf, String>,
+
+    // Whether the window listing the failed files is open.
+    show_failed: bool,
+
+    // The folder that was open when it disappeared from under us (deleted,
+    // or on a drive or share that's gone away), leaving nothing to show.
+    // The view then offers to try it again or open something else.
+    unavailable_folder: Option<PathBuf>,
+
+    // A one-line message shown in the toolbar, e.g. the outcome of the
+    // last batch operation. Cleared when a new folder is opened.
+    status_message: Option<String>,
 }
 
 impl Gallerust {
-    fn new() -> Self {
-        Self {
+    fn new(cc: &eframe::CreationContext<'_>, initial_paths: Vec<PathBuf>, quiet: bool, present: bool) -> Self {
+        let session = Session::load(cc.storage);
+
+        // The window has already been created, maximized or at its
+        // remembered size, by the time we get here, so fullscreen is
+        // restored on top of that. Leaving fullscreen then drops back to
+        // the window as it was.
+        if session.fullscreen {
+            cc.egui_ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+        }
+        // For file names and notes in scripts egui's own fonts don't cover
+        // (see text.rs).
+        text::load_fallback_fonts(&cc.egui_ctx);
+
+        let mut app = Self {
             images: Vec::new(),
+            folders: Vec::new(),
             current_index: 0,
+            page: 0,
+            page_count: 1,
             zoom: 1.0,
+            pan: egui::Vec2::ZERO,
             texture: None,
+            texture_filter: Filter::Linear,
+            averaged_texture: None,
+            checkerboard: background::checkerboard_texture(&cc.egui_ctx),
+            source_image: None,
+            reduced_from: None,
+            tiles: None,
+            playback: None,
+            info: None,
+            info_for: None,
+            zoom_locked: false,
+            eyedropper: false,
+            hovered_pixel: None,
+            undo: Undo::new(),
+            commands: Commands::new(),
+            crop: None,
+            url: None,
+            quiet,
+            presentation: None,
+            hidden_since: None,
+            last_frame: Instant::now(),
+            bounce: None,
+            glide: None,
+            system_reduced_motion: motion::system_prefers_reduced(),
+            animation_time: cc.egui_ctx.style().animation_time,
+            align_pending: false,
+            grid: None,
+            flagged_only: false,
+            shuffle: None,
+            note: None,
+            note_draft: None,
+            history: History::default(),
+            settings: Settings::load(cc.storage),
+            session,
+            show_settings: false,
+            slideshow: None,
+            totals: Totals::new(),
+            sort_keys: scan::SortKeys::default(),
+            loader: Loader::new(),
+            direction: Direction::default(),
+            thumbnails: Thumbnails::new(&cc.egui_ctx),
+            filmstrip_scrolled_to: None,
+            batch: None,
+            pending_change: None,
+            failed: BTreeMap::new(),
+            show_failed: false,
+            unavailable_folder: None,
+            status_message: None,
+        };
+
+        // The first path opens as usual and picks the starting image; any
+        // more are merged in after it.
+        app.loader.share_identical_files(app.settings.share_identical_files);
+        workers::set_limit(app.settings.decode_threads);
+        let mut paths = initial_paths.into_iter();
+        match paths.next() {
+            Some(path) if quiet => app.open_single(&cc.egui_ctx, path),
+            Some(path) if is_url(&path) => app.open_url(&cc.egui_ctx, path.to_string_lossy().into_owned()),
+            Some(path) => { app.open_path(&cc.egui_ctx, path); }
+            None => {}
+        }
+        for path in paths {
+            app.add_folder(&cc.egui_ctx, path);
+        }
+        if present {
+            app.start_presentation(&cc.egui_ctx);
         }
+        app
     }
 
     // Open a file picker dialog and load the selected image and its folder.
     fn open_file(&mut self, ctx: &egui::Context) {
         // Show a native OS file picker filtered to supported image types.
         // pick_file() blocks until the user makes a selection or cancels.
-        let Some(file) = FileDialog::new()
-            .add_filter("Images", &["jpg", "jpeg", "png", "gif", "webp", "bmp"])
+        let Some(file) = self.file_dialog()
+            .add_filter("Images", scan::EXTENSIONS)
             .pick_file()
         else {
             return; // User cancelled, do nothing
         };
 
+        self.open_path(ctx, file);
+    }
+
+    // A file dialog for opening things, starting in dialog_folder.
+    fn file_dialog(&self) -> FileDialog {
+        match self.dialog_folder() {
+            Some(folder) => FileDialog::new().set_directory(folder),
+            None => FileDialog::new(),
+        }
+    }
+
+    // Where the open dialogs start: the folder chosen in the settings, or
+    // else the one the most recent image was opened from. None, leaving it
+    // to the system, when that's not set or no longer there.
+    fn dialog_folder(&self) -> Option<PathBuf> {
+        let folder = match (&self.settings.dialog_folder, self.session.recent.first()) {
+            (Some(folder), _) => folder.clone(),
+            (None, Some(recent)) if recent.is_dir() => recent.clone(),
+            (None, Some(recent)) => recent.parent()?.to_path_buf(),
+            (None, None) => return None,
+        };
+        folder.is_dir().then_some(folder)
+    }
+
+    // Reopen an entry from the recents list. If it can't be opened any
+    // more (moved, deleted, drive not mounted) it's dropped from the list.
+    fn open_recent(&mut self, ctx: &egui::Context, path: PathBuf) {
+        if !self.open_path(ctx, path.clone()) {
+            self.session.recent.retain(|p| p != &path);
+            self.status_message = Some(format!("Couldn't open {}", path.display()));
+        }
+    }
+
+    // Open the next entry in the recents list after the one currently
+    // showing, wrapping around. Unlike picking from the menu this doesn't
+    // reorder the list, so repeated presses walk through every entry.
+    fn cycle_recent(&mut self, ctx: &egui::Context) {
+        if self.session.recent.is_empty() { return; }
+        // Match on folder rather than file, since the user has probably
+        // navigated away from the file they originally opened.
+        let current_folder = self.images.get(self.current_index).and_then(|p| p.parent());
+        let next = self.session.recent.iter()
+            .position(|p| {
+                let folder = if p.is_dir() { Some(p.as_path()) } else { p.parent() };
+                folder == current_folder
+            })
+            .map_or(0, |i| (i + 1) % self.session.recent.len());
+        let path = self.session.recent[next].clone();
+        if !self.open_path_without_remembering(ctx, path.clone()) {
+            self.session.recent.retain(|p| p != &path);
+        }
+    }
+
+    // Scan the folder containing `file` (or `file` itself if it's a folder)
+    // and show it, adding it to the front of the recents list. Returns
+    // false if nothing could be opened.
+    fn open_path(&mut self, ctx: &egui::Context, file: PathBuf) -> bool {
+        if !self.open_path_without_remembering(ctx, file.clone()) {
+            return false;
+        }
+        self.session.remember(file, self.settings.recent_limit);
+        true
+    }
+
+    fn open_path_without_remembering(&mut self, ctx: &egui::Context, file: PathBuf) -> bool {
         // Derive the folder from the selected file's parent directory so
         // we can browse all images in the same folder.
-        let Some(folder) = file.parent() else {
-            return;
+        let Some(folder) = scan::folder_of(&file) else {
+            return false;
         };
 
         // Use match instead of unwrap() so a folder read failure doesn't
         // crash the app. This can happen if a drive is ejected, permissions
         // change, or the path is on an unavailable network share.
-        let read_result = match std::fs::read_dir(folder) {
-            Ok(r) => r,
+        let options = self.settings.scan_options();
+        let mut images = match scan::scan_folder(folder, &options) {
+            Ok(images) => images,
             Err(e) => {
-                eprintln!("Failed to read folder: {e}");
-                return;
+                log::error!("{}: {e}", folder.display());
+                return false;
             }
         };
 
-        // Scan folder for supported image files, filtering by extension.
-        // to_lowercase() ensures .JPG and .jpg both match.
-        let mut images: Vec<PathBuf> = read_result
-            .filter_map(|entry| {
-                let path = entry.ok()?.path();
-                match path.extension()?.to_str()?.to_lowercase().as_str() {
-                    "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" => Some(path),
-                    _ => None,
+        // If the user explicitly picked a hidden file, show it anyway
+        // rather than silently opening a different image. Any other file
+        // that isn't an image opens its folder at the first image, if the
+        // settings allow.
+        if file.is_file() {
+            if scan::is_image(&file, &options) {
+                if !images.contains(&file) {
+                    images.push(file.clone());
                 }
-            })
-            .collect();
-
-        images.sort();
+            } else if self.settings.other_files == OtherFiles::Refuse {
+                log::warn!("{}: not a supported image", file.display());
+                return false;
+            }
+        }
+        scan::sort_images(&mut images, self.settings.sort_order, self.settings.name_order, self.settings.include_subfolders, &mut self.sort_keys);
 
         if images.is_empty() {
-            eprintln!("No supported images found in folder.");
-            return;
+            log::warn!("{}: no supported images found", folder.display());
+            return false;
         }
 
         // Start on the file the user actually picked rather than always
@@ -125,213 +671,2504 @@ impl Gallerust {
             .unwrap_or(0);
 
         self.images = images;
+        self.folders = vec![folder.to_path_buf()];
+        self.totals.refresh(ctx, &self.images);
+        self.direction.clear();
+        self.flagged_only = false;
+        self.unavailable_folder = None;
         self.current_index = current_index;
-        self.zoom = 1.0;
+        self.page = 0;
+        self.page_count = 1;
+        self.url = None;
+        self.reset_view();
+        self.status_message = None;
+        self.failed.clear();
+        self.thumbnails.clear();
+        self.filmstrip_scrolled_to = None;
+        self.shuffle = None;
         self.load_texture(ctx);
+        true
     }
 
-    // Load the image at current_index from disk and upload it to the GPU
-    // as an egui texture. egui handles scaling and rendering from here.
-    fn load_texture(&mut self, ctx: &egui::Context) {
-        let path = &self.images[self.current_index];
+    // Ask for a folder and merge its images into the session.
+    fn add_folder_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(folder) = self.file_dialog().pick_folder() {
+            self.add_folder(ctx, folder);
+        }
+    }
 
-        let img = match image::open(path) {
-            Ok(i) => i.to_rgba8(),
-            Err(e) => {
-                eprintln!("Failed to load image: {e}");
-                return;
-            }
+    // Merge the images in `path`'s folder into the ones already open, so
+    // several folders can be browsed as one sequence. The current image
+    // stays on screen. With nothing open yet this is just opening it.
+    fn add_folder(&mut self, ctx: &egui::Context, path: PathBuf) -> bool {
+        if self.images.is_empty() || self.url.is_some() {
+            return self.open_path(ctx, path);
+        }
+        let Some(folder) = scan::folder_of(&path).map(Path::to_path_buf) else {
+            return false;
         };
+        if self.folders.iter().any(|open| scan::same_folder(open, &folder)) {
+            self.status_message = Some(format!("{} is already open", folder.display()));
+            return true;
+        }
 
-        let (width, height) = img.dimensions();
-
-        // ColorImage is egui's CPU-side image type. We convert the raw RGBA
-        // bytes into Color32 values by chunking into groups of 4 bytes.
-        let pixels: Vec<egui::Color32> = img
-            .chunks_exact(4)
-            .map(|p| egui::Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
-            .collect();
-
-        let color_image = ColorImage {
-            size: [width as usize, height as usize],
-            pixels,
+        let images = match scan::scan_folder(&folder, &self.settings.scan_options()) {
+            Ok(images) => images,
+            Err(e) => {
+                log::error!("{}: {e}", folder.display());
+                self.status_message = Some(format!("Couldn't open {}: {e}", folder.display()));
+                return false;
+            }
         };
+        if images.is_empty() {
+            log::warn!("{}: no supported images found", folder.display());
+            self.status_message = Some(format!("No images in {}", folder.display()));
+            return false;
+        }
 
-        // Upload to GPU. TextureOptions::LINEAR gives smooth scaling
-        // (bilinear filtering) instead of blocky nearest-neighbor.
-        self.texture = Some(ctx.load_texture(
-            "current_image",
-            color_image,
-            egui::TextureOptions::LINEAR,
-        ));
+        let current = self.images[self.current_index].clone();
+        // The new folder may hold links to images that are already open,
+        // or be a link to an open folder under another name.
+        let before = self.images.len();
+        self.images.extend(images);
+        scan::remove_duplicate_files(&mut self.images);
+        let added = self.images.len() - before;
+        if added == 0 {
+            self.status_message = Some(format!("Everything in {} is already open", folder.display()));
+            return true;
+        }
+        scan::sort_images(&mut self.images, self.settings.sort_order, self.settings.name_order, self.settings.include_subfolders, &mut self.sort_keys);
+        self.current_index = self.images.iter().position(|p| p == &current).unwrap_or(0);
+        self.totals.refresh(ctx, &self.images);
+        self.shuffle = None;
+        self.status_message = Some(format!("Added {added} images from {}", folder.display()));
+        self.folders.push(folder);
+        true
     }
 
-    // Navigate to the next image, wrapping from last back to first.
-    fn go_next(&mut self, ctx: &egui::Context) {
-        if self.images.is_empty() { return; }
-        self.current_index = (self.current_index + 1) % self.images.len();
-        self.zoom = 1.0;
-        self.load_texture(ctx);
+    // Show the image at `url` on its own. Not added to the recent files,
+    // which are all local paths.
+    fn open_url(&mut self, ctx: &egui::Context, url: String) {
+        self.url = Some(url.clone());
+        self.open_single(ctx, PathBuf::from(url));
     }
 
-    // Navigate to the previous image, wrapping from first back to last.
-    fn go_prev(&mut self, ctx: &egui::Context) {
-        if self.images.is_empty() { return; }
-        // checked_sub prevents usize underflow at index 0
-        self.current_index = self.current_index
-            .checked_sub(1)
-            .unwrap_or(self.images.len() - 1);
-        self.zoom = 1.0;
+    // Show `path` as the only image there is, without scanning its folder:
+    // for a URL, or the image given with --quiet.
+    fn open_single(&mut self, ctx: &egui::Context, path: PathBuf) {
+        self.images = vec![path];
+        self.folders.clear();
+        self.current_index = 0;
+        self.page = 0;
+        self.page_count = 1;
+        self.unavailable_folder = None;
+        self.reset_view();
+        self.status_message = None;
+        self.failed.clear();
+        self.thumbnails.clear();
         self.load_texture(ctx);
     }
 
-    // Apply a multiplicative zoom delta, clamped to a safe range.
-    // This is used by both scroll wheel and pinch-to-zoom gestures.
-    // Multiplicative zoom feels more natural than additive because each
-    // step is proportional to the current zoom level — going from 1.0 to
-    // 2.0 feels the same as going from 2.0 to 4.0.
-    fn apply_zoom_delta(&mut self, delta: f32) {
-        self.zoom = (self.zoom * delta).clamp(0.1, 5.0);
+    // Re-sort the open folder after the sort order changes, staying on the
+    // same file rather than on whatever now happens to be at the same index.
+    fn resort(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() || self.url.is_some() { return; }
+        let current = self.images[self.current_index].clone();
+
+        // Files deleted since the folder was scanned would sort arbitrarily
+        // with no metadata to go on, so this is a good moment to drop them.
+        self.images.retain(|path| path.exists());
+        self.totals.refresh(ctx, &self.images);
+        scan::sort_images(&mut self.images, self.settings.sort_order, self.settings.name_order, self.settings.include_subfolders, &mut self.sort_keys);
+        self.shuffle = None;
+        self.reselect(ctx, &current);
     }
 
-    // Build the title string e.g. "cat.jpg (3/12)".
-    fn title(&self) -> String {
-        if self.images.is_empty() {
-            return "Gallerust".to_string();
+    // Scan the open folders again (Shift+F5) and bring `images` up to date
+    // with what's on disk now: new files are added where the sort order
+    // puts them, and deleted ones dropped. The folders aren't watched, so
+    // this is for when you know they've changed underneath. The current
+    // image stays selected, or the nearest one if it's gone.
+    fn refresh_listing(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() || self.url.is_some() || self.folders.is_empty() { return; }
+        let current = self.images[self.current_index].clone();
+
+        let options = self.settings.scan_options();
+        let mut images = Vec::new();
+        for folder in &self.folders {
+            match scan::scan_folder(folder, &options) {
+                Ok(found) => images.extend(found),
+                // Keep what we had from a folder that can't be read right
+                // now; if it's really gone, the next load notices.
+                Err(e) => {
+                    log::error!("{}: {e}", folder.display());
+                    images.extend(self.images.iter().filter(|p| p.starts_with(folder)).cloned());
+                }
+            }
         }
-        let filename = self.images[self.current_index]
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy();
-        format!("{} ({}/{})", filename, self.current_index + 1, self.images.len())
-    }
-}
+        // Like opening it, a hidden file that was picked explicitly stays,
+        // unless it's in a subfolder that's no longer included.
+        let in_open_folder = self.settings.include_subfolders || current.parent().is_some_and(|parent| self.folders.iter().any(|folder| folder == parent));
+        if current.is_file() && in_open_folder && !images.contains(&current) {
+            images.push(current.clone());
+        }
+        scan::remove_duplicate_files(&mut images);
 
-impl eframe::App for Gallerust {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (before, after): (HashSet<&PathBuf>, HashSet<&PathBuf>) = (self.images.iter().collect(), images.iter().collect());
+        let added = after.difference(&before).count();
+        let removed = before.difference(&after).count();
+        log::info!("refreshed the listing: {added} new, {removed} gone");
+        self.status_message = Some(match (added, removed) {
+            (0, 0) => "No changes in the folder".to_string(),
+            _ => format!("Listing refreshed: {added} new, {removed} gone"),
+        });
+        if added == 0 && removed == 0 {
+            return;
+        }
 
-        // ── Zoom input ───────────────────────────────────────────────────────
-        // We handle zoom at the top of update(), before the panels are drawn,
-        // so input is never missed regardless of what widget has focus.
+        self.images = images;
+        scan::sort_images(&mut self.images, self.settings.sort_order, self.settings.name_order, self.settings.include_subfolders, &mut self.sort_keys);
+        self.failed.retain(|path, _| path.exists());
+        self.totals.refresh(ctx, &self.images);
+        self.shuffle = None;
+        self.reselect(ctx, &current);
+    }
 
-        // zoom_delta() is egui's cross-platform normalized zoom input.
-        // It returns a multiplier: 1.1 = 10% larger, 0.9 = 10% smaller, 1.0 = no change.
-        // It handles both scroll wheels AND trackpad pinch-to-zoom gestures automatically,
-        // and egui normalizes the raw platform delta values for us so we don't have to
-        // worry about different mice or OSes reporting wildly different scroll magnitudes.
-        let zoom_delta = ctx.input(|i| i.zoom_delta());
-        if zoom_delta != 1.0 {
-            self.apply_zoom_delta(zoom_delta);
+    // Select `current` again after `images` has changed around it, or
+    // whatever is now nearest to where it was if it's gone.
+    fn reselect(&mut self, ctx: &egui::Context, current: &Path) {
+        match self.images.iter().position(|p| p == current) {
+            Some(index) => self.current_index = index,
+            // The current file itself was deleted: show whatever is nearest
+            // to where it used to be, or nothing if the folder is now empty.
+            None if self.images.is_empty() => {
+                self.current_index = 0;
+                self.texture = None;
+                self.averaged_texture = None;
+                self.source_image = None;
+                self.reduced_from = None;
+                self.tiles = None;
+                self.playback = None;
+                self.info = None;
+                self.status_message = Some("No images left in this folder".to_string());
+            }
+            None => {
+                self.current_index = self.current_index.min(self.images.len() - 1);
+                self.page = 0;
+                self.page_count = 1;
+                self.reset_view();
+                self.load_texture(ctx);
+            }
         }
+    }
 
-        // Keyboard zoom uses the same multiplicative model for consistency.
-        // 1.1 and 0.9 match what a single scroll notch typically produces,
-        // so keyboard and scroll wheel feel equivalent.
-        if ctx.input(|i| i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals)) {
-            self.apply_zoom_delta(1.1);
+    // Start loading the image at current_index. Decoding happens on a
+    // background thread (see loader.rs); poll_loader() uploads the result
+    // to the GPU when it arrives. Until then the previous image stays on
+    // screen, which avoids a black flash between quick-to-decode images.
+    fn load_texture(&mut self, ctx: &egui::Context) {
+        // A selection only makes sense on the image it was drawn on, and
+        // an animation stops here rather than playing on over the wait
+        // (see animation.rs).
+        self.crop = None;
+        self.playback = None;
+        log::trace!("showing {} ({} of {})", self.images[self.current_index].display(), self.current_index + 1, self.images.len());
+        self.history.visit(&self.images[self.current_index]);
+        self.note = match self.url {
+            Some(_) => None,
+            None if self.quiet => None,
+            None => notes::load(&self.images[self.current_index]),
+        };
+        let options = self.decode_options(ctx);
+        match &self.url {
+            Some(url) => self.loader.request_url(ctx, url.clone(), options),
+            None => self.loader.request(ctx, self.images[self.current_index].clone(), self.page, options),
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Minus)) {
-            self.apply_zoom_delta(0.9);
+    }
+
+    fn decode_options(&self, ctx: &egui::Context) -> DecodeOptions {
+        // The whole window, to leave room for the panels being hidden.
+        let window = ctx.screen_rect().size() * ctx.pixels_per_point();
+        DecodeOptions {
+            dither: self.settings.dither,
+            max_size: self.settings.reduced_decode.then_some([window.x as u32, window.y as u32]),
+            color: self.settings.color_policy,
         }
+    }
 
-        // ── Bottom toolbar panel ─────────────────────────────────────────────
-        // Panels claim space from the edges inward. Bottom panel is declared
-        // first so the central panel fills the remaining space above it.
-        egui::TopBottomPanel::bottom("toolbar")
-            .exact_height(48.0)
-            .show(ctx, |ui| {
-                ui.horizontal_centered(|ui| {
+    // The current image's size in pixels, however large the copy we have
+    // of it is.
+    fn image_size(&self) -> Option<[u32; 2]> {
+        self.reduced_from.or_else(|| self.source_image.as_ref().map(|img| [img.width(), img.height()]))
+    }
 
-                    if ui.button("📂 Open").clicked() {
-                        self.open_file(ctx);
-                    }
+    // Swap a shrunk copy of the current image for the real thing, once
+    // it's zoomed past the copy's detail or looked at pixel by pixel. Not
+    // for an image shown in tiles, whose real thing wouldn't fit in memory.
+    fn load_full_resolution(&mut self, ctx: &egui::Context) {
+        if self.reduced_from.is_none() || self.tiles.is_some() || self.loader.is_loading() || self.url.is_some() {
+            return;
+        }
+        log::debug!("decoding {} at full resolution", self.images[self.current_index].display());
+        let options = self.decode_options(ctx);
+        self.loader.request_full_resolution(ctx, self.images[self.current_index].clone(), self.page, options);
+    }
 
-                    ui.separator();
+    // Start decoding the images the user is likely to go to next (see
+    // prefetch.rs). Not for shuffling, where the next image could be
+    // anywhere, or a URL, which is on its own.
+    fn prefetch_neighbors(&mut self, ctx: &egui::Context) {
+        let paths = if self.settings.prefetch && self.url.is_none() && !self.settings.shuffle {
+            let wrap = self.settings.end_action == EndAction::Loop;
+            self.direction.targets(self.current_index, self.images.len(), wrap)
+                .into_iter()
+                .map(|index| self.images[index].clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.loader.prefetch(paths, self.decode_options(ctx));
+    }
 
-                    if ui.button("◀ Prev").clicked() {
-                        self.go_prev(ctx);
+    // Pick up any finished decode and put it on screen.
+    fn poll_loader(&mut self, ctx: &egui::Context) {
+        while let Some((path, result)) = self.loader.poll() {
+            match result {
+                // The preview is only a stand-in for display. We don't keep
+                // its pixels, so the eyedropper stays off until the real
+                // image arrives rather than reporting blurry colors.
+                LoadResult::Preview(img) => {
+                    if let Err(e) = self.upload_texture(ctx, &img) {
+                        log::warn!("couldn't show the preview of {}: {e}", path.display());
+                        continue;
                     }
-                    if ui.button("Next ▶").clicked() {
-                        self.go_next(ctx);
+                    self.source_image = None;
+                    self.reduced_from = None;
+                    self.tiles = None;
+                    self.playback = None;
+                    self.info = None;
+                }
+                LoadResult::Full(Ok(decoded)) => {
+                    if let Err(e) = self.upload_texture(ctx, &decoded.pixels) {
+                        self.load_failed(ctx, path, e);
+                        continue;
                     }
+                    self.failed.remove(&path);
+                    let size = [decoded.pixels.width(), decoded.pixels.height()];
+                    self.reduced_from = (decoded.full_size != size).then_some(decoded.full_size);
+                    self.tiles = decoded.tiled.then(|| Tiles::new(ctx, &path, decoded.full_size, size, self.decode_options(ctx)));
+                    self.playback = decoded.frames.map(Playback::new);
+                    self.source_image = Some(decoded.pixels);
+                    self.info = Some(decoded.info);
+                    self.info_for = Some(path.clone());
+                    self.page_count = decoded.page_count;
+                    self.image_shown();
+                    // Only now, so decoding ahead never holds up the image
+                    // that's actually wanted.
+                    self.prefetch_neighbors(ctx);
+                }
+                LoadResult::Full(Err(e)) => self.load_failed(ctx, path, e),
+            }
+        }
+    }
 
-                    ui.separator();
-
-                    ui.label(self.title());
-
-                    ui.separator();
-
-                    // Zoom slider. Because we now use a multiplicative zoom model,
-                    // the slider still works fine — it just directly sets self.zoom
-                    // to whatever value the user drags to.
-                    ui.label("Zoom:");
-                    ui.add(
-                        egui::Slider::new(&mut self.zoom, 0.1..=5.0)
-                            .step_by(0.1)
-                            .fixed_decimals(1)
-                    );
-
-                    if ui.button("↺").on_hover_text("Reset zoom").clicked() {
-                        self.zoom = 1.0;
-                    }
-                });
-            });
+    // `path` couldn't be loaded or shown. Clear the texture rather than
+    // leaving the previous image up, which would look like the new file
+    // had loaded.
+    fn load_failed(&mut self, ctx: &egui::Context, path: PathBuf, error: String) {
+        // Not a problem with the file if its whole folder has gone.
+        if self.url.is_none()
+            && let Some(folder) = path.parent()
+            && !folder.as_os_str().is_empty()
+            && !folder.is_dir()
+        {
+            self.folder_unavailable(ctx, folder.to_path_buf());
+            return;
+        }
+        log::warn!("failed to load {}: {error}", path.display());
+        self.failed.insert(path, error);
+        self.texture = None;
+        self.averaged_texture = None;
+        self.source_image = None;
+        self.reduced_from = None;
+        self.tiles = None;
+        self.playback = None;
+        self.info = None;
+        self.image_shown();
+    }
 
-        // ── Central panel (image display area) ──────────────────────────────
-        egui::CentralPanel::default()
-    .frame(egui::Frame::none().fill(egui::Color32::BLACK))
-    .show(ctx, |ui| {
-        if let Some(texture) = &self.texture {
-            let available = ui.available_size();
-
-            let img_size = texture.size_vec2();
-            let scale_x = available.x / img_size.x;
-            let scale_y = available.y / img_size.y;
-            let base_scale = scale_x.min(scale_y);
-            let final_scale = base_scale * self.zoom;
-
-            let display_size = egui::vec2(
-                img_size.x * final_scale,
-                img_size.y * final_scale,
-            );
+    // `folder` has disappeared. Its images are dropped and we move on to
+    // the nearest image left from the other open folders, if there are
+    // any. Otherwise there's nothing left to navigate, and the view shows
+    // what happened instead of an image.
+    fn folder_unavailable(&mut self, ctx: &egui::Context, folder: PathBuf) {
+        log::warn!("{} is no longer available", folder.display());
+        let in_folder = |path: &Path| path.parent() == Some(folder.as_path());
+        let before = self.images[..self.current_index].iter().filter(|p| !in_folder(p)).count();
+        self.images.retain(|p| !in_folder(p));
+        self.totals.refresh(ctx, &self.images);
+        self.failed.retain(|p, _| !in_folder(p));
+        self.folders.retain(|f| f != &folder);
+        self.shuffle = None;
+        self.crop = None;
+        self.texture = None;
+        self.averaged_texture = None;
+        self.source_image = None;
+        self.reduced_from = None;
+        self.tiles = None;
+        self.playback = None;
+        self.info = None;
+        self.page = 0;
+        self.page_count = 1;
 
-            // ScrollArea prevents the image from overflowing outside the
-            // central panel when zoomed in. Without this, a large display_size
-            // would push content past the panel boundary and hide the toolbar.
-            // auto_shrink(false) ensures the scroll area always fills the
-            // full available space even when the image is smaller than the panel.
-            egui::ScrollArea::both()
-                .auto_shrink([false, false])
-                .show(ui, |ui| {
-                    // When the image is smaller than the panel (zoom <= 1.0),
-                    // we still want it centered. We do this by adding padding
-                    // inside the scroll area.
-                    let padding_x = ((available.x - display_size.x) / 2.0).max(0.0);
-                    let padding_y = ((available.y - display_size.y) / 2.0).max(0.0);
-
-                    ui.add_space(padding_y);
-                    ui.horizontal(|ui| {
-                        ui.add_space(padding_x);
-                        ui.add(
-                            egui::Image::new(texture)
-                                .fit_to_exact_size(display_size)
-                        );
-                    });
-                });
+        if self.images.is_empty() {
+            self.current_index = 0;
+            self.slideshow = None;
+            self.status_message = None;
+            self.unavailable_folder = Some(folder);
+            return;
+        }
+        self.current_index = before.min(self.images.len() - 1);
+        self.status_message = Some(format!("{} is no longer available", folder.display()));
+        self.reset_view();
+        self.load_texture(ctx);
+    }
 
-        } else {
-            ui.centered_and_justified(|ui| {
-                ui.label("Click '📂 Open' to select an image");
-            });
+    // Open a folder picked in a dialog in place of whatever is open.
+    fn open_folder_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(folder) = self.file_dialog().pick_folder()
+            && !self.open_path(ctx, folder.clone())
+        {
+            self.status_message = Some(format!("No images in {}", folder.display()));
         }
+    }
 
-        // Keyboard navigation
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-            self.go_next(ctx);
+    // Called once the current image (or its error) is actually on screen.
+    // The slideshow interval starts from here rather than from when we
+    // started decoding, so a slow decode doesn't eat into viewing time.
+    fn image_shown(&mut self) {
+        if let Some(slideshow) = &mut self.slideshow {
+            slideshow.restart_timer();
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-            self.go_prev(ctx);
+    }
+
+    // Upload decoded pixels to the GPU as an egui texture.
+    // egui handles scaling and rendering from here. Pixels that can't be
+    // drawn (see decode::check_pixels) are refused, leaving whatever was
+    // up before for the caller to deal with.
+    fn upload_texture(&mut self, ctx: &egui::Context, img: &image::RgbaImage) -> Result<(), String> {
+        decode::check_pixels(img)?;
+        // Upload to GPU. TextureOptions::LINEAR gives smooth scaling
+        // (bilinear filtering) instead of blocky nearest-neighbor; once
+        // the image is on screen, smoothed_texture() may switch it to
+        // another filter to suit the zoom.
+        //
+        // This upload happens once per image load, never per frame. After
+        // that, zooming and panning only change the rectangle the texture
+        // is drawn into, and the GPU does the scaling as it samples, so
+        // there's no CPU-side resampling to speed up. (view::render is a
+        // CPU version of the same drawing, but only for the golden tests
+        // and benchmarks; nothing on screen goes through it.)
+        let limit = self.texture_limit(ctx);
+        if img.width().max(img.height()) as usize > limit {
+            log::warn!("{}x{} is larger than the largest texture allowed ({limit} pixels a side); shrinking it to draw", img.width(), img.height());
+            self.status_message = Some(format!("Shown shrunk to {limit} pixels a side, the most the graphics card can draw"));
         }
-    });
+        self.texture = Some(ctx.load_texture(
+            "current_image",
+            color_image(img, limit),
+            egui::TextureOptions::LINEAR,
+        ));
+        self.texture_filter = Filter::Linear;
+        self.averaged_texture = None;
+        Ok(())
+    }
+
+    // The largest texture side we upload: the graphics card's own limit,
+    // or the one in the settings if that's lower. Textures past the card's
+    // limit fail to upload, leaving a blank view or, with some drivers,
+    // taking the whole window down.
+    fn texture_limit(&self, ctx: &egui::Context) -> usize {
+        let gpu = ctx.input(|i| i.max_texture_side);
+        match self.settings.max_texture_size {
+            0 => gpu,
+            size => gpu.min(size as usize),
+        }
+    }
+
+    // The texture to draw the current image with when it's shown at
+    // `scale` physical pixels per image pixel, filtered as the settings
+    // ask for that scale (see smoothing.rs). Switching between nearest and
+    // bilinear re-uploads the full image; shrinking far enough makes an
+    // averaged copy, which is kept until the zoom needs a different one.
+    //
+    // Both only happen when the zoom crosses a threshold, not every frame.
+    fn smoothed_texture(&mut self, ctx: &egui::Context, scale: f32) -> Option<egui::TextureId> {
+        let limit = self.texture_limit(ctx);
+        let texture = self.texture.as_mut()?;
+        // A quick preview is only up for a moment; it's drawn as it is.
+        let Some(source) = &self.source_image else { return Some(texture.id()); };
+
+        match self.settings.smoothing.filter(scale) {
+            Filter::Averaged(halvings) => {
+                if self.averaged_texture.as_ref().is_none_or(|(h, _)| *h != halvings) {
+                    let small = smoothing::averaged(source, halvings);
+                    let handle = ctx.load_texture("current_image_averaged", color_image(&small, limit), egui::TextureOptions::LINEAR);
+                    self.averaged_texture = Some((halvings, handle));
+                }
+                self.averaged_texture.as_ref().map(|(_, handle)| handle.id())
+            }
+            filter => {
+                self.averaged_texture = None;
+                if filter != self.texture_filter {
+                    texture.set(color_image(source, limit), filter.texture_options());
+                    self.texture_filter = filter;
+                }
+                Some(texture.id())
+            }
+        }
+    }
+
+    // Navigate to the next image. Past the last one, the configured
+    // end action decides what happens (see settings::EndAction).
+    fn go_next(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() { return; }
+        // The pages of a multi-page file come before the next file.
+        if self.page + 1 < self.page_count {
+            self.show_page(ctx, self.current_index, self.page + 1);
+            return;
+        }
+        // Flagged images wrap around among themselves, so there's no end
+        // to reach.
+        if self.flagged_only {
+            if let Some(index) = self.next_flagged(true) {
+                self.show_index(ctx, index);
+            }
+            return;
+        }
+        let next = if self.settings.shuffle {
+            self.shuffle_order().next()
+        } else {
+            Some(self.current_index + 1).filter(|&index| index < self.images.len())
+        };
+        if let Some(index) = next {
+            self.show_index(ctx, index);
+            return;
+        }
+
+        // Past the last image: what happens is up to the user.
+        log::debug!("reached the last image; end action is {}", self.settings.end_action.label());
+        match self.settings.end_action {
+            EndAction::Stop => {
+                self.slideshow = None;
+                self.bounce_at_end(Bounce::forward());
+            }
+            EndAction::Loop => {
+                let first = if self.settings.shuffle { self.shuffle_order().new_pass() } else { 0 };
+                self.show_index(ctx, first);
+            }
+            EndAction::Exit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            EndAction::NextFolder => {
+                let options = self.settings.scan_options();
+                let next = scan::folder_of(&self.images[self.current_index])
+                    .and_then(|folder| scan::next_sibling_folder(folder, &options));
+                match next {
+                    // Not remembered in recents: it wasn't opened by hand.
+                    Some(folder) => { self.open_path_without_remembering(ctx, folder); }
+                    None => {
+                        self.slideshow = None;
+                        self.status_message = Some("No more folders".to_string());
+                        self.bounce_at_end(Bounce::forward());
+                    }
+                }
+            }
+        }
+    }
+
+    // Navigate to the previous image. Only wraps from first back to last
+    // when looping; every other end action just stops at the first image.
+    // In shuffle mode it retraces the shuffled order instead.
+    fn go_prev(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() { return; }
+        if self.page > 0 {
+            self.show_page(ctx, self.current_index, self.page - 1);
+            return;
+        }
+        let prev = if self.flagged_only {
+            self.next_flagged(false)
+        } else if self.settings.shuffle {
+            self.shuffle_order().prev()
+        } else {
+            match self.current_index.checked_sub(1) {
+                Some(index) => Some(index),
+                None if self.settings.end_action == EndAction::Loop => Some(self.images.len() - 1),
+                None => None,
+            }
+        };
+        // Stepping back into a multi-page file lands on its last page,
+        // just as stepping forward into it starts on the first.
+        if let Some(index) = prev {
+            let last_page = match self.url {
+                Some(_) => 0,
+                None => pages::count(&self.images[index]) - 1,
+            };
+            self.show_page(ctx, index, last_page);
+        } else {
+            self.bounce_at_end(Bounce::backward());
+        }
+    }
+
+    // The nearest flagged image after (or before) the current one,
+    // wrapping around the folder. The current image itself if it's the
+    // only one flagged.
+    fn next_flagged(&self, forward: bool) -> Option<usize> {
+        self.flagged_after(self.current_index, forward)
+    }
+
+    // The first flagged image after `from` (or before it), wrapping round.
+    fn flagged_after(&self, from: usize, forward: bool) -> Option<usize> {
+        let len = self.images.len();
+        (1..=len)
+            .map(|step| if forward { (from + step) % len } else { (from + len - step) % len })
+            .find(|&index| self.session.flagged.contains(&self.images[index]))
+    }
+
+    fn toggle_flag(&mut self) {
+        if self.images.is_empty() || self.url.is_some() { return; }
+        let path = &self.images[self.current_index];
+        if !self.session.flagged.remove(path) {
+            self.session.flagged.insert(path.clone());
+        }
+    }
+
+    fn toggle_flagged_only(&mut self, ctx: &egui::Context) {
+        if self.flagged_only {
+            self.flagged_only = false;
+            return;
+        }
+        if !self.images.iter().any(|path| self.session.flagged.contains(path)) {
+            self.status_message = Some("No images in this folder are flagged (F flags one)".to_string());
+            return;
+        }
+        self.flagged_only = true;
+        // Start on a flagged image rather than wherever we happen to be.
+        if !self.session.flagged.contains(&self.images[self.current_index])
+            && let Some(index) = self.next_flagged(true)
+        {
+            self.show_index(ctx, index);
+        }
+    }
+
+    fn bounce_at_end(&mut self, bounce: Bounce) {
+        if self.settings.edge_bounce && !self.reduce_motion() {
+            self.bounce = Some(if self.settings.right_to_left { bounce.mirrored() } else { bounce });
+        }
+    }
+
+    // What the mouse may do to `sense` an area of the view: anything, or
+    // in keyboard-only mode nothing but hover over it.
+    fn mouse_sense(&self, sense: egui::Sense) -> egui::Sense {
+        if self.mouse_ignored() { egui::Sense::hover() } else { sense }
+    }
+
+    // Keyboard-only mode, or a presentation, which includes it.
+    fn mouse_ignored(&self) -> bool {
+        self.settings.keyboard_only || self.presentation.is_some()
+    }
+
+    fn reduce_motion(&self) -> bool {
+        self.settings.reduce_motion.applies(self.system_reduced_motion)
+    }
+
+    // Jump to a random image other than the current one.
+    fn go_random(&mut self, ctx: &egui::Context) {
+        if self.images.len() < 2 { return; }
+        // Pick from all but one slot, then step over the current image, so
+        // every other image is equally likely.
+        let mut index = fastrand::usize(..self.images.len() - 1);
+        if index >= self.current_index {
+            index += 1;
+        }
+        self.show_index(ctx, index);
+    }
+
+    // The shuffled order for the current folder, made fresh if there isn't
+    // one yet or the folder has changed size since.
+    fn shuffle_order(&mut self) -> &mut Shuffle {
+        let len = self.images.len();
+        let current = self.current_index;
+        let shuffle = self.shuffle.get_or_insert_with(|| Shuffle::new(len, current));
+        if shuffle.len() != len {
+            *shuffle = Shuffle::new(len, current);
+        }
+        shuffle
+    }
+
+    // Skip `by` images forward, or back if it's negative (Ctrl+Shift+Right
+    // and Left, by Settings::jump_size). Past either end it wraps round
+    // when looping and otherwise stops at the first or last image, which
+    // bounces if it's already there. Only flagged images count while
+    // showing just those. Jumps follow the folder's order even when
+    // shuffling, since the random order has no "ten further on".
+    fn go_by(&mut self, ctx: &egui::Context, by: isize) {
+        if self.images.is_empty() || by == 0 { return; }
+        let bounce = if by > 0 { Bounce::forward() } else { Bounce::backward() };
+        let target = if self.flagged_only {
+            let mut index = Some(self.current_index);
+            for _ in 0..by.unsigned_abs() {
+                index = index.and_then(|from| self.flagged_after(from, by > 0));
+            }
+            index
+        } else {
+            let len = self.images.len() as isize;
+            let target = self.current_index as isize + by;
+            Some(if self.settings.end_action == EndAction::Loop { target.rem_euclid(len) } else { target.clamp(0, len - 1) } as usize)
+        };
+        match target {
+            Some(index) if index != self.current_index => self.show_index(ctx, index),
+            _ => self.bounce_at_end(bounce),
+        }
+    }
+
+    // Jump to the first image of the next or previous subfolder, or day when
+    // sorted by date taken (Ctrl+Right and Ctrl+Left; see
+    // scan::chapter_start).
+    fn go_chapter(&mut self, ctx: &egui::Context, forward: bool) {
+        if self.images.is_empty() || self.url.is_some() { return; }
+        match scan::chapter_start(&self.images, self.current_index, forward, self.settings.sort_order, &self.sort_keys) {
+            Some(index) => self.show_index(ctx, index),
+            None => self.bounce_at_end(if forward { Bounce::forward() } else { Bounce::backward() }),
+        }
+    }
+
+    fn show_index(&mut self, ctx: &egui::Context, index: usize) {
+        self.show_page(ctx, index, 0);
+    }
+
+    // Every kind of navigation ends up here. Landing on what's already
+    // shown (Next/Prev wrapping round a folder of one image, or clicking
+    // the current thumbnail) keeps the zoom and doesn't decode the file
+    // again; F5 is there for deliberately re-reading it.
+    fn show_page(&mut self, ctx: &egui::Context, index: usize, page: usize) {
+        if index == self.current_index && page == self.page {
+            return;
+        }
+        if index != self.current_index {
+            self.page_count = 1;
+            self.direction.moved(self.current_index, index, self.images.len());
+        }
+        self.current_index = index;
+        self.page = page;
+        self.reset_view();
+        self.load_texture(ctx);
+    }
+
+    // Back/Forward through the viewing history. The entry may be in a
+    // folder we've since left, in which case that folder is reopened.
+    fn go_back(&mut self, ctx: &egui::Context) {
+        if let Some(path) = self.history.back().map(Path::to_path_buf) {
+            self.show_path(ctx, path);
+        }
+    }
+
+    fn go_forward(&mut self, ctx: &egui::Context) {
+        if let Some(path) = self.history.forward().map(Path::to_path_buf) {
+            self.show_path(ctx, path);
+        }
+    }
+
+    // Flip back to the image shown before this one. Pressed again, it
+    // flips back, for comparing two images wherever they are.
+    fn go_to_previous_image(&mut self, ctx: &egui::Context) {
+        match self.history.previous().map(Path::to_path_buf) {
+            Some(path) => self.show_path(ctx, path),
+            None => self.status_message = Some("No other image viewed yet".to_string()),
+        }
+    }
+
+    fn show_path(&mut self, ctx: &egui::Context, path: PathBuf) {
+        if let Some(index) = self.images.iter().position(|p| p == &path) {
+            self.show_index(ctx, index);
+        } else if is_url(&path) {
+            self.open_url(ctx, path.to_string_lossy().into_owned());
+        } else if !self.open_path_without_remembering(ctx, path.clone()) {
+            self.status_message = Some(format!("{} is no longer available", path.display()));
+        }
+    }
+
+    // Re-read the current image from disk, e.g. after editing it in
+    // another program. Nothing decoded is cached between loads, but the
+    // filmstrip keeps its thumbnail in memory, so that's dropped to be
+    // remade (the disk cache is keyed on the file's modification time, so
+    // it picks up the edit by itself).
+    fn reload_current(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() { return; }
+        self.thumbnails.forget(&self.images[self.current_index]);
+        self.loader.forget(&self.images[self.current_index]);
+        self.load_texture(ctx);
+    }
+
+    // Open the note editor for the current image.
+    fn edit_note(&mut self) {
+        if self.images.is_empty() || self.url.is_some() { return; }
+        let path = self.images[self.current_index].clone();
+        self.note_draft = Some((path, self.note.clone().unwrap_or_default()));
+    }
+
+    fn save_note(&mut self, path: &Path, text: &str) {
+        match notes::save(path, text) {
+            Ok(()) => {
+                if self.images.get(self.current_index).is_some_and(|p| p == path) {
+                    self.note = notes::load(path);
+                }
+            }
+            Err(e) => {
+                log::error!("failed to save note for {}: {e}", path.display());
+                self.status_message = Some(format!("Couldn't save note: {e}"));
+            }
+        }
+    }
+
+    // Go into presentation mode (see presentation.rs), remembering what
+    // to put back afterwards.
+    fn start_presentation(&mut self, ctx: &egui::Context) {
+        log::debug!("presentation started");
+        self.presentation = Some(Presentation::new(self.session.fullscreen, self.slideshow.is_some()));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+        if self.slideshow.is_none() {
+            self.toggle_slideshow();
+        }
+        self.crop = None;
+        self.show_settings = false;
+    }
+
+    fn stop_presentation(&mut self, ctx: &egui::Context) {
+        let Some(presentation) = self.presentation.take() else { return; };
+        log::debug!("presentation ended");
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(presentation.was_fullscreen));
+        if !presentation.had_slideshow {
+            self.slideshow = None;
+        }
+        self.status_message = Some("Presentation ended".to_string());
+    }
+
+    // Notice the window being minimized and coming back. Nothing is drawn
+    // while it's minimized, so the slideshow and any animation are held
+    // where they were, and pick up from there when it's back, instead of
+    // jumping on by however long it was away. Returns whether the window
+    // can be seen.
+    //
+    // egui only says whether the window is minimized; a window that's
+    // merely covered by others keeps going.
+    fn track_visibility(&mut self, ctx: &egui::Context) -> bool {
+        let minimized = ctx.input(|i| i.viewport().minimized) == Some(true);
+        match (minimized, self.hidden_since) {
+            (true, None) => {
+                log::debug!("window minimized; pausing the slideshow and animation");
+                self.hidden_since = Some(Instant::now());
+            }
+            (false, Some(since)) => {
+                let hidden = since.elapsed();
+                log::debug!("window back after {hidden:.1?}");
+                if let Some(slideshow) = &mut self.slideshow {
+                    slideshow.resume_after(hidden);
+                }
+                if let Some(playback) = &mut self.playback {
+                    playback.resume_after(hidden);
+                }
+                self.hidden_since = None;
+            }
+            _ => {}
+        }
+        !minimized
+    }
+
+    // Start or stop the slideshow. Starting does nothing until a folder
+    // has been opened, since there's nothing to advance through.
+    fn toggle_slideshow(&mut self) {
+        if self.slideshow.take().is_none() && !self.images.is_empty() {
+            self.slideshow = Some(Slideshow::start(self.settings.keep_awake_during_slideshow));
+        }
+    }
+
+    // Put up the next frame of an animation when it's due, and schedule a
+    // repaint for the one after.
+    fn tick_animation(&mut self, ctx: &egui::Context) {
+        let Some(playback) = &mut self.playback else { return; };
+        let (changed, next) = playback.advance();
+        if changed {
+            self.show_frame(ctx);
+        }
+        if let Some(next) = next {
+            ctx.request_repaint_after(next);
+        }
+    }
+
+    // Upload the animation's current frame over the image's texture.
+    fn show_frame(&mut self, ctx: &egui::Context) {
+        let limit = self.texture_limit(ctx);
+        let (Some(playback), Some(texture)) = (&self.playback, &mut self.texture) else { return; };
+        let frame = playback.frame();
+        // The last frame that could be drawn stays up instead.
+        if let Err(e) = decode::check_pixels(frame) {
+            log::warn!("skipping an animation frame: {e}");
+            return;
+        }
+        texture.set(color_image(frame, limit), self.texture_filter.texture_options());
+        // Remade from the new frame when it's next needed.
+        self.averaged_texture = None;
+        self.source_image = Some(Arc::new(frame.clone()));
+    }
+
+    fn step_frame(&mut self, ctx: &egui::Context, by: isize) {
+        if let Some(playback) = &mut self.playback {
+            playback.step(by);
+            self.show_frame(ctx);
+        }
+    }
+
+    // Advance the slideshow if the current image has been shown for long
+    // enough, and otherwise schedule a repaint for when it will be due.
+    // egui only repaints on input by default, so without the scheduled
+    // repaint the timer would never fire while the user is idle.
+    fn tick_slideshow(&mut self, ctx: &egui::Context) {
+        let Some(slideshow) = &self.slideshow else { return; };
+        // Don't skip past an image that hasn't finished loading yet;
+        // poll_loader() restarts the timer when it does.
+        if self.loader.is_loading() { return; }
+
+        let interval = Duration::from_secs_f32(self.settings.slideshow_interval_secs.max(1.0));
+        let remaining = slideshow.time_remaining(interval);
+        if remaining.is_zero() {
+            self.go_next(ctx);
+            ctx.request_repaint_after(interval);
+        } else {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+
+    // Rotate the current image on disk and show the result. Not for a
+    // tiled image (see tiles.rs), which would have to be decoded whole.
+    fn rotate_current(&mut self, ctx: &egui::Context, rotation: Rotation) {
+        if self.images.is_empty() || self.tiles.is_some() { return; }
+        let path = &self.images[self.current_index];
+        let quality = self.settings.save_quality;
+        match self.undo.record(path, "rotate", || edit::rotate_file(path, rotation, quality)) {
+            Ok(()) => {
+                self.thumbnails.forget(&self.images[self.current_index]);
+                self.load_texture(ctx);
+            }
+            Err(e) => {
+                log::error!("failed to rotate {}: {e}", self.images[self.current_index].display());
+                self.status_message = Some(format!("Rotate failed: {e}"));
+            }
+        }
+    }
+
+    // Copy the current file next to itself (Ctrl+D; see
+    // edit::duplicate_file) and add the copy to the folder where the sort
+    // order puts it. The original stays on screen.
+    fn duplicate_current(&mut self, ctx: &egui::Context) {
+        if self.images.is_empty() || self.url.is_some() { return; }
+        let current = self.images[self.current_index].clone();
+        match edit::duplicate_file(&current) {
+            Ok(copy) => {
+                log::info!("copied {} to {}", current.display(), copy.display());
+                self.status_message = Some(format!("Copied to {}", copy.file_name().unwrap_or_default().to_string_lossy()));
+                self.images.push(copy);
+                scan::sort_images(&mut self.images, self.settings.sort_order, self.settings.name_order, self.settings.include_subfolders, &mut self.sort_keys);
+                self.current_index = self.images.iter().position(|p| p == &current).unwrap_or(0);
+                self.totals.refresh(ctx, &self.images);
+                self.shuffle = None;
+            }
+            Err(e) => {
+                log::error!("failed to copy {}: {e}", current.display());
+                self.status_message = Some(format!("Copy failed: {e}"));
+            }
+        }
+    }
+
+    // Enter crop mode on the current image, or leave it if already there.
+    // Needs the full decoded image, not just the quick preview, so the
+    // selection is measured in the image's real pixels.
+    fn toggle_crop(&mut self) {
+        if self.crop.is_some() {
+            self.crop = None;
+            return;
+        }
+        if self.url.is_some() || self.tiles.is_some() || self.batch.is_some() || self.grid.is_some() { return; }
+        // In the real image's pixels even over a shrunk copy, since that's
+        // what gets cropped.
+        if let Some(size) = self.image_size() {
+            self.crop = Some(Crop::new(size));
+        }
+    }
+
+    fn toggle_grid(&mut self) {
+        if self.grid.take().is_none() && self.url.is_none() {
+            self.crop = None;
+            self.grid = Some(Grid::new());
+        }
+    }
+
+    // Draw the comparison grid: the current image top left, then the three
+    // after it, each fitted to its cell. Clicking a cell leaves the grid
+    // and shows that image on its own.
+    fn draw_grid(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let following: Vec<PathBuf> = self.images.iter().skip(self.current_index + 1).take(3).cloned().collect();
+        let Some(grid) = &mut self.grid else { return; };
+        grid.show(ctx, &following, self.settings.color_policy);
+        grid.poll(ctx);
+
+        let mut clicked = None;
+        let sense = self.mouse_sense(egui::Sense::click());
+        for (n, rect) in grid::quadrants(ui.max_rect(), GRID_GAP).into_iter().enumerate() {
+            let index = self.current_index + n;
+            let Some(path) = self.images.get(index) else { break; };
+            if ui.interact(rect, ui.id().with(("grid", n)), sense).clicked() {
+                clicked = Some(index);
+            }
+
+            let cell = if n == 0 {
+                match (&self.texture, self.failed.get(path)) {
+                    (Some(texture), _) => grid::Cell::Ready(texture.clone()),
+                    (None, Some(error)) => grid::Cell::Failed(error.clone()),
+                    (None, None) => grid::Cell::Loading,
+                }
+            } else {
+                match self.grid.as_ref().and_then(|grid| grid.cells().get(n - 1)) {
+                    Some((_, grid::Cell::Ready(texture))) => grid::Cell::Ready(texture.clone()),
+                    Some((_, grid::Cell::Failed(error))) => grid::Cell::Failed(error.clone()),
+                    _ => grid::Cell::Loading,
+                }
+            };
+            let painter = ui.painter().with_clip_rect(rect);
+            match cell {
+                grid::Cell::Ready(texture) => {
+                    let size = view::display_size(rect.size(), texture.size_vec2(), 1.0, FitMode::Window);
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    painter.image(texture.id(), egui::Rect::from_center_size(rect.center(), size), uv, egui::Color32::WHITE);
+                }
+                grid::Cell::Failed(error) => {
+                    overlay::draw_text(
+                        &painter,
+                        rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        &format!("Couldn't load this image:\n{error}"),
+                        self.settings.overlay_text_size,
+                        egui::Color32::LIGHT_RED,
+                    );
+                }
+                grid::Cell::Loading => {
+                    ui.put(egui::Rect::from_center_size(rect.center(), egui::Vec2::splat(24.0)), egui::Spinner::new());
+                }
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            overlay::draw_text(
+                &painter,
+                rect.left_bottom() + egui::vec2(8.0, -8.0),
+                egui::Align2::LEFT_BOTTOM,
+                &name,
+                self.settings.overlay_text_size,
+                egui::Color32::WHITE,
+            );
+            // The current image, which the keyboard and tools act on.
+            if n == 0 {
+                painter.rect_stroke(rect.shrink(1.0), 0.0, ui.visuals().selection.stroke);
+            }
+        }
+
+        if let Some(index) = clicked {
+            self.grid = None;
+            self.show_index(ctx, index);
+        }
+    }
+
+    // Make `change`, or ask about it first if the settings say to. Saving a
+    // crop that's still the whole image only leaves crop mode, so there's
+    // nothing to ask about.
+    fn change_files(&mut self, ctx: &egui::Context, change: Change) {
+        let nothing_to_crop = change == Change::Crop && self.crop.as_ref().and_then(Crop::selection).is_none();
+        if self.settings.confirm.asks(change) && !nothing_to_crop {
+            self.pending_change = Some(change);
+        } else {
+            self.make_change(ctx, change);
+        }
+    }
+
+    // The answer to the question about the pending change.
+    fn answer_change(&mut self, ctx: &egui::Context, confirmed: bool) {
+        if let Some(change) = self.pending_change.take()
+            && confirmed
+        {
+            self.make_change(ctx, change);
+        }
+    }
+
+    fn make_change(&mut self, ctx: &egui::Context, change: Change) {
+        match change {
+            Change::Rotate(rotation) => self.rotate_current(ctx, rotation),
+            Change::RotateAll(rotation) => self.start_batch_rotation(ctx, rotation),
+            Change::Crop => self.save_crop(ctx),
+        }
+    }
+
+    // Save the selected area over the original file. A selection that's
+    // still the whole image just leaves crop mode, rather than re-encoding
+    // the file for nothing.
+    fn save_crop(&mut self, ctx: &egui::Context) {
+        let Some(selection) = self.crop.take().and_then(|crop| crop.selection()) else { return; };
+        if self.tiles.is_some() { return; }
+        let path = self.images[self.current_index].clone();
+        let origin = [selection.x[0], selection.y[0]];
+        let size = [selection.width(), selection.height()];
+        let quality = self.settings.save_quality;
+        match self.undo.record(&path, "crop", || edit::crop_file(&path, origin, size, quality)) {
+            Ok(()) => {
+                self.thumbnails.forget(&path);
+                self.status_message = Some(format!("Cropped to {} × {}", size[0], size[1]));
+                self.load_texture(ctx);
+            }
+            Err(e) => {
+                log::error!("failed to crop {}: {e}", path.display());
+                self.status_message = Some(format!("Crop failed: {e}"));
+            }
+        }
+    }
+
+    // Switch between converting to sRGB and showing the raw values (K),
+    // for comparing an image with and without its color profile applied.
+    fn toggle_color_policy(&mut self, ctx: &egui::Context) {
+        self.settings.color_policy = match self.settings.color_policy {
+            color::Policy::Srgb => color::Policy::Raw,
+            color::Policy::Raw => color::Policy::Srgb,
+        };
+        self.status_message = Some(format!("Colors: {}", self.settings.color_policy.label().to_lowercase()));
+        self.recolor(ctx);
+    }
+
+    // Decode what's on screen again after the color policy changes.
+    fn recolor(&mut self, ctx: &egui::Context) {
+        if self.grid.is_some() {
+            self.grid = Some(Grid::new());
+        }
+        if self.texture.is_some() {
+            self.load_texture(ctx);
+        }
+    }
+
+    // Save the view as it's drawn, at the settings' multiple of the
+    // window's resolution (see export.rs). `view_size` and `fit` are the
+    // central panel's, as it last drew the image.
+    fn export_view(&mut self, ctx: &egui::Context, view_size: egui::Vec2, fit: FitMode) {
+        let Some(source) = &self.source_image else {
+            self.status_message = Some("Wait for the image to finish loading first".to_string());
+            return;
+        };
+        let path = &self.images[self.current_index];
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut dialog = FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name(format!("{stem} (view).png"));
+        if self.url.is_none() && let Some(folder) = path.parent() {
+            dialog = dialog.set_directory(folder);
+        }
+        let Some(target) = dialog.save_file() else { return; };
+
+        let framing = export::Framing {
+            view_size,
+            zoom: self.zoom,
+            fit,
+            fit_percent: self.settings.fit_percent,
+            pan: self.pan,
+            alignment: self.settings.alignment,
+        };
+        let has_transparency = self.info.as_ref().is_some_and(|info| info.has_transparency);
+        let backdrop = export::Backdrop {
+            surround: self.settings.background.surround(self.info.as_ref()).map_or([0, 0, 0], |color| [color.r(), color.g(), color.b()]),
+            checkerboard: self.settings.background.shows_checkerboard(has_transparency),
+            border: self.settings.border,
+            vignette: self.settings.vignette,
+        };
+        let img = export::render(source, &framing, &backdrop, ctx.pixels_per_point() * self.settings.export_scale);
+        let name = target.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        match export::save(&img, &target) {
+            Ok(()) => self.status_message = Some(format!("Saved the view as {name} ({} × {})", img.width(), img.height())),
+            Err(e) => {
+                log::error!("failed to export the view to {}: {e}", target.display());
+                self.status_message = Some(format!("Couldn't save the view: {e}"));
+            }
+        }
+    }
+
+    // Put back the file as it was before the last rotate or crop, and show
+    // it.
+    fn undo_last_edit(&mut self, ctx: &egui::Context) {
+        match self.undo.undo() {
+            None => self.status_message = Some("Nothing to undo".to_string()),
+            Some(Ok((path, action))) => {
+                self.thumbnails.forget(&path);
+                let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                if self.images.get(self.current_index) == Some(&path) {
+                    self.load_texture(ctx);
+                } else {
+                    self.show_path(ctx, path);
+                }
+                self.status_message = Some(format!("Undid {action} of {name}"));
+            }
+            Some(Err(e)) => {
+                log::error!("failed to undo: {e}");
+                self.status_message = Some(format!("Undo failed: {e}"));
+            }
+        }
+    }
+
+    // Rotate every image in the folder on a background thread.
+    fn start_batch_rotation(&mut self, ctx: &egui::Context, rotation: Rotation) {
+        if self.batch.is_some() || self.images.is_empty() { return; }
+        self.status_message = None;
+        let quality = self.settings.save_quality;
+        self.batch = Some(BatchJob::spawn(
+            ctx,
+            "Rotating",
+            self.images.clone(),
+            move |path| edit::rotate_file(path, rotation, quality),
+        ));
+    }
+
+    // Check on the background batch, if any. When it finishes we reload
+    // the current image, since it has most likely been rewritten on disk.
+    fn poll_batch(&mut self, ctx: &egui::Context) {
+        let Some(batch) = &mut self.batch else { return; };
+        if !batch.poll() { return; }
+
+        self.status_message = Some(batch.summary());
+        self.batch = None;
+        self.thumbnails.clear();
+        if !self.images.is_empty() {
+            self.load_texture(ctx);
+        }
+    }
+
+    // Map a screen position over the displayed image back to the source
+    // pixel it covers. `image_rect` is where the image was drawn this
+    // frame, so this stays correct at any zoom or scroll offset.
+    fn pixel_at(&self, image_rect: egui::Rect, pos: egui::Pos2) -> Option<([u32; 2], [u8; 4])> {
+        // A shrunk copy's pixels are averages; wait for the real ones.
+        if self.reduced_from.is_some() { return None; }
+        let img = self.source_image.as_ref()?;
+        let [x, y] = view::source_pixel(image_rect, [img.width(), img.height()], pos)?;
+        Some(([x, y], img.get_pixel(x, y).0))
+    }
+
+    // How an image of `img_size` is sized at zoom 1.0, which can differ
+    // for portrait and landscape images (e.g. filling the width with tall
+    // images and panning down them), and panoramas (see Settings::fit_for).
+    fn fit_mode(&self, img_size: egui::Vec2) -> FitMode {
+        self.settings.fit_for(img_size)
+    }
+
+    // Apply a multiplicative zoom delta, clamped to a safe range.
+    // This is used by both scroll wheel and pinch-to-zoom gestures.
+    // Multiplicative zoom feels more natural than additive because each
+    // step is proportional to the current zoom level — going from 1.0 to
+    // 2.0 feels the same as going from 2.0 to 4.0.
+    fn apply_zoom_delta(&mut self, delta: f32) {
+        let old_zoom = self.zoom;
+        self.zoom = (self.zoom * delta).clamp(view::MIN_ZOOM, view::MAX_ZOOM);
+        // Scale the pan with the zoom so the point at the center of the
+        // view stays at the center, rather than drifting toward the edge.
+        self.pan *= self.zoom / old_zoom;
+    }
+
+    // Back to fit-to-window, centered or aligned as the settings say.
+    fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.pan = egui::Vec2::ZERO;
+        self.align_pending = true;
+        self.glide = None;
+    }
+
+    // Keyboard panning for a zoomed image. Shift+arrows nudge the view;
+    // Home/End jump to the left/right edge and PageUp/PageDown to the
+    // top/bottom. The jumps just set pan to its extremes, so they go
+    // through exactly the same clamp as every other kind of panning.
+    fn handle_pan_keys(&mut self, ctx: &egui::Context, view: egui::Vec2, display: egui::Vec2) {
+        const STEP: f32 = 50.0;
+        let limit = view::pan_limit(view, display);
+
+        ctx.input(|i| {
+            if i.modifiers.shift && !i.modifiers.command {
+                // Moving the view right means moving the image left.
+                if i.key_pressed(egui::Key::ArrowRight) { self.pan.x -= STEP; }
+                if i.key_pressed(egui::Key::ArrowLeft) { self.pan.x += STEP; }
+                if i.key_pressed(egui::Key::ArrowDown) { self.pan.y -= STEP; }
+                if i.key_pressed(egui::Key::ArrowUp) { self.pan.y += STEP; }
+            }
+            if i.key_pressed(egui::Key::Home) { self.pan.x = limit.x; }
+            if i.key_pressed(egui::Key::End) { self.pan.x = -limit.x; }
+            if i.key_pressed(egui::Key::PageUp) { self.pan.y = limit.y; }
+            if i.key_pressed(egui::Key::PageDown) { self.pan.y = -limit.y; }
+        });
+
+        self.pan = view::clamp_pan(view, display, self.pan);
+    }
+
+    // A strip of thumbnails for the whole folder. Only the cells that are
+    // actually on screen ask for their thumbnail, so opening a folder of
+    // thousands of images doesn't queue thousands of decodes. Returns the
+    // index of the image the user clicked, if any.
+    fn filmstrip(&mut self, ui: &mut egui::Ui) -> Option<usize> {
+        let scroll_to_current = self.filmstrip_scrolled_to != Some(self.current_index);
+        self.filmstrip_scrolled_to = Some(self.current_index);
+
+        let mut clicked = None;
+        let sense = self.mouse_sense(egui::Sense::click());
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (index, path) in self.images.iter().enumerate() {
+                    // Mark where one merged folder ends and the next begins.
+                    if index > 0 && self.folders.len() > 1 && path.parent() != self.images[index - 1].parent() {
+                        ui.separator();
+                    }
+                    let (rect, response) = ui.allocate_exact_size(
+                        egui::Vec2::splat(FILMSTRIP_CELL),
+                        sense,
+                    );
+                    let response = response.on_hover_text(path.file_name().unwrap_or_default().to_string_lossy());
+
+                    if ui.is_rect_visible(rect) {
+                        let painter = ui.painter();
+                        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(30));
+                        match self.thumbnails.get(path) {
+                            Some(texture) => {
+                                // Fit inside the square cell, keeping the aspect ratio.
+                                let display = view::display_size(rect.size() - egui::Vec2::splat(4.0), texture.size_vec2(), 1.0, FitMode::Window);
+                                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                                painter.image(texture.id(), egui::Rect::from_center_size(rect.center(), display), uv, egui::Color32::WHITE);
+                            }
+                            None if self.failed.contains_key(path) => {
+                                painter.text(rect.center(), egui::Align2::CENTER_CENTER, "⚠", egui::FontId::proportional(20.0), egui::Color32::GRAY);
+                            }
+                            None => {}
+                        }
+                        if index == self.current_index {
+                            painter.rect_stroke(rect, 2.0, egui::Stroke::new(2.0, ui.visuals().selection.stroke.color));
+                        }
+                    }
+
+                    if index == self.current_index && scroll_to_current {
+                        response.scroll_to_me(Some(egui::Align::Center));
+                    }
+                    if response.clicked() {
+                        clicked = Some(index);
+                    }
+                }
+            });
+        });
+        clicked
+    }
+
+    // The toolbar along the bottom: opening and navigating, the image's
+    // title, zoom and the menus, and the status line at the right.
+    fn toolbar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("toolbar")
+            .exact_height(48.0)
+            .show(ctx, |ui| {
+                ui.horizontal_centered(|ui| {
+
+                    if ui.button("📂 Open").on_hover_text("Open an image (Ctrl+O)").clicked() {
+                        self.open_file(ctx);
+                    }
+                    if ui.button("➕").on_hover_text("Add another folder to this session (Ctrl+Shift+O)").clicked() {
+                        self.add_folder_dialog(ctx);
+                    }
+
+                    ui.add_enabled_ui(!self.session.recent.is_empty(), |ui| {
+                        ui.menu_button("🕘", |ui| {
+                            let mut chosen = None;
+                            for path in &self.session.recent {
+                                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                                if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                                    chosen = Some(path.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                            if let Some(path) = chosen {
+                                self.open_recent(ctx, path);
+                            }
+                        }).response.on_hover_text("Recently opened (Ctrl+R cycles)");
+                    });
+
+                    ui.separator();
+
+                    // Reading right to left, the buttons swap places,
+                    // so the left one still points the way Left goes.
+                    if self.settings.right_to_left {
+                        if ui.button("◀ Next").clicked() {
+                            self.go_next(ctx);
+                        }
+                        if ui.button("Prev ▶").clicked() {
+                            self.go_prev(ctx);
+                        }
+                        if ui.small_button("RTL").on_hover_text("Reading right to left; click to go back to left to right").clicked() {
+                            self.settings.right_to_left = false;
+                        }
+                    } else {
+                        if ui.button("◀ Prev").clicked() {
+                            self.go_prev(ctx);
+                        }
+                        if ui.button("Next ▶").clicked() {
+                            self.go_next(ctx);
+                        }
+                    }
+
+                    ui.separator();
+
+                    ui.label(self.title());
+                    if let Some(note) = &self.note
+                        && ui.add(egui::Label::new("📝").sense(egui::Sense::click())).on_hover_text(note).clicked()
+                    {
+                        self.edit_note();
+                    }
+                    if self.loader.is_loading() {
+                        ui.spinner();
+                    }
+                    if !self.failed.is_empty() {
+                        let count = self.failed.len();
+                        let text = if count == 1 { "(1 file failed)".to_string() } else { format!("({count} files failed)") };
+                        if ui.link(text).on_hover_text("Show files that couldn't be loaded").clicked() {
+                            self.show_failed = !self.show_failed;
+                        }
+                    }
+
+                    ui.separator();
+
+                    // Zoom slider. Because we now use a multiplicative zoom model,
+                    // the slider still works fine — it just directly sets self.zoom
+                    // to whatever value the user drags to.
+                    ui.label("Zoom:");
+                    ui.add(
+                        egui::Slider::new(&mut self.zoom, view::MIN_ZOOM..=view::MAX_ZOOM)
+                            .step_by(0.1)
+                            .fixed_decimals(1)
+                    );
+
+                    if ui.button("↺").on_hover_text("Reset zoom").clicked() {
+                        self.reset_view();
+                    }
+
+                    let lock_icon = if self.zoom_locked { "🔒" } else { "🔓" };
+                    ui.toggle_value(&mut self.zoom_locked, lock_icon)
+                        .on_hover_text("Lock zoom against the mouse wheel and pinch gestures (L)");
+
+                    ui.separator();
+
+                    let slideshow_label = if self.slideshow.is_some() { "⏸ Slideshow" } else { "▶ Slideshow" };
+                    if ui.button(slideshow_label).on_hover_text("Start/stop slideshow (S)").clicked() {
+                        self.toggle_slideshow();
+                    }
+                    ui.toggle_value(&mut self.settings.shuffle, "🔀")
+                        .on_hover_text("Shuffle: Next and the slideshow visit every image once, in random order");
+
+                    ui.menu_button("🛠 Tools", |ui| {
+                        let has_images = !self.images.is_empty() && self.url.is_none() && self.tiles.is_none();
+                        let idle = self.batch.is_none();
+
+                        ui.add_enabled_ui(has_images && idle, |ui| {
+                            ui.menu_button("Rotate this image", |ui| {
+                                for rotation in Rotation::ALL {
+                                    if ui.button(rotation.label()).clicked() {
+                                        self.change_files(ctx, Change::Rotate(rotation));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                            ui.menu_button("Rotate all images in folder", |ui| {
+                                for rotation in Rotation::ALL {
+                                    if ui.button(rotation.label()).clicked() {
+                                        self.change_files(ctx, Change::RotateAll(rotation));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                            if ui.add_enabled(self.source_image.is_some(), egui::Button::new("Crop… (C)")).clicked() {
+                                self.toggle_crop();
+                                ui.close_menu();
+                            }
+                            if ui.button("Duplicate this file (Ctrl+D)").clicked() {
+                                self.duplicate_current(ctx);
+                                ui.close_menu();
+                            }
+                            let undo_label = match self.undo.last_action() {
+                                Some(action) => format!("Undo {action} (Ctrl+Z)"),
+                                None => "Undo (Ctrl+Z)".to_string(),
+                            };
+                            if ui.add_enabled(self.undo.last_action().is_some(), egui::Button::new(undo_label)).clicked() {
+                                self.undo_last_edit(ctx);
+                                ui.close_menu();
+                            }
+                        });
+                    });
+
+                    ui.toggle_value(&mut self.eyedropper, "💧")
+                        .on_hover_text("Eyedropper: show the color under the cursor, click to copy (I)");
+
+                    if ui.selectable_label(self.grid.is_some(), "2×2")
+                        .on_hover_text("Compare with the next three images (G)")
+                        .clicked()
+                    {
+                        self.toggle_grid();
+                    }
+                    ui.toggle_value(&mut self.session.show_filmstrip, "🎞")
+                        .on_hover_text("Filmstrip (T)");
+                    ui.toggle_value(&mut self.session.show_info_panel, "ℹ")
+                        .on_hover_text("Image info panel (M)");
+
+                    if ui.button("⚙").on_hover_text("Settings").clicked() {
+                        self.show_settings = !self.show_settings;
+                    }
+
+                    // Status area: progress of a running batch, or the
+                    // outcome of the last one.
+                    if let Some(batch) = &self.batch {
+                        ui.separator();
+                        ui.spinner();
+                        ui.label(batch.status());
+                    } else if let Some(crop) = &self.crop {
+                        ui.separator();
+                        ui.label(match crop.selection() {
+                            Some(s) => format!("Crop to {} × {}: Enter to save, Esc to cancel, Z to zoom in on it", s.width(), s.height()),
+                            None => "Drag over the image, or pull the edges in with the arrow keys".to_string(),
+                        });
+                    } else if let (true, Some(([x, y], [r, g, b, a]))) = (self.eyedropper, self.hovered_pixel) {
+                        ui.separator();
+                        let color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+                        let (swatch, _) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+                        ui.painter().rect_filled(swatch, 2.0, color);
+                        ui.label(format!("({x}, {y})  RGBA({r}, {g}, {b}, {a})  #{r:02X}{g:02X}{b:02X}{a:02X}"));
+                    } else if let Some(message) = &self.status_message {
+                        ui.separator();
+                        ui.label(message);
+                    }
+                });
+            });
+    }
+
+    // Contents of the side info panel.
+    fn info_panel(&self, ui: &mut egui::Ui) {
+        ui.heading("Info");
+        let Some(path) = self.images.get(self.current_index) else {
+            ui.weak("No image open");
+            return;
+        };
+
+        egui::Grid::new("info_grid").num_columns(2).striped(true).show(ui, |ui| {
+            ui.label("Name");
+            ui.label(path.file_name().unwrap_or_default().to_string_lossy());
+            ui.end_row();
+
+            if let Some(url) = &self.url {
+                ui.label("URL");
+                ui.label(url);
+            } else {
+                ui.label("Folder");
+                ui.label(path.parent().map(|p| p.display().to_string()).unwrap_or_default());
+            }
+            ui.end_row();
+
+            if self.url.is_none() {
+                ui.label(if self.folders.len() > 1 { "Folders" } else { "In folder" });
+                let count = self.totals.count();
+                let images = if count == 1 { "1 image".to_string() } else { format!("{count} images") };
+                match self.totals.bytes() {
+                    Some(bytes) => ui.label(format!("{images}, {}", metadata::format_size(bytes))),
+                    None => ui.label(format!("{images}, …")),
+                };
+                ui.end_row();
+            }
+
+            let info = self.info.as_ref().filter(|_| self.info_for.as_ref() == Some(path));
+            if info.is_none() {
+                ui.label("Details");
+                if self.failed.contains_key(path) {
+                    ui.weak("Couldn't be read");
+                } else {
+                    ui.weak("Loading…");
+                }
+                ui.end_row();
+            }
+            if let (Some([width, height]), Some(info)) = (self.image_size(), info) {
+                ui.label("Dimensions");
+                ui.label(format!("{width} × {height}"));
+                ui.end_row();
+
+                ui.label("File size");
+                ui.label(metadata::format_size(info.file_size));
+                ui.end_row();
+
+                // A file whose contents are in another format than its
+                // extension says is worth pointing out: other programs may
+                // refuse it, or open it wrong.
+                ui.label("Format");
+                match info.format {
+                    Some(format) if self.url.is_some() || metadata::extension_matches(path, format) => {
+                        ui.label(metadata::format_name(format));
+                    }
+                    Some(format) => {
+                        let extension = path.extension().unwrap_or_default().to_string_lossy();
+                        ui.colored_label(egui::Color32::YELLOW, format!("{}, named .{extension}", metadata::format_name(format)))
+                            .on_hover_text("The file's contents don't match its extension");
+                    }
+                    None => {
+                        ui.weak("Not recognised from its contents");
+                    }
+                }
+                ui.end_row();
+
+                if let Some(color_type) = info.color_type {
+                    ui.label("Pixels");
+                    ui.label(metadata::describe_color_type(color_type));
+                    ui.end_row();
+                }
+
+                ui.label("Color profile");
+                match &info.color_profile {
+                    Some(profile) if profile.applied => { ui.label(&profile.name); }
+                    Some(profile) => { ui.label(format!("{} (not applied)", profile.name)); }
+                    None => { ui.weak("None embedded"); }
+                }
+                ui.end_row();
+
+                // Every field, with a dash for the ones this camera didn't
+                // fill in, so the list has the same shape from photo to
+                // photo. A file with no EXIF at all just says so.
+                match &info.exif {
+                    Some(exif) if !exif.is_empty() => {
+                        for (label, value) in metadata::exif_fields(exif) {
+                            ui.label(label);
+                            match value {
+                                Some(value) => ui.label(value),
+                                None => ui.weak("—"),
+                            };
+                            ui.end_row();
+                        }
+                    }
+                    Some(_) => {
+                        ui.label("EXIF");
+                        ui.weak("No EXIF data");
+                        ui.end_row();
+                    }
+                    None => {
+                        ui.label("EXIF");
+                        ui.weak("Not read for an image this big");
+                        ui.end_row();
+                    }
+                }
+            }
+        });
+
+        if let Some(info) = self.info.as_ref().filter(|_| self.info_for.as_ref() == Some(path)) {
+            ui.separator();
+            ui.label("Histogram");
+            draw_histogram(ui, &info.histogram);
+        }
+    }
+
+    // Hold this frame back if it comes sooner than the frame rate limit
+    // allows. When nothing is animating egui doesn't draw frames at all,
+    // so this costs nothing while the view is still; it only ever delays
+    // frames that were going to come back to back anyway.
+    fn limit_frame_rate(&mut self) {
+        if self.settings.max_fps > 0 {
+            let min_frame = Duration::from_secs_f32(1.0 / self.settings.max_fps as f32);
+            let elapsed = self.last_frame.elapsed();
+            if elapsed < min_frame {
+                std::thread::sleep(min_frame - elapsed);
+            }
+        }
+        self.last_frame = Instant::now();
+    }
+
+    // A thin bar along the bottom of the view with a marker for where the
+    // current image is in the folder, for a sense of place without the
+    // filmstrip. Clicking or dragging along it jumps to the image at that
+    // point, so it doubles as a quick way across a big folder.
+    fn position_bar(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let panel = ui.max_rect();
+        let rect = egui::Rect::from_min_max(egui::pos2(panel.left(), panel.bottom() - POSITION_BAR_HEIGHT), panel.max);
+        let response = ui.interact(rect, ui.id().with("position_bar"), self.mouse_sense(egui::Sense::click_and_drag()));
+
+        let len = self.images.len();
+        let cell = rect.width() / len as f32;
+        let index_at = |x: f32| (((x - rect.left()) / cell).floor().max(0.0) as usize).min(len - 1);
+
+        // In a big folder one image is narrower than a pixel, so the
+        // marker is kept wide enough to see.
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+        let center = egui::pos2(rect.left() + (self.current_index as f32 + 0.5) * cell, rect.center().y);
+        let marker = egui::Rect::from_center_size(center, egui::vec2(cell.max(POSITION_BAR_HEIGHT), rect.height()));
+        painter.rect_filled(marker.intersect(rect), 0.0, egui::Color32::from_gray(220));
+
+        if (response.clicked() || response.dragged())
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            self.show_index(ctx, index_at(pos.x));
+        }
+        if let Some(pos) = response.hover_pos() {
+            let index = index_at(pos.x);
+            let name = self.images[index].file_name().unwrap_or_default().to_string_lossy();
+            let percent = (index as f32 / (len - 1) as f32 * 100.0).round();
+            response.on_hover_cursor(egui::CursorIcon::PointingHand)
+                .on_hover_text(format!("{name} ({}/{len}, {percent}%)", index + 1));
+        }
+    }
+
+    // Build the title string e.g. "cat.jpg (3/12)", or "scan.tif (3/12),
+    // page 2/10". With --quiet it's just "cat.jpg". Without the extension,
+    // if the settings say so, "cat (3/12)".
+    fn title(&self) -> String {
+        if self.images.is_empty() {
+            return "Gallerust".to_string();
+        }
+        let path = &self.images[self.current_index];
+        let filename = if self.settings.title_extension { path.file_name() } else { path.file_stem() };
+        let filename = filename.unwrap_or_default().to_string_lossy();
+        if self.quiet {
+            return format!("{filename}{}", self.frame_label());
+        }
+        // With several folders merged, say which one this image is from,
+        // and with subfolders included, which of them. Sorted by date
+        // taken, the day it was taken tops those.
+        let root = self.folders.iter().find(|folder| path.starts_with(folder));
+        let mut location = String::new();
+        if self.folders.len() > 1 {
+            let folder = root.and_then(|folder| folder.file_name()).or_else(|| path.parent().and_then(Path::file_name));
+            location += &format!("{} › ", folder.unwrap_or_default().to_string_lossy());
+        }
+        if self.settings.sort_order == scan::SortOrder::Taken
+            && let Some(taken) = self.sort_keys.known_taken(path)
+        {
+            location = format!("{} › {location}", taken.day_label());
+        }
+        if self.settings.include_subfolders
+            && let Some(chapter) = root.and_then(|root| scan::chapter_name(path, root))
+        {
+            location += &format!("{chapter} › ");
+        }
+        format!("{}{location}{filename} ({}/{}){}{}{}", self.flag_mark(), self.current_index + 1, self.images.len(), self.page_label(), self.frame_label(), self.flagged_label())
+    }
+
+    // ", frame 3/20 (paused)" while an animation is paused.
+    fn frame_label(&self) -> String {
+        match &self.playback {
+            Some(playback) if playback.is_paused() => {
+                let (frame, frames) = playback.position();
+                format!(", frame {frame}/{frames} (paused)")
+            }
+            _ => String::new(),
+        }
+    }
+
+    // "⚑ " in front of a flagged image's name.
+    fn flag_mark(&self) -> &'static str {
+        let flagged = self.url.is_none() && self.session.flagged.contains(&self.images[self.current_index]);
+        if flagged { "⚑ " } else { "" }
+    }
+
+    // " (flagged 2/7)" while only visiting flagged images: where this one
+    // is among them, or a dash if it was unflagged after getting here.
+    fn flagged_label(&self) -> String {
+        if !self.flagged_only {
+            return String::new();
+        }
+        let is_flagged = |path: &&PathBuf| self.session.flagged.contains(*path);
+        let total = self.images.iter().filter(is_flagged).count();
+        if self.session.flagged.contains(&self.images[self.current_index]) {
+            let position = self.images[..=self.current_index].iter().filter(is_flagged).count();
+            format!(" (flagged {position}/{total})")
+        } else {
+            format!(" (flagged –/{total})")
+        }
+    }
+
+    // ", page 2/10" for a multi-page file, and nothing otherwise.
+    fn page_label(&self) -> String {
+        if self.page_count > 1 {
+            format!(", page {}/{}", self.page + 1, self.page_count)
+        } else {
+            String::new()
+        }
+    }
+}
+
+// ColorImage is egui's CPU-side image type. We convert the raw RGBA bytes
+// into Color32 values by chunking into groups of 4 bytes.
+// `img` for uploading as a texture, shrunk first if either side is more
+// than `max_side` pixels.
+fn color_image(img: &image::RgbaImage, max_side: usize) -> ColorImage {
+    let shrunk;
+    let mut img = img;
+    let (width, height) = img.dimensions();
+    if width.max(height) as usize > max_side {
+        let scale = max_side as f64 / width.max(height) as f64;
+        let size = [((width as f64 * scale) as u32).clamp(1, max_side as u32), ((height as f64 * scale) as u32).clamp(1, max_side as u32)];
+        shrunk = image::imageops::thumbnail(img, size[0], size[1]);
+        img = &shrunk;
+    }
+    let (width, height) = img.dimensions();
+    let pixels: Vec<egui::Color32> = img
+        .chunks_exact(4)
+        .map(|p| egui::Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
+        .collect();
+    ColorImage {
+        size: [width as usize, height as usize],
+        pixels,
+    }
+}
+
+// The zoom keys. egui names a key by what it types on the user's layout
+// rather than where it sits, so Plus is whichever key types '+': Shift+=
+// on a US keyboard, the key of its own on a German or Nordic one, 1 on a
+// Czech one, and the numpad's + everywhere. '=' counts too, so a US
+// keyboard doesn't need Shift, and so do ']' and '[', which some viewers
+// use and which work the same on any layout that has them.
+fn zoom_in_pressed(input: &egui::InputState) -> bool {
+    input.key_pressed(egui::Key::Plus) || input.key_pressed(egui::Key::Equals) || input.key_pressed(egui::Key::CloseBracket)
+}
+
+// '-' is unshifted almost everywhere, and the numpad's - counts as it.
+fn zoom_out_pressed(input: &egui::InputState) -> bool {
+    input.key_pressed(egui::Key::Minus) || input.key_pressed(egui::Key::OpenBracket)
+}
+
+// Draw the red, green and blue histograms overlaid, each as a filled
+// column per value. Additive-looking translucent colors let overlapping
+// channels read as mixed (all three together look white).
+fn draw_histogram(ui: &mut egui::Ui, histogram: &metadata::Histogram) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 100.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+
+    let max = histogram.max().max(1) as f32;
+    let column_width = rect.width() / 256.0;
+    let channels = [
+        (&histogram.red, egui::Color32::from_rgba_unmultiplied(255, 60, 60, 110)),
+        (&histogram.green, egui::Color32::from_rgba_unmultiplied(60, 255, 60, 110)),
+        (&histogram.blue, egui::Color32::from_rgba_unmultiplied(60, 60, 255, 110)),
+    ];
+
+    for (counts, color) in channels {
+        for (value, &count) in counts.iter().enumerate() {
+            if count == 0 { continue; }
+            let height = count as f32 / max * rect.height();
+            let x = rect.left() + value as f32 * column_width;
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(x, rect.bottom() - height),
+                    egui::pos2(x + column_width.max(1.0), rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+    }
+}
+
+impl eframe::App for Gallerust {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.limit_frame_rate();
+
+        // With reduced motion, egui's own animations finish the moment
+        // they start.
+        let animation_time = if self.reduce_motion() { 0.0 } else { self.animation_time };
+        if ctx.style().animation_time != animation_time {
+            ctx.style_mut(|style| style.animation_time = animation_time);
+        }
+
+        // While a text field has focus (e.g. a note being typed), letters
+        // and arrows belong to it, so none of the keyboard shortcuts apply.
+        let typing = ctx.wants_keyboard_input();
+
+        // With --quiet only the zoom, pan and fullscreen keys work; every
+        // other shortcut is checked against this instead of just `typing`.
+        // A question about changing files takes the keyboard until it's
+        // answered. A presentation keeps moving between images and the
+        // slideshow, which are checked against `navigation`.
+        let presenting = self.presentation.is_some();
+        let navigation = !typing && !self.quiet && self.pending_change.is_none();
+        let shortcuts = navigation && !presenting;
+        if self.pending_change.is_some() && !typing {
+            let (yes, no) = ctx.input_mut(|i| {
+                let mut pressed = |key| i.consume_key(egui::Modifiers::NONE, key);
+                (pressed(egui::Key::Y) || pressed(egui::Key::Enter), pressed(egui::Key::N) || pressed(egui::Key::Escape))
+            });
+            if yes || no {
+                self.answer_change(ctx, yes);
+            }
+        }
+
+        // The user's own key bindings come first, so they can take over a
+        // key from the built-in shortcuts below.
+        if shortcuts && let Some(binding) = self.commands.pressed(ctx, &self.settings.key_commands) {
+            match self.images.get(self.current_index) {
+                Some(path) => {
+                    if let Err(e) = self.commands.run(ctx, binding, path) {
+                        self.status_message = Some(format!("Couldn't run the command on {}: {e}", binding.key));
+                    }
+                }
+                None => self.status_message = Some(format!("No image to run the command on {} with", binding.key)),
+            }
+        }
+        if let Some(failure) = self.commands.poll() {
+            self.status_message = Some(failure);
+        }
+
+        // ── Zoom input ───────────────────────────────────────────────────────
+        // We handle zoom at the top of update(), before the panels are drawn,
+        // so input is never missed regardless of what widget has focus.
+
+        // zoom_delta() is egui's cross-platform normalized zoom input.
+        // It returns a multiplier: 1.1 = 10% larger, 0.9 = 10% smaller, 1.0 = no change.
+        // It handles both scroll wheels AND trackpad pinch-to-zoom gestures automatically,
+        // and egui normalizes the raw platform delta values for us so we don't have to
+        // worry about different mice or OSes reporting wildly different scroll magnitudes.
+        if !typing && !presenting && ctx.input(|i| i.key_pressed(egui::Key::L)) {
+            self.zoom_locked = !self.zoom_locked;
+        }
+        let keyboard_zoom_locked = self.zoom_locked && self.settings.zoom_lock_includes_keyboard;
+
+        let zoom_delta = ctx.input(|i| i.zoom_delta());
+        if zoom_delta != 1.0 && !self.zoom_locked && !self.mouse_ignored() {
+            // egui reports pinch gestures and Ctrl+wheel through the same
+            // zoom event, so look for a raw MouseWheel event this frame to
+            // tell them apart. Only the wheel gets inverted: a pinch is a
+            // physical gesture and "spread fingers = bigger" never changes.
+            // egui-winit already converts line-based (mouse) and pixel-based
+            // (trackpad) wheel deltas with the same sign, so both agree here.
+            let from_wheel = ctx.input(|i| {
+                i.events.iter().any(|e| matches!(e, egui::Event::MouseWheel { .. }))
+            });
+            if from_wheel && self.settings.invert_wheel_zoom {
+                self.apply_zoom_delta(1.0 / zoom_delta);
+            } else {
+                self.apply_zoom_delta(zoom_delta);
+            }
+        }
+
+        // Keyboard zoom uses the same multiplicative model for consistency.
+        // 1.1 and 0.9 match what a single scroll notch typically produces,
+        // so keyboard and scroll wheel feel equivalent.
+        if !keyboard_zoom_locked {
+            if !typing && !presenting && ctx.input(zoom_in_pressed) {
+                self.apply_zoom_delta(1.1);
+            }
+            if !typing && !presenting && ctx.input(zoom_out_pressed) {
+                self.apply_zoom_delta(0.9);
+            }
+        }
+
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::I)) {
+            self.eyedropper = !self.eyedropper;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::M)) {
+            self.session.show_info_panel = !self.session.show_info_panel;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.session.show_filmstrip = !self.session.show_filmstrip;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::P)) {
+            self.session.show_position_bar = !self.session.show_position_bar;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::F) && !i.modifiers.shift && !i.modifiers.command) {
+            self.toggle_flag();
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::F) && i.modifiers.shift) {
+            self.toggle_flagged_only(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::G)) {
+            self.toggle_grid();
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::K)) {
+            self.toggle_color_policy(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::B)) {
+            self.settings.border.show = !self.settings.border.show;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::V) && !i.modifiers.command) {
+            self.settings.vignette.show = !self.settings.vignette.show;
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::N)) {
+            self.edit_note();
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::F5) && !i.modifiers.shift) {
+            self.reload_current(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::F5) && i.modifiers.shift) {
+            self.refresh_listing(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::C) && !i.modifiers.command) {
+            self.toggle_crop();
+        }
+        // Enter saves the crop and Esc cancels it. Esc is taken here so it
+        // doesn't also leave fullscreen below.
+        let mut escape_used = false;
+        if self.crop.is_some() && !typing && self.pending_change.is_none() {
+            if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.change_files(ctx, Change::Crop);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.crop = None;
+                escape_used = true;
+            }
+        }
+
+        // ── Fullscreen ───────────────────────────────────────────────────────
+        // Track the real window state every frame rather than only when we
+        // toggle it, so leaving fullscreen through the window manager is
+        // remembered correctly too. Not while presenting, which forces
+        // fullscreen: what's remembered then is the state to go back to, so
+        // closing the app mid-presentation doesn't open it fullscreen next
+        // time.
+        let fullscreen = ctx.input(|i| i.viewport().fullscreen);
+        if let Some(fullscreen) = fullscreen
+            && self.presentation.is_none()
+        {
+            self.session.fullscreen = fullscreen;
+        }
+        let leave_fullscreen = !typing && !escape_used && self.session.fullscreen && ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        if !presenting && (!typing && ctx.input(|i| i.key_pressed(egui::Key::F11) && !i.modifiers.shift) || leave_fullscreen) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!self.session.fullscreen));
+        }
+
+        // ── Presentation ─────────────────────────────────────────────────────
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::F11) && i.modifiers.shift) {
+            self.start_presentation(ctx);
+        }
+        let escape_down = ctx.input(|i| i.key_down(egui::Key::Escape));
+        if let Some(presentation) = &mut self.presentation {
+            if presentation.hold_escape(escape_down) {
+                self.stop_presentation(ctx);
+            } else if let Some(wait) = presentation.time_to_leave() {
+                ctx.request_repaint_after(wait);
+            }
+        }
+
+        // ── Opening ──────────────────────────────────────────────────────────
+        if shortcuts && ctx.input(|i| i.modifiers.command && !i.modifiers.shift && i.key_pressed(egui::Key::O)) {
+            self.open_file(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.modifiers.command && i.modifiers.shift && i.key_pressed(egui::Key::O)) {
+            self.add_folder_dialog(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Z)) {
+            self.undo_last_edit(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::R)) {
+            self.cycle_recent(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::D)) {
+            self.duplicate_current(ctx);
+        }
+
+        self.poll_loader(ctx);
+        self.totals.poll();
+        self.thumbnails.poll(ctx);
+
+        // ── Slideshow ────────────────────────────────────────────────────────
+        if navigation && ctx.input(|i| i.key_pressed(egui::Key::S)) {
+            self.toggle_slideshow();
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::R) && !i.modifiers.command) {
+            self.go_random(ctx);
+        }
+        if self.track_visibility(ctx) {
+            self.tick_slideshow(ctx);
+            self.tick_animation(ctx);
+        }
+
+        self.poll_batch(ctx);
+        progress::paint(ctx, Progress::combined(self.batch.iter().map(BatchJob::progress).chain([self.thumbnails.progress()])));
+
+        // ── Bottom toolbar panel ─────────────────────────────────────────────
+        // Panels claim space from the edges inward. Bottom panel is declared
+        // first so the central panel fills the remaining space above it.
+        if !self.quiet && !presenting {
+            self.toolbar(ctx);
+        }
+
+        // ── Confirming a change to files ─────────────────────────────────────
+        // Asked as the settings say (see settings::Confirm). The keys that
+        // answer it are handled with the other shortcuts.
+        if let Some(change) = self.pending_change {
+            let name = self.images.get(self.current_index).and_then(|path| path.file_name()).unwrap_or_default().to_string_lossy().into_owned();
+            let (title, question, action) = match change {
+                Change::Rotate(rotation) => ("Rotate this image?", format!("Rotate {name} {} and save over the original?", rotation.label()), "Rotate"),
+                Change::RotateAll(rotation) => (
+                    "Rotate all images?",
+                    format!("Rotate all {} images in this folder {} and save over the originals?", self.images.len(), rotation.label()),
+                    "Rotate all",
+                ),
+                Change::Crop => {
+                    let size = self.crop.as_ref().and_then(Crop::selection).map_or([0, 0], |s| [s.width(), s.height()]);
+                    ("Crop this image?", format!("Save the selected {} × {} area over {name}?", size[0], size[1]), "Crop")
+                }
+            };
+            let mut decision = None;
+            egui::Window::new(title)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(question);
+                    ui.label(if change.undoable() { "Ctrl+Z undoes it." } else { "This can't be undone." });
+                    ui.horizontal(|ui| {
+                        if ui.button(action).on_hover_text("Y or Enter").clicked() {
+                            decision = Some(true);
+                        }
+                        if ui.button("Cancel").on_hover_text("N or Esc").clicked() {
+                            decision = Some(false);
+                        }
+                    });
+                });
+
+            if let Some(confirmed) = decision {
+                self.answer_change(ctx, confirmed);
+            }
+        }
+
+        // ── Failed files window ──────────────────────────────────────────────
+        egui::Window::new("Files that failed to load")
+            .open(&mut self.show_failed)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (path, error) in &self.failed {
+                        ui.label(path.display().to_string());
+                        ui.weak(error);
+                        ui.add_space(4.0);
+                    }
+                });
+            });
+
+        // ── Note editor ──────────────────────────────────────────────────────
+        if let Some((path, text)) = &mut self.note_draft {
+            let mut save = false;
+            let mut close = false;
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            egui::Window::new(format!("Note for {name}"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let editor = ui.add(
+                        egui::TextEdit::multiline(text)
+                            .desired_rows(4)
+                            .hint_text("Type a note. Leave empty to remove it.")
+                    );
+                    // Type straight away without having to click the box.
+                    if ui.memory(|m| m.focused().is_none()) {
+                        editor.request_focus();
+                    }
+                    ui.horizontal(|ui| {
+                        save = ui.button("Save").on_hover_text("Ctrl+Enter").clicked()
+                            || ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Enter));
+                        close = ui.button("Cancel").on_hover_text("Escape").clicked()
+                            || ui.input(|i| i.key_pressed(egui::Key::Escape));
+                    });
+                });
+            if save {
+                let (path, text) = self.note_draft.take().unwrap_or_default();
+                self.save_note(&path, &text);
+            } else if close {
+                self.note_draft = None;
+            }
+        }
+
+        // ── Settings window ──────────────────────────────────────────────────
+        let decode_before = (self.settings.dither, self.settings.color_policy);
+        let sort_before = (self.settings.sort_order, self.settings.name_order);
+        let subfolders_before = self.settings.include_subfolders;
+        let fit_before = (self.settings.portrait_fit, self.settings.landscape_fit);
+        egui::Window::new("Settings")
+            .open(&mut self.show_settings)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| self.settings.ui(ui));
+
+        // Dithering and the color policy are applied at decode time, so
+        // re-decode what's shown when either changes, to show the
+        // difference straight away.
+        if (self.settings.dither, self.settings.color_policy) != decode_before {
+            self.recolor(ctx);
+        }
+        self.loader.share_identical_files(self.settings.share_identical_files);
+        workers::set_limit(self.settings.decode_threads);
+        if (self.settings.sort_order, self.settings.name_order) != sort_before {
+            self.resort(ctx);
+        }
+        if self.settings.include_subfolders != subfolders_before {
+            self.refresh_listing(ctx);
+        }
+        if (self.settings.portrait_fit, self.settings.landscape_fit) != fit_before && self.settings.reset_zoom_on_fit_change {
+            self.reset_view();
+        }
+
+        // ── Filmstrip ────────────────────────────────────────────────────────
+        // Declared after the toolbar, so it sits just above it.
+        if self.session.show_filmstrip && !self.images.is_empty() && self.url.is_none() && !self.quiet && !presenting {
+            egui::TopBottomPanel::bottom("filmstrip")
+                .exact_height(FILMSTRIP_CELL + 16.0)
+                .show(ctx, |ui| {
+                    if let Some(index) = self.filmstrip(ui) {
+                        self.show_index(ctx, index);
+                    }
+                });
+        }
+
+        // ── Info panel ───────────────────────────────────────────────────────
+        // A side panel rather than an overlay, so it never covers the image:
+        // declaring it before the central panel takes its width out of the
+        // space the image is fitted into.
+        if self.session.show_info_panel && !self.quiet && !presenting {
+            egui::SidePanel::right("info_panel")
+                .default_width(260.0)
+                .resizable(true)
+                .show(ctx, |ui| self.info_panel(ui));
+        }
+
+        // ── Central panel (image display area) ──────────────────────────────
+        egui::CentralPanel::default()
+    .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+    .show(ctx, |ui| {
+        if self.grid.is_some() && !self.images.is_empty() {
+            self.draw_grid(ui, ctx);
+        } else if let Some(texture) = self.texture.clone() {
+            // Claim the whole panel so it can be dragged to pan and clicked
+            // for the eyedropper. The painter is clipped to the panel, so a
+            // zoomed image can never spill over the toolbar.
+            let (response, painter) = ui.allocate_painter(
+                ui.available_size(),
+                self.mouse_sense(egui::Sense::click_and_drag()),
+            );
+            let view_rect = response.rect;
+
+            // No need to debounce window resizes here: the image is already
+            // on the GPU at full resolution and scaled while drawing, so a
+            // resize only changes where the texture is drawn. There's no
+            // per-size rescaling or buffer reallocation to coalesce, and
+            // redrawing every frame of a drag keeps the image tracking the
+            // window edge without lag.
+
+            // Keep the zoom sane for this image, fit and window size (see
+            // view::clamp_zoom for why that can't be done once up front).
+            let fit = self.fit_mode(texture.size_vec2());
+            let fit_area = view::fit_area(view_rect.size(), self.settings.fit_percent);
+            self.zoom = view::clamp_zoom(fit_area, texture.size_vec2(), self.zoom, fit);
+            let mut display_size = view::display_size(fit_area, texture.size_vec2(), self.zoom, fit);
+            if self.align_pending {
+                self.pan = view::initial_pan(view_rect.size(), display_size, self.settings.alignment);
+                self.align_pending = self.loader.is_loading();
+            }
+
+            // Z while cropping zooms in on the selection, to look it over
+            // before saving; nothing is cropped until Enter.
+            if let Some(region) = self.crop.as_ref().and_then(Crop::fraction)
+                && !typing
+                && !keyboard_zoom_locked
+                && self.pending_change.is_none()
+                && ctx.input(|i| i.key_pressed(egui::Key::Z) && !i.modifiers.command)
+            {
+                (self.zoom, self.pan) = view::zoom_to_region(view_rect.size(), fit_area, texture.size_vec2(), fit, self.settings.alignment, region);
+                display_size = view::display_size(fit_area, texture.size_vec2(), self.zoom, fit);
+                self.glide = None;
+                // So a load that is still going on doesn't put the pan back.
+                self.align_pending = false;
+            }
+
+            // Drag to pan, and scroll (without Ctrl, which zooms) to pan
+            // as well, like a scrollable page. While cropping, dragging
+            // and the arrow keys belong to the selection, so only
+            // scrolling pans.
+            let cropping = self.crop.is_some();
+            if response.dragged() && !cropping {
+                self.pan += response.drag_delta();
+                self.glide = None;
+            }
+            if response.drag_stopped() && !cropping && self.settings.kinetic_panning && !self.reduce_motion() {
+                self.glide = Glide::start(ctx.input(|i| i.pointer.velocity()));
+            }
+            if let Some(glide) = &mut self.glide {
+                match glide.step() {
+                    Some(distance) => {
+                        let moved = self.pan + distance;
+                        self.pan = view::clamp_pan(view_rect.size(), display_size, moved);
+                        glide.stop_at_edges(self.pan.x != moved.x, self.pan.y != moved.y);
+                        ctx.request_repaint();
+                    }
+                    None => self.glide = None,
+                }
+            }
+            if response.hovered() && !self.mouse_ignored() {
+                self.pan += ctx.input(|i| if self.reduce_motion() { i.raw_scroll_delta } else { i.smooth_scroll_delta });
+            }
+            if !typing && !cropping && !presenting {
+                self.handle_pan_keys(ctx, view_rect.size(), display_size);
+            }
+
+            // An open hand when there's more of the image to drag into
+            // view, closed while dragging. At fit there's nowhere to pan,
+            // so the normal arrow stays. The eyedropper's crosshair below
+            // takes over when it's on.
+            let can_pan = view::pan_limit(view_rect.size(), display_size) != egui::Vec2::ZERO;
+            if self.settings.grab_cursor && can_pan && !cropping && !self.mouse_ignored() {
+                if response.dragged() {
+                    ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+                } else if response.hovered() {
+                    ctx.set_cursor_icon(egui::CursorIcon::Grab);
+                }
+            }
+
+            let mut pan = self.pan;
+            match self.bounce.as_ref().and_then(Bounce::offset) {
+                Some(offset) => {
+                    pan.x += offset;
+                    ctx.request_repaint();
+                }
+                None => self.bounce = None,
+            }
+            let image_rect = view::image_rect(view_rect, display_size, pan, self.settings.alignment);
+            if let Some(color) = self.settings.background.surround(self.info.as_ref()) {
+                painter.rect_filled(view_rect, 0.0, color);
+            }
+            let has_transparency = self.info.as_ref().is_some_and(|info| info.has_transparency);
+            if self.settings.background.shows_checkerboard(has_transparency) {
+                background::paint_checkerboard(&painter, image_rect, &self.checkerboard);
+            }
+            // Filtered to suit how far the image is shrunk or enlarged on
+            // the screen. When stretched, the axis shrunk the most decides.
+            let scale = (display_size / texture.size_vec2()).min_elem() * ctx.pixels_per_point();
+            let texture_id = self.smoothed_texture(ctx, scale).unwrap_or(texture.id());
+            if scale > 1.0 || self.eyedropper || cropping {
+                self.load_full_resolution(ctx);
+            }
+            painter.image(
+                texture_id,
+                image_rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                egui::Color32::WHITE,
+            );
+            if let Some(tiles) = &mut self.tiles {
+                tiles.paint(ctx, &painter, image_rect, view_rect);
+            }
+            self.settings.border.paint(&painter, image_rect);
+            self.settings.vignette.paint(&painter, view_rect);
+
+            if shortcuts && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::E)) {
+                self.export_view(ctx, view_rect.size(), fit);
+            }
+
+            // The current image's note, captioned along the bottom. Hidden
+            // while it's being edited, since the editor shows it anyway.
+            if let Some(note) = &self.note
+                && self.note_draft.is_none()
+            {
+                overlay::draw_text(
+                    &painter,
+                    view_rect.left_bottom() + egui::vec2(12.0, -12.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    note,
+                    self.settings.overlay_text_size,
+                    egui::Color32::WHITE,
+                );
+            }
+
+            // Cropping: the mouse moves the selection's edges, or draws a
+            // new one, and the arrow keys move the right and bottom edges
+            // (Shift for left and top) a pixel at a time, or ten with Ctrl.
+            if let Some(crop) = &mut self.crop {
+                if response.drag_started()
+                    && let Some(origin) = ctx.input(|i| i.pointer.press_origin())
+                {
+                    crop.drag_started(image_rect, origin);
+                }
+                if response.dragged()
+                    && let Some(pos) = response.interact_pointer_pos()
+                {
+                    crop.dragged(image_rect, pos);
+                }
+                if response.drag_stopped() {
+                    crop.drag_stopped();
+                }
+                if let Some(icon) = response.hover_pos().and_then(|pos| crop.cursor(image_rect, pos)) {
+                    ctx.set_cursor_icon(icon);
+                }
+                if !typing {
+                    let (step, near_edges) = ctx.input(|i| (if i.modifiers.command { 10 } else { 1 }, i.modifiers.shift));
+                    for (key, delta) in [
+                        (egui::Key::ArrowLeft, [-step, 0]),
+                        (egui::Key::ArrowRight, [step, 0]),
+                        (egui::Key::ArrowUp, [0, -step]),
+                        (egui::Key::ArrowDown, [0, step]),
+                    ] {
+                        if ctx.input(|i| i.key_pressed(key) && !i.modifiers.alt) {
+                            crop.nudge(delta, near_edges);
+                        }
+                    }
+                }
+                crop.paint(&painter, image_rect, self.settings.overlay_text_size);
+            }
+
+            // Eyedropper: read the source pixel under the cursor, and copy
+            // its hex value to the clipboard on click.
+            self.hovered_pixel = None;
+            if self.eyedropper {
+                if let Some(pos) = response.hover_pos() {
+                    self.hovered_pixel = self.pixel_at(image_rect, pos);
+                    if let Some((_, [r, g, b, a])) = self.hovered_pixel {
+                        ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+                        overlay::draw_text(
+                            &painter,
+                            pos + egui::vec2(16.0, 16.0),
+                            egui::Align2::LEFT_TOP,
+                            &format!("#{r:02X}{g:02X}{b:02X}{a:02X}"),
+                            self.settings.overlay_text_size,
+                            egui::Color32::WHITE,
+                        );
+                    }
+                }
+                if let (true, Some((_, [r, g, b, a]))) = (response.clicked(), self.hovered_pixel) {
+                    ctx.output_mut(|o| o.copied_text = format!("#{r:02X}{g:02X}{b:02X}{a:02X}"));
+                }
+            }
+
+        } else if let Some(error) = self.images.get(self.current_index).and_then(|p| self.failed.get(p)) {
+            overlay::draw_text(
+                ui.painter(),
+                ui.max_rect().center(),
+                egui::Align2::CENTER_CENTER,
+                &format!("Couldn't load this image:\n{error}"),
+                self.settings.overlay_text_size,
+                egui::Color32::LIGHT_RED,
+            );
+        } else if let Some(folder) = self.unavailable_folder.clone() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.colored_label(egui::Color32::LIGHT_RED, format!("{} is no longer available", folder.display()));
+                ui.label("It may have been deleted, or be on a drive or network share that was disconnected.");
+                ui.add_space(8.0);
+                if ui.button("Try again").clicked() && !self.open_path_without_remembering(ctx, folder.clone()) {
+                    self.status_message = Some("Still not available".to_string());
+                }
+                if ui.button("📂 Open another folder…").clicked() {
+                    self.open_folder_dialog(ctx);
+                }
+            });
+        } else if self.loader.is_loading() && self.url.is_some() {
+            ui.centered_and_justified(|ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Downloading…");
+                });
+            });
+        } else if self.loader.is_loading() {
+            ui.centered_and_justified(|ui| {
+                ui.spinner();
+            });
+        } else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Click '📂 Open' to select an image");
+            });
+        }
+
+        if self.session.show_position_bar && self.images.len() > 1 && !presenting {
+            self.position_bar(ui, ctx);
+        }
+
+        // Keyboard navigation. Shift+arrows pan instead (see handle_pan_keys),
+        // and Alt+arrows go back and forward through the viewing history, as
+        // do the back/forward buttons on the side of many mice.
+        // While an animation is paused the arrows step through its frames
+        // instead, until Space resumes it.
+        let cropping = self.crop.is_some();
+        // Presenting, animations just play and the arrows only ever move
+        // between images (see presentation.rs).
+        let frame_keys = !typing && !presenting;
+        let stepping = frame_keys && self.playback.as_ref().is_some_and(Playback::is_paused);
+        let plain = |i: &egui::InputState| !typing && !cropping && !i.modifiers.shift && !i.modifiers.alt;
+        if frame_keys && ctx.input(|i| i.key_pressed(egui::Key::Space)) && let Some(playback) = &mut self.playback {
+            playback.toggle_pause();
+        }
+        if frame_keys && ctx.input(|i| (i.key_pressed(egui::Key::ArrowRight) && stepping && plain(i)) || i.key_pressed(egui::Key::Period)) {
+            self.step_frame(ctx, 1);
+        }
+        if frame_keys && ctx.input(|i| (i.key_pressed(egui::Key::ArrowLeft) && stepping && plain(i)) || i.key_pressed(egui::Key::Comma)) {
+            self.step_frame(ctx, -1);
+        }
+        let plain = |i: &egui::InputState| plain(i) && !stepping;
+        // Reading right to left, Left is the way forward. Animation frames
+        // and history stay as they are: they're time, not reading order.
+        let (forward_key, backward_key) = if self.settings.right_to_left {
+            (egui::Key::ArrowLeft, egui::Key::ArrowRight)
+        } else {
+            (egui::Key::ArrowRight, egui::Key::ArrowLeft)
+        };
+        if navigation && ctx.input(|i| i.key_pressed(forward_key) && plain(i)) {
+            if ctx.input(|i| i.modifiers.command) {
+                self.go_chapter(ctx, true);
+            } else {
+                self.go_next(ctx);
+            }
+        }
+        if navigation && ctx.input(|i| i.key_pressed(backward_key) && plain(i)) {
+            if ctx.input(|i| i.modifiers.command) {
+                self.go_chapter(ctx, false);
+            } else {
+                self.go_prev(ctx);
+            }
+        }
+        // Shift on its own pans, but with Ctrl it jumps.
+        let jump = |i: &egui::InputState| !typing && !cropping && !stepping && i.modifiers.command && i.modifiers.shift && !i.modifiers.alt;
+        let jump_size = self.settings.jump_size.max(1) as isize;
+        if navigation && ctx.input(|i| i.key_pressed(forward_key) && jump(i)) {
+            self.go_by(ctx, jump_size);
+        }
+        if navigation && ctx.input(|i| i.key_pressed(backward_key) && jump(i)) {
+            self.go_by(ctx, -jump_size);
+        }
+        let mouse = !self.mouse_ignored();
+        if !self.quiet && !presenting && ctx.input(|i| {
+            (!typing && i.key_pressed(egui::Key::ArrowLeft) && i.modifiers.alt)
+                || (mouse && i.pointer.button_pressed(egui::PointerButton::Extra1))
+        }) {
+            self.go_back(ctx);
+        }
+        if !self.quiet && !presenting && ctx.input(|i| {
+            (!typing && i.key_pressed(egui::Key::ArrowRight) && i.modifiers.alt)
+                || (mouse && i.pointer.button_pressed(egui::PointerButton::Extra2))
+        }) {
+            self.go_forward(ctx);
+        }
+        if shortcuts && ctx.input(|i| i.key_pressed(egui::Key::Backspace)) {
+            self.go_to_previous_image(ctx);
+        }
+    });
+
+        // Over whatever cursor the view asked for above.
+        if self.presentation.is_some() {
+            ctx.set_cursor_icon(egui::CursorIcon::None);
+        }
+    }
+
+    // Called by eframe periodically and on shutdown (persistence feature).
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.settings.save(storage);
+        self.session.save(storage);
+    }
+
+    // Drop the slideshow explicitly on exit so the keep-awake request is
+    // released even if eframe keeps the app alive a little longer.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.slideshow = None;
     }
 }
\ No newline at end of file
@@ -0,0 +1,311 @@
+// Information about the current image beyond its pixels: file details,
+// EXIF tags and a color histogram. Gathered on the loader thread alongside
+// the decode, so the info panel never has to touch the disk itself.
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::{ColorType, ImageFormat, RgbaImage};
+
+use crate::scan;
+
+// EXIF tags worth showing, in display order. Cameras write dozens more,
+// but these are the ones people actually look for.
+const EXIF_TAGS: &[(exif::Tag, &str)] = &[
+    (exif::Tag::Make, "Camera make"),
+    (exif::Tag::Model, "Camera model"),
+    (exif::Tag::LensModel, "Lens"),
+    (exif::Tag::DateTimeOriginal, "Taken"),
+    (exif::Tag::ExposureTime, "Exposure"),
+    (exif::Tag::FNumber, "Aperture"),
+    (exif::Tag::PhotographicSensitivity, "ISO"),
+    (exif::Tag::FocalLength, "Focal length"),
+    (exif::Tag::Flash, "Flash"),
+    (exif::Tag::Software, "Software"),
+];
+
+#[derive(Clone)]
+pub struct ImageInfo {
+    pub file_size: u64,
+    // The format the file's contents are in, which its extension may not
+    // agree with. None if they're in no format we recognise.
+    pub format: Option<ImageFormat>,
+    // How the file stored its pixels, before they were converted to 8-bit
+    // RGBA for showing. Filled in by the loader, which has the decoded
+    // image.
+    pub color_type: Option<ColorType>,
+    // (label, value) pairs for the tags in EXIF_TAGS that the file has:
+    // empty for a file with no EXIF at all, as most PNGs and every BMP.
+    // None if it wasn't looked for.
+    pub exif: Option<Vec<(&'static str, String)>>,
+    // Boxed: three 256-entry tables are too big to move around inline.
+    pub histogram: Box<Histogram>,
+    // Whether any pixel is less than fully opaque.
+    pub has_transparency: bool,
+    // The mean color of the visible pixels, for Background::AverageColor.
+    // Worked out once here rather than on every redraw.
+    pub average_color: [u8; 3],
+    // The ICC profile embedded in the file, if any. Filled in by the
+    // loader, which is what reads it.
+    pub color_profile: Option<ColorProfile>,
+}
+
+#[derive(Clone)]
+pub struct ColorProfile {
+    // E.g. "Display P3".
+    pub name: String,
+    // Whether the pixels were converted according to it (see color.rs):
+    // not when color management is off or the profile couldn't be used.
+    pub applied: bool,
+}
+
+impl ImageInfo {
+    pub fn read(path: &Path, pixels: &RgbaImage) -> Self {
+        let exif = match std::fs::File::open(path) {
+            Ok(file) => Some(read_exif(&mut BufReader::new(file))),
+            Err(_) => Some(Vec::new()),
+        };
+        let histogram = Histogram::of(pixels);
+        Self {
+            file_size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            format: scan::content_format(path),
+            color_type: None,
+            exif,
+            average_color: histogram.mean(),
+            histogram: Box::new(histogram),
+            has_transparency: has_transparency(pixels),
+            color_profile: None,
+        }
+    }
+
+    // The same, without the EXIF tags, for an image shown in tiles:
+    // kamadak-exif reads a whole TIFF into memory to find them.
+    pub fn without_exif(path: &Path, pixels: &RgbaImage) -> Self {
+        let histogram = Histogram::of(pixels);
+        Self {
+            file_size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            format: scan::content_format(path),
+            color_type: None,
+            exif: None,
+            average_color: histogram.mean(),
+            histogram: Box::new(histogram),
+            has_transparency: has_transparency(pixels),
+            color_profile: None,
+        }
+    }
+
+    // The same, for an image that only exists in memory (e.g. downloaded).
+    pub fn from_bytes(bytes: &[u8], pixels: &RgbaImage) -> Self {
+        let histogram = Histogram::of(pixels);
+        Self {
+            file_size: bytes.len() as u64,
+            format: image::guess_format(bytes).ok(),
+            color_type: None,
+            exif: Some(read_exif(&mut Cursor::new(bytes))),
+            average_color: histogram.mean(),
+            histogram: Box::new(histogram),
+            has_transparency: has_transparency(pixels),
+            color_profile: None,
+        }
+    }
+}
+
+// Every EXIF field the info panel lists, in order, with its value in this
+// file if it has it.
+pub fn exif_fields<'a>(found: &'a [(&'static str, String)]) -> impl Iterator<Item = (&'static str, Option<&'a str>)> {
+    EXIF_TAGS.iter().map(|&(_, label)| (label, found.iter().find(|(l, _)| *l == label).map(|(_, value)| value.as_str())))
+}
+
+fn read_exif(reader: &mut (impl BufRead + Seek)) -> Vec<(&'static str, String)> {
+    let Ok(exif) = exif::Reader::new().read_from_container(reader) else {
+        return Vec::new();
+    };
+
+    EXIF_TAGS.iter()
+        .filter_map(|&(tag, label)| {
+            let field = exif.get_field(tag, exif::In::PRIMARY)?;
+            let value = field.display_value().with_unit(&exif).to_string();
+            // Strings are shown with their quotes by display_value().
+            Some((label, value.trim_matches('"').to_string()))
+        })
+        .collect()
+}
+
+// When a photo was taken, to the second, for sorting by date taken and
+// grouping by day. Compares in time order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Taken {
+    // Year, month and day.
+    pub date: [u16; 3],
+    pub seconds: u32,
+}
+
+impl Taken {
+    // When the file at `path` was taken: its EXIF DateTimeOriginal, which
+    // is the camera's clock as the shutter fired, or, for files without
+    // one, its modification time.
+    pub fn read(path: &Path, modified: SystemTime) -> Self {
+        Self::from_exif(path).unwrap_or_else(|| Self::from_system_time(modified))
+    }
+
+    fn from_exif(path: &Path) -> Option<Self> {
+        let file = std::fs::File::open(path).ok()?;
+        let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+        let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+        let exif::Value::Ascii(ref values) = field.value else { return None; };
+        let taken = exif::DateTime::from_ascii(values.first()?).ok()?;
+        Some(Self {
+            date: [taken.year, taken.month as u16, taken.day as u16],
+            seconds: taken.hour as u32 * 3600 + taken.minute as u32 * 60 + taken.second as u32,
+        })
+    }
+
+    // A file time as a date. Without a time zone library this is the date
+    // in UTC, whereas cameras write local time, so an evening photo with
+    // no EXIF date can land on the day after.
+    // Times before 1970 aren't supported, and count as its first moment:
+    // a file can't really have been written then, so a clock that says so
+    // is wrong anyway.
+    fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        // Days since 1970 to a calendar date, from Howard Hinnant's
+        // "chrono-Compatible Low-Level Date Algorithms": count in 400-year
+        // eras of years starting in March, so the leap day comes last.
+        let days = (since_epoch / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let march_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+        let month = if march_month < 10 { march_month + 3 } else { march_month - 9 };
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+        Self { date: [year as u16, month as u16, day as u16], seconds: (since_epoch % 86_400) as u32 }
+    }
+
+    // e.g. "2024-05-17".
+    pub fn day_label(self) -> String {
+        let [year, month, day] = self.date;
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+}
+
+fn has_transparency(pixels: &RgbaImage) -> bool {
+    pixels.pixels().any(|p| p.0[3] < 255)
+}
+
+// Per-channel counts of each 0-255 value, ignoring fully transparent
+// pixels since their color isn't visible.
+#[derive(Clone)]
+pub struct Histogram {
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+}
+
+impl Histogram {
+    pub fn of(pixels: &RgbaImage) -> Self {
+        let mut histogram = Self { red: [0; 256], green: [0; 256], blue: [0; 256] };
+        for pixel in pixels.pixels() {
+            let [r, g, b, a] = pixel.0;
+            if a == 0 { continue; }
+            histogram.red[r as usize] += 1;
+            histogram.green[g as usize] += 1;
+            histogram.blue[b as usize] += 1;
+        }
+        histogram
+    }
+
+    pub fn max(&self) -> u32 {
+        self.red.iter().chain(&self.green).chain(&self.blue).copied().max().unwrap_or(0)
+    }
+
+    // The mean of each channel, or black for an image with no visible
+    // pixels at all.
+    pub fn mean(&self) -> [u8; 3] {
+        let mean = |counts: &[u32; 256]| {
+            let total: u64 = counts.iter().map(|&n| n as u64).sum();
+            let sum: u64 = counts.iter().enumerate().map(|(value, &n)| value as u64 * n as u64).sum();
+            sum.checked_div(total).unwrap_or(0) as u8
+        };
+        [mean(&self.red), mean(&self.green), mean(&self.blue)]
+    }
+}
+
+// e.g. "JPEG", for the info panel.
+pub fn format_name(format: ImageFormat) -> String {
+    match format {
+        ImageFormat::Jpeg => "JPEG".to_string(),
+        ImageFormat::Png => "PNG".to_string(),
+        ImageFormat::Gif => "GIF".to_string(),
+        ImageFormat::WebP => "WebP".to_string(),
+        ImageFormat::Bmp => "BMP".to_string(),
+        ImageFormat::Tiff => "TIFF".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+// Whether `path`'s extension is one that `format` goes by: false for a
+// JPEG named ".png". Files without an extension don't claim any format,
+// so they never disagree.
+pub fn extension_matches(path: &Path, format: ImageFormat) -> bool {
+    path.extension().is_none_or(|ext| ImageFormat::from_extension(ext) == Some(format))
+}
+
+// e.g. "RGB with alpha, 16 bits per channel".
+pub fn describe_color_type(color_type: ColorType) -> String {
+    let channels = match color_type {
+        ColorType::L8 | ColorType::L16 => "Grayscale",
+        ColorType::La8 | ColorType::La16 => "Grayscale with alpha",
+        ColorType::Rgb8 | ColorType::Rgb16 | ColorType::Rgb32F => "RGB",
+        _ => "RGB with alpha",
+    };
+    let depth = match color_type {
+        ColorType::Rgb32F | ColorType::Rgba32F => "32-bit floating point".to_string(),
+        other => format!("{} bits per channel", other.bytes_per_pixel() / other.channel_count() * 8),
+    };
+    format!("{channels}, {depth}")
+}
+
+// e.g. "3.4 MB". Decimal units, matching what file managers show.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn taken_at(seconds: u64) -> Taken {
+        Taken::from_system_time(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn system_time_to_calendar_date() {
+        let epoch = taken_at(0);
+        assert_eq!((epoch.date, epoch.seconds), ([1970, 1, 1], 0));
+        // A leap day, an hour, a minute and a second in.
+        let leap_day = taken_at(19_782 * 86_400 + 3661);
+        assert_eq!((leap_day.date, leap_day.seconds), ([2024, 2, 29], 3661));
+        // The day after 2000's leap day, where a 400-year era begins.
+        assert_eq!(taken_at(11_017 * 86_400).date, [2000, 3, 1]);
+        assert_eq!(taken_at(11_017 * 86_400 - 1).date, [2000, 2, 29]);
+    }
+
+    #[test]
+    fn times_before_1970_count_as_1970() {
+        let before = Taken::from_system_time(UNIX_EPOCH - Duration::from_secs(86_400 * 365));
+        assert_eq!((before.date, before.seconds), ([1970, 1, 1], 0));
+    }
+}
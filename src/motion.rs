@@ -0,0 +1,74 @@
+// Reduced motion: turning the interface's animations into instant changes,
+// for people who find movement on screen distracting or worse, and for
+// laptops where every animated frame costs battery.
+//
+// What moves, and what becomes of it:
+//
+// - the bounce at the first and last image (bounce.rs) doesn't happen;
+// - a drag let go of with the image still moving stops where it is,
+//   rather than gliding on (glide.rs);
+// - scrolling to pan jumps straight to where the wheel turned to, instead
+//   of egui gliding there over a few frames;
+// - egui's own animations (panels and headers opening, tooltips fading)
+//   take no time at all.
+//
+// Animated images still play, since that's the image itself rather than
+// the interface; Space pauses one.
+//
+// By default we go along with the desktop's own setting, read once at
+// startup: "Animation effects" on Windows, and the GNOME setting of the
+// same name (also used by most GTK desktops) on Linux.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReduceMotion {
+    // Whatever the desktop is set to.
+    System,
+    Always,
+    Never,
+}
+
+impl ReduceMotion {
+    pub const ALL: [ReduceMotion; 3] = [ReduceMotion::System, ReduceMotion::Always, ReduceMotion::Never];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReduceMotion::System => "Like the rest of the desktop",
+            ReduceMotion::Always => "Always",
+            ReduceMotion::Never => "Never",
+        }
+    }
+
+    // Whether to reduce motion, given what the desktop asks for (see
+    // system_prefers_reduced).
+    pub fn applies(self, system: bool) -> bool {
+        match self {
+            ReduceMotion::System => system,
+            ReduceMotion::Always => true,
+            ReduceMotion::Never => false,
+        }
+    }
+}
+
+// Whether the desktop has animations turned off. Anything we can't find
+// out counts as no.
+#[cfg(target_os = "windows")]
+pub fn system_prefers_reduced() -> bool {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{SPI_GETCLIENTAREAANIMATION, SystemParametersInfoW};
+    let mut enabled: i32 = 1;
+    // SAFETY: SPI_GETCLIENTAREAANIMATION writes a single BOOL through the
+    // pointer, which points at one.
+    let ok = unsafe { SystemParametersInfoW(SPI_GETCLIENTAREAANIMATION, 0, (&mut enabled as *mut i32).cast(), 0) };
+    ok != 0 && enabled == 0
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn system_prefers_reduced() -> bool {
+    // Not every desktop has gsettings, or this key; either way the answer
+    // is no.
+    std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "enable-animations"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "false")
+}
@@ -0,0 +1,36 @@
+// Short text notes attached to images.
+//
+// A note lives in a plain text "sidecar" file next to the image, named
+// after it with ".txt" added ("IMG_0042.jpg" -> "IMG_0042.jpg.txt"). That
+// keeps notes with the photos when a folder is copied or backed up, and
+// they can be read or edited with any text editor, with no database to
+// look after.
+use std::path::{Path, PathBuf};
+
+pub fn sidecar_path(image: &Path) -> PathBuf {
+    let mut name = image.file_name().unwrap_or_default().to_os_string();
+    name.push(".txt");
+    image.with_file_name(name)
+}
+
+// The note for `image`, if it has one.
+pub fn load(image: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(sidecar_path(image)).ok()?;
+    let text = text.trim_end();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+// Save the note for `image`. An empty note deletes the sidecar file
+// rather than leaving an empty one behind.
+pub fn save(image: &Path, text: &str) -> std::io::Result<()> {
+    let path = sidecar_path(image);
+    let text = text.trim_end();
+    if text.is_empty() {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        std::fs::write(path, format!("{text}\n"))
+    }
+}
@@ -0,0 +1,33 @@
+// Text drawn directly over the image.
+//
+// Widgets in panels get their look from the egui style, but text painted over
+// a photo has to stay readable on anything from a white sky to a black night
+// shot. Every overlay goes through draw_text() so they all share the same
+// backdrop, padding and user-configured size. egui's text is already
+// antialiased and laid out in points, so it stays crisp at any DPI scale.
+// Right-to-left text is put in reading order first (see text.rs).
+use eframe::egui::{self, Align2, Color32, FontId, Painter, Pos2, Rect};
+
+use crate::text;
+
+const PADDING: f32 = 6.0;
+const BACKDROP: Color32 = Color32::from_black_alpha(180);
+
+// Draw `text` anchored at `pos` (e.g. Align2::LEFT_TOP puts the top-left
+// corner of the backdrop at `pos`) and return the area it covered, so
+// callers can stack several overlays without overlapping.
+pub fn draw_text(
+    painter: &Painter,
+    pos: Pos2,
+    anchor: Align2,
+    text: &str,
+    size: f32,
+    color: Color32,
+) -> Rect {
+    let galley = painter.layout_no_wrap(text::visual_order(text).into_owned(), FontId::proportional(size), color);
+    let backdrop = anchor.anchor_size(pos, galley.size() + egui::Vec2::splat(PADDING * 2.0));
+
+    painter.rect_filled(backdrop, 4.0, BACKDROP);
+    painter.galley(backdrop.min + egui::Vec2::splat(PADDING), galley, color);
+    backdrop
+}
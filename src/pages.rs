@@ -0,0 +1,119 @@
+// Files with more than one page in them.
+//
+// A TIFF can hold a whole sequence of images, one after another: a scanned
+// document, a fax, or a stack of exposures. We treat each page as an image
+// of its own, so Next on page 2 of 10 shows page 3, and Next on the last
+// page moves on to the following file, much as if the pages had been
+// saved as separate files. Every other format has exactly one page.
+//
+// The image crate only ever decodes the first page, so pages after that
+// are read with the tiff crate (the same one the image crate uses under
+// the hood) and converted here.
+//
+// PDFs would be the obvious next container to open this way, but drawing
+// a PDF page needs a full PDF renderer (pdfium or MuPDF, both large native
+// libraries), which is a lot to bundle with a small image viewer.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer};
+use tiff::ColorType;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+use crate::decode;
+
+// A corrupt file could claim an endless chain of pages. Nobody pages
+// through more than this in an image viewer.
+const MAX_PAGES: usize = 10_000;
+
+// How many pages `path` has. Anything we can't read as a TIFF counts as
+// one page, and reading it will report what's actually wrong.
+pub fn count(path: &Path) -> usize {
+    page_directories(path).map_or(1, |pages| pages.len().max(1))
+}
+
+// Decode page `page` (counting from 0) of `path`, upright.
+pub fn open(path: &Path, page: usize) -> Result<DynamicImage, String> {
+    // The first page goes through the normal decoder, which also handles
+    // every other format and the EXIF orientation.
+    if page == 0 {
+        return decode::open(path).map_err(|e| e.to_string());
+    }
+
+    let pages = page_directories(path).ok_or("not a multi-page file")?;
+    let &directory = pages.get(page).ok_or_else(|| format!("there's no page {}", page + 1))?;
+
+    let mut decoder = tiff_decoder(path).map_err(|e| e.to_string())?;
+    decoder.seek_to_image(directory).map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let color = decoder.colortype().map_err(|e| e.to_string())?;
+    let data = decoder.read_image().map_err(|e| e.to_string())?;
+    to_image(width, height, color, data)
+        .ok_or_else(|| format!("page {} has an unsupported pixel format ({color:?})", page + 1))
+}
+
+pub fn is_tiff(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tif") || ext.eq_ignore_ascii_case("tiff"))
+}
+
+fn tiff_decoder(path: &Path) -> tiff::TiffResult<Decoder<BufReader<File>>> {
+    Decoder::new(BufReader::new(File::open(path)?))
+}
+
+// The index of each page's directory in the file's chain of directories,
+// or None for files that aren't TIFFs.
+//
+// Not every directory is a page: scanners and cameras often add a small
+// preview after the full image, marked as "reduced resolution" in its
+// NewSubfileType tag, and paging onto that would look like a glitch.
+fn page_directories(path: &Path) -> Option<Vec<usize>> {
+    if !is_tiff(path) {
+        return None;
+    }
+    let mut decoder = tiff_decoder(path).ok()?;
+    let mut pages = Vec::new();
+    let mut directory = 0;
+    loop {
+        let subfile_type: u32 = decoder.find_tag_unsigned(Tag::NewSubfileType).ok().flatten().unwrap_or(0);
+        if subfile_type & 1 == 0 {
+            pages.push(directory);
+        }
+        if !decoder.more_images() || directory + 1 >= MAX_PAGES || decoder.next_image().is_err() {
+            break;
+        }
+        directory += 1;
+    }
+    Some(pages)
+}
+
+// Wrap a decoded page's samples in the matching DynamicImage. Covers the
+// layouts that turn up in practice; anything more exotic (CMYK, floating
+// point, palettes) only opens if it's the first page.
+pub fn to_image(width: u32, height: u32, color: ColorType, data: DecodingResult) -> Option<DynamicImage> {
+    match (color, data) {
+        // Black and white pages, as in faxes and most scanned documents,
+        // pack eight pixels into a byte, with each row starting on a fresh
+        // byte.
+        (ColorType::Gray(1), DecodingResult::U8(packed)) => {
+            let row_bytes = (width as usize).div_ceil(8);
+            let mut pixels = Vec::with_capacity(width as usize * height as usize);
+            for row in packed.chunks(row_bytes).take(height as usize) {
+                pixels.extend((0..width as usize).map(|x| if row[x / 8] & (0x80 >> (x % 8)) != 0 { 255 } else { 0 }));
+            }
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+        (ColorType::RGB(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        (ColorType::Gray(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16),
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16),
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16),
+        _ => None,
+    }
+}
@@ -0,0 +1,78 @@
+// Which images to decode ahead of time, so the next one is ready the
+// moment it's asked for.
+//
+// Most browsing goes one way: Next, Next, Next. So once the last few steps
+// have all gone the same way we decode the next two images in that
+// direction and nothing behind, which is where the user won't be going.
+// Until then (just opened a folder, or going back and forth comparing two
+// shots) we hedge with one on either side.
+//
+// Each prefetched image is a full decoded copy in memory, so this is kept
+// to two at a time.
+use std::collections::VecDeque;
+
+// How many steps in a row in one direction count as a habit.
+const STREAK: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Forward,
+    Backward,
+}
+
+// The last few moves through the folder.
+#[derive(Default)]
+pub struct Direction {
+    recent: VecDeque<Step>,
+}
+
+impl Direction {
+    // Note a move from image `from` to image `to` in a folder of `len`
+    // images. Anything that isn't a single step (a jump from the
+    // filmstrip, a random image) breaks the pattern.
+    pub fn moved(&mut self, from: usize, to: usize, len: usize) {
+        let step = if to == (from + 1) % len {
+            Step::Forward
+        } else if from == (to + 1) % len {
+            Step::Backward
+        } else {
+            self.recent.clear();
+            return;
+        };
+        self.recent.push_back(step);
+        if self.recent.len() > STREAK {
+            self.recent.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+
+    // The direction of the current streak, if there is one.
+    fn streak(&self) -> Option<Step> {
+        let &first = self.recent.front()?;
+        (self.recent.len() == STREAK && self.recent.iter().all(|&step| step == first)).then_some(first)
+    }
+
+    // The images worth decoding ahead of time from `current`, most likely
+    // first. With `wrap`, stepping off either end comes round to the other.
+    pub fn targets(&self, current: usize, len: usize, wrap: bool) -> Vec<usize> {
+        let offset = |by: isize| {
+            let index = current as isize + by;
+            if wrap {
+                Some(index.rem_euclid(len as isize) as usize)
+            } else {
+                (0..len as isize).contains(&index).then_some(index as usize)
+            }
+        };
+        let offsets: [isize; 2] = match self.streak() {
+            Some(Step::Forward) => [1, 2],
+            Some(Step::Backward) => [-1, -2],
+            None => [1, -1],
+        };
+        let mut targets: Vec<usize> = offsets.into_iter().filter_map(offset).filter(|&index| index != current).collect();
+        targets.dedup();
+        targets
+    }
+}
@@ -0,0 +1,53 @@
+// Presentation mode: a gallery left running on a screen, unattended.
+// Started with --present or Shift+F11, it puts together several things
+// that are otherwise separate:
+//
+// - the window goes fullscreen, and F11 and Escape no longer leave it;
+// - the toolbar, filmstrip, info panel and position bar are hidden, and
+//   so is the mouse cursor;
+// - the slideshow starts, as the settings have it;
+// - the mouse does nothing, as in keyboard-only mode;
+// - of the keys, only the arrows (Left and Right, and with Ctrl or
+//   Ctrl+Shift to jump), moving between images, and S, pausing the
+//   slideshow, work. Nothing that changes files, opens dialogs or runs
+//   commands, no panels to toggle back on, and no zooming, panning,
+//   viewing history or stepping through an animation's frames, which
+//   just play.
+//
+// The only way out is holding Escape for a couple of seconds, so a
+// passer-by tapping keys can't end it. Leaving puts fullscreen and the
+// slideshow back the way they were before.
+use std::time::{Duration, Instant};
+
+// How long Escape has to be held to leave.
+const HOLD_TO_LEAVE: Duration = Duration::from_secs(2);
+
+pub struct Presentation {
+    // What to go back to afterwards.
+    pub was_fullscreen: bool,
+    pub had_slideshow: bool,
+    // Since when Escape has been held down, if it is.
+    escape_held_since: Option<Instant>,
+}
+
+impl Presentation {
+    pub fn new(was_fullscreen: bool, had_slideshow: bool) -> Self {
+        Self { was_fullscreen, had_slideshow, escape_held_since: None }
+    }
+
+    // Call every frame with whether Escape is down. True once it's been
+    // held long enough to leave.
+    pub fn hold_escape(&mut self, down: bool) -> bool {
+        if !down {
+            self.escape_held_since = None;
+            return false;
+        }
+        self.escape_held_since.get_or_insert_with(Instant::now).elapsed() >= HOLD_TO_LEAVE
+    }
+
+    // How much longer Escape needs holding, while it's held, so a frame
+    // can be asked for then even if nothing else is going on.
+    pub fn time_to_leave(&self) -> Option<Duration> {
+        self.escape_held_since.map(|since| HOLD_TO_LEAVE.saturating_sub(since.elapsed()))
+    }
+}
@@ -0,0 +1,83 @@
+// A thin bar across the top of the window while something long is going
+// on in the background, filling up as it goes and gone once it's done.
+//
+// Each long-running job keeps its own count of how far it's got (a batch
+// of rotations, a round of thumbnails for the filmstrip) and the UI adds
+// them all up into one bar once a frame. So the feedback looks the same
+// whatever is running, and two jobs at once share a bar rather than
+// stacking up indicators.
+use eframe::egui::{self, Color32, Rect};
+
+// How tall the bar is, in points.
+const HEIGHT: f32 = 3.0;
+
+// How far a job has got: `done` of `total` steps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn new(done: usize, total: usize) -> Self {
+        Self { done, total }
+    }
+
+    // All the jobs in `parts` as one. Jobs count for their number of
+    // steps, so a batch of 500 isn't swamped by a few thumbnails.
+    pub fn combined(parts: impl IntoIterator<Item = Progress>) -> Self {
+        parts.into_iter().fold(Self::default(), |sum, part| Self::new(sum.done + part.done, sum.total + part.total))
+    }
+
+    pub fn running(self) -> bool {
+        self.done < self.total
+    }
+
+    // From 0 to 1.
+    pub fn fraction(self) -> f32 {
+        if self.total == 0 { 1.0 } else { (self.done as f32 / self.total as f32).clamp(0.0, 1.0) }
+    }
+}
+
+// Draw the bar over everything else at the top of the window, if
+// `progress` is still running.
+pub fn paint(ctx: &egui::Context, progress: Progress) {
+    if !progress.running() {
+        return;
+    }
+    let screen = ctx.screen_rect();
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("progress")));
+    let track = Rect::from_min_size(screen.min, egui::vec2(screen.width(), HEIGHT));
+    painter.rect_filled(track, 0.0, Color32::from_black_alpha(96));
+    let filled = Rect::from_min_size(track.min, egui::vec2(track.width() * progress.fraction(), HEIGHT));
+    painter.rect_filled(filled, 0.0, ctx.style().visuals.selection.bg_fill);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combined_adds_up_the_steps() {
+        // A batch of 500 half done and a round of 10 thumbnails not started
+        // weigh in by their steps, so the bar is nearly half full.
+        let all = Progress::combined([Progress::new(250, 500), Progress::new(0, 10), Progress::default()]);
+        assert_eq!(all, Progress::new(250, 510));
+        assert!(all.running());
+        assert!((all.fraction() - 250.0 / 510.0).abs() < 1e-6);
+
+        // Still running while any one job is.
+        assert!(Progress::combined([Progress::new(500, 500), Progress::new(9, 10)]).running());
+        assert!(!Progress::combined([Progress::new(500, 500), Progress::new(10, 10)]).running());
+    }
+
+    #[test]
+    fn nothing_to_do_is_finished() {
+        // With no jobs at all there's nothing left, so the bar would be
+        // full rather than dividing by zero, and it isn't drawn.
+        let none = Progress::combined([]);
+        assert_eq!(none, Progress::default());
+        assert_eq!(none.fraction(), 1.0);
+        assert!(!none.running());
+    }
+}
@@ -0,0 +1,34 @@
+// Fetching an image from the web, for a URL given on the command line.
+//
+// This is deliberately minimal: one blocking GET on the loader's
+// background thread, with a timeout and a size cap, and the bytes handed
+// straight to the decoder. A remote image is shown on its own, since
+// there's no folder to browse around it.
+use std::io::Read;
+use std::time::Duration;
+
+// Refuse anything bigger than this rather than filling memory with a
+// mistyped link to a video or disk image.
+const MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+pub fn is_url(s: &str) -> bool {
+    let s = s.to_ascii_lowercase();
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = ureq::get(url)
+        .timeout(Duration::from_secs(30))
+        .call()
+        .map_err(|e| e.to_string())?;
+
+    let mut bytes = Vec::new();
+    response.into_reader()
+        .take(MAX_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if bytes.len() as u64 > MAX_BYTES {
+        return Err(format!("larger than {} MB", MAX_BYTES / 1024 / 1024));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,496 @@
+// Finding the images in a folder, and optionally in the folders inside it.
+//
+// With subfolders included, a tree of folders (one per event, say) is
+// browsed as one continuous gallery. Each subfolder's images are kept
+// together as a "chapter", in the sort order within it, and Ctrl+Left and
+// Ctrl+Right jump from one chapter to the next while the arrow keys still
+// step through every image in turn.
+//
+// Sorted by date taken, the chapters are days instead, across all the
+// folders: a trip's photos play back in the order they were taken, and
+// Ctrl+arrows jump to the next or previous day.
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::decode;
+use crate::metadata::{self, Taken};
+
+// File extensions we treat as images, matched case-insensitively. Also used
+// for the file dialog's filter so the two can never disagree.
+pub const EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+// The formats behind those extensions, for recognising images by content.
+pub const FORMATS: &[ImageFormat] = &[ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Gif, ImageFormat::WebP, ImageFormat::Bmp, ImageFormat::Tiff];
+
+// e.g. "JPEG, PNG, GIF, WebP, BMP and TIFF", for telling someone what
+// would have worked.
+pub fn supported_formats() -> String {
+    let names: Vec<String> = FORMATS.iter().copied().filter(|format| format.reading_enabled()).map(metadata::format_name).collect();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {last}", rest.join(", ")),
+        _ => names.join(""),
+    }
+}
+
+pub struct ScanOptions {
+    // Leave out dotfiles (".thumb.jpg", macOS "._IMG_0001.jpg" resource
+    // forks) and, on Windows, files marked hidden or system, such as the
+    // "Folder.jpg" album art Windows Media Player scatters around.
+    pub skip_hidden: bool,
+
+    // Decide what's an image by reading the first few bytes of each file
+    // rather than trusting its extension. Catches photos with a wrong or
+    // missing extension, and skips files that are named like images but
+    // aren't. Off by default, since it means opening every file in the
+    // folder, which is slow on network drives.
+    pub sniff_content: bool,
+
+    // Move on into folders that are symlinks (or junctions) when looking
+    // for the next folder or through subfolders. Off by default: a link
+    // back up the tree would otherwise have "next folder" going round in
+    // circles forever.
+    pub follow_folder_links: bool,
+
+    // List the images in every folder inside the one scanned too, all the
+    // way down.
+    pub include_subfolders: bool,
+}
+
+// The order images are listed (and so navigated) in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortOrder {
+    Name,
+    // Oldest first, so a folder of photos plays back in the order they
+    // were taken (or copied off the camera).
+    Modified,
+    // Smallest first.
+    FileSize,
+    // Fewest pixels first (width times height), so thumbnails and web
+    // images come before full-size photos.
+    Pixels,
+    // Tallest first, so portraits, then squares, then landscapes and
+    // finally panoramas each end up together.
+    AspectRatio,
+    // Oldest first, by the EXIF capture date, or the date modified for
+    // files without one.
+    Taken,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 6] = [SortOrder::Name, SortOrder::Modified, SortOrder::Taken, SortOrder::FileSize, SortOrder::Pixels, SortOrder::AspectRatio];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Name => "Name",
+            SortOrder::Modified => "Date modified",
+            SortOrder::FileSize => "File size",
+            SortOrder::Pixels => "Dimensions",
+            SortOrder::AspectRatio => "Aspect ratio",
+            SortOrder::Taken => "Date taken",
+        }
+    }
+}
+
+// How names compare, when sorting by name and in keeping folders together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameOrder {
+    // "a.jpg", "B.jpg", "c.jpg", the way file managers list them.
+    IgnoreCase,
+    // Byte by byte, which puts every capital before every lowercase
+    // letter: "B.jpg", "a.jpg", "c.jpg".
+    Exact,
+}
+
+impl NameOrder {
+    pub const ALL: [NameOrder; 2] = [NameOrder::IgnoreCase, NameOrder::Exact];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NameOrder::IgnoreCase => "Ignoring case",
+            NameOrder::Exact => "Exact (capitals first)",
+        }
+    }
+
+    // What to sort `path` by. Compared folder by folder, like paths
+    // themselves, so "a b/x.jpg" doesn't slip in between "a/x.jpg" and
+    // "a/y.jpg". Names that differ only in case keep their exact order
+    // between them, so the order never depends on what order the folder
+    // was read in.
+    fn key(self, path: &Path) -> (Vec<String>, PathBuf) {
+        let folded = match self {
+            NameOrder::IgnoreCase => path.components().map(|part| part.as_os_str().to_string_lossy().to_lowercase()).collect(),
+            NameOrder::Exact => Vec::new(),
+        };
+        (folded, path.to_path_buf())
+    }
+}
+
+// Whether `path` has one of our supported extensions.
+// to_lowercase() ensures .JPG and .jpg both match.
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// Whether `path` should be listed as an image under `options`.
+pub fn is_image(path: &Path, options: &ScanOptions) -> bool {
+    if options.sniff_content {
+        has_image_content(path)
+    } else {
+        is_supported(path)
+    }
+}
+
+// Whether the file starts with the signature of a format we can show,
+// whatever it's called.
+fn has_image_content(path: &Path) -> bool {
+    content_format(path).is_some_and(|format| FORMATS.contains(&format))
+}
+
+// The format `path` is in, going by its first few bytes alone, whatever
+// its extension says. None if they don't match any format's signature.
+pub fn content_format(path: &Path) -> Option<ImageFormat> {
+    // 32 bytes is enough for every signature image::guess_format knows.
+    let mut header = [0u8; 32];
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.read(&mut header).ok()?;
+    image::guess_format(&header[..len]).ok()
+}
+
+// The folder to browse for `path`: the path itself if it's a folder,
+// otherwise the folder containing it. A bare file name like "cat.jpg" has
+// an empty parent, which read_dir rejects, so that becomes ".".
+pub fn folder_of(path: &Path) -> Option<&Path> {
+    if path.is_dir() {
+        return Some(path);
+    }
+    match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Some(Path::new(".")),
+        parent => parent,
+    }
+}
+
+// List the supported images inside `folder`, sorted by path. Only those
+// directly inside it, unless options.include_subfolders says otherwise.
+pub fn scan_folder(folder: &Path, options: &ScanOptions) -> std::io::Result<Vec<PathBuf>> {
+    let started = Instant::now();
+    let mut images = Vec::new();
+    let mut links = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    // Folders already listed, so links that lead back up the tree (or two
+    // links to one folder) can't list anything twice or loop forever.
+    let mut listed = HashSet::new();
+    while let Some(dir) = pending.pop() {
+        if !listed.insert(dir.canonicalize().unwrap_or_else(|_| dir.clone())) {
+            continue;
+        }
+        // Only the folder asked for has to be readable; a subfolder we
+        // aren't allowed into is left out.
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == folder => return Err(e),
+            Err(e) => {
+                log::warn!("{}: {e}", dir.display());
+                continue;
+            }
+        };
+        for entry in entries {
+            let Ok(entry) = entry else { continue; };
+            if options.skip_hidden && is_hidden(&entry) {
+                continue;
+            }
+            let path = entry.path();
+            let link = entry.file_type().is_ok_and(|t| t.is_symlink());
+            if options.include_subfolders && path.is_dir() {
+                if !link || options.follow_folder_links {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if !(path.is_file() && is_image(&path, options)) {
+                continue;
+            }
+            if link {
+                links.push(path);
+            } else {
+                images.push(path);
+            }
+        }
+    }
+
+    // A link to an image in the same folder, or two links to one image,
+    // would show the same picture twice in a row. The real files go first
+    // so they're the ones kept.
+    if !links.is_empty() {
+        images.extend(links);
+        remove_duplicate_files(&mut images);
+    }
+
+    images.sort();
+    log::debug!("scanned {}: {} images in {:.1?}", folder.display(), images.len(), started.elapsed());
+    Ok(images)
+}
+
+// The folder after `folder` among its siblings, in alphabetical order,
+// that has at least one image in it. Empty folders (and ones full of
+// non-images) are passed over, so "open the next folder" never lands on
+// nothing. None when `folder` is the last one with images.
+pub fn next_sibling_folder(folder: &Path, options: &ScanOptions) -> Option<PathBuf> {
+    // We need the folder's real parent to list its siblings, but the
+    // folder itself has to keep its own name: if it's a link, resolving it
+    // would put us wherever it points, which could be earlier in the list
+    // and start the whole walk over again. So only the parent is
+    // canonicalized (which also turns "." into a real name). A path whose
+    // last part is ".." has no name of its own, and is resolved whole.
+    let absolute = std::path::absolute(folder).ok()?;
+    let folder = match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize().ok()?.join(name),
+        _ => absolute.canonicalize().ok()?,
+    };
+    let parent = folder.parent()?;
+
+    let mut siblings: Vec<PathBuf> = std::fs::read_dir(parent).ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if options.skip_hidden && is_hidden(&entry) {
+                return None;
+            }
+            if !options.follow_folder_links && entry.file_type().ok()?.is_symlink() {
+                return None;
+            }
+            let path = entry.path();
+            path.is_dir().then_some(path)
+        })
+        .collect();
+    siblings.sort();
+
+    siblings.into_iter()
+        .filter(|sibling| *sibling > folder)
+        .find(|sibling| scan_folder(sibling, options).is_ok_and(|images| !images.is_empty()))
+}
+
+// Drop images that are the same file as one earlier in the list, reached
+// through a symlink or a second folder that's a link to the first. The
+// first of each is kept, wherever the list came from.
+pub fn remove_duplicate_files(images: &mut Vec<PathBuf>) {
+    let mut seen = HashSet::new();
+    images.retain(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
+}
+
+// Whether `a` and `b` are the same folder, even if one is a link to the
+// other or they're written differently ("." and its full path).
+pub fn same_folder(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// Image sizes and capture dates read for sorting by them. Reading a header
+// is quick, but not so quick that a folder of thousands should be read
+// again every time it's re-sorted or reopened, so they're kept for as long
+// as Gallerust runs, and trusted for as long as the file's modification
+// time doesn't change.
+#[derive(Default)]
+pub struct SortKeys {
+    dimensions: HashMap<PathBuf, (SystemTime, [u32; 2])>,
+    taken: HashMap<PathBuf, (SystemTime, Taken)>,
+}
+
+impl SortKeys {
+    // `path`'s size, upright, or [0, 0] if it can't be read.
+    fn dimensions(&mut self, path: &Path) -> [u32; 2] {
+        let modified = modified(path);
+        if let Some(&(when, size)) = self.dimensions.get(path)
+            && when == modified
+        {
+            return size;
+        }
+        let size = decode::dimensions(path).unwrap_or([0, 0]);
+        self.dimensions.insert(path.to_path_buf(), (modified, size));
+        size
+    }
+
+    fn taken(&mut self, path: &Path) -> Taken {
+        let modified = modified(path);
+        if let Some(&(when, taken)) = self.taken.get(path)
+            && when == modified
+        {
+            return taken;
+        }
+        let taken = Taken::read(path, modified);
+        self.taken.insert(path.to_path_buf(), (modified, taken));
+        taken
+    }
+
+    // When `path` was taken, if it's been read while sorting. Sorted by date
+    // taken, that's every image listed, so this is how the chapters and the
+    // title find the days without going back to the disk.
+    pub fn known_taken(&self, path: &Path) -> Option<Taken> {
+        self.taken.get(path).map(|&(_, taken)| taken)
+    }
+}
+
+fn modified(path: &Path) -> SystemTime {
+    std::fs::metadata(path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+// Sort `images` in place. The metadata-based orders fall back to the path
+// for ties (and for files whose metadata can't be read), so the order is
+// always the same for the same folder. With `by_folder`, each folder's
+// images are kept together as a chapter, and sorted within it.
+pub fn sort_images(images: &mut [PathBuf], order: SortOrder, names: NameOrder, by_folder: bool, keys: &mut SortKeys) {
+    match order {
+        SortOrder::Name => images.sort_by_cached_key(|path| names.key(path)),
+        SortOrder::Modified => images.sort_by_cached_key(|path| (modified(path), path.clone())),
+        SortOrder::FileSize => images.sort_by_cached_key(|path| {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            (size, path.clone())
+        }),
+        SortOrder::Pixels => images.sort_by_cached_key(|path| {
+            let [width, height] = keys.dimensions(path);
+            (width as u64 * height as u64, path.clone())
+        }),
+        SortOrder::AspectRatio => images.sort_by_cached_key(|path| {
+            // Width over height, to six decimal places, since floats can't
+            // be sort keys. Files that couldn't be read come first.
+            let [width, height] = keys.dimensions(path);
+            let ratio = if height == 0 { 0 } else { (width as f64 / height as f64 * 1e6) as u64 };
+            (ratio, path.clone())
+        }),
+        SortOrder::Taken => images.sort_by_cached_key(|path| (keys.taken(path), path.clone())),
+    }
+    // A stable sort, so the order within each folder stays as it is. Paths
+    // compare folder by folder, so a folder's own images come before its
+    // subfolders', and each subfolder is followed by its own subfolders.
+    // Not by date taken, whose chapters are days whatever folder they're in.
+    if by_folder && order != SortOrder::Taken {
+        images.sort_by_cached_key(|path| names.key(path.parent().unwrap_or(path)));
+    }
+}
+
+// Which chapter (see the top of this file) an image is in.
+#[derive(PartialEq)]
+enum Chapter<'a> {
+    Folder(Option<&'a Path>),
+    Day(Option<[u16; 3]>),
+}
+
+fn chapter<'a>(path: &'a Path, order: SortOrder, keys: &SortKeys) -> Chapter<'a> {
+    match order {
+        SortOrder::Taken => Chapter::Day(keys.known_taken(path).map(|taken| taken.date)),
+        _ => Chapter::Folder(path.parent()),
+    }
+}
+
+// The index of the first image of the chapter after the one `current` is
+// in, or before it if not `forward`: the next or previous run of images
+// from one folder, or one day when sorted by `order` Taken. None at
+// either end.
+pub fn chapter_start(images: &[PathBuf], current: usize, forward: bool, order: SortOrder, keys: &SortKeys) -> Option<usize> {
+    let chapter_of = |index: usize| chapter(&images[index], order, keys);
+    if forward {
+        (current + 1..images.len()).find(|&index| chapter_of(index) != chapter_of(current))
+    } else {
+        // The start of the current chapter, then the start of the one
+        // before it.
+        let start = |from: usize| (0..from).rev().find(|&index| chapter_of(index) != chapter_of(from)).map_or(0, |index| index + 1);
+        let this = start(current);
+        (this > 0).then(|| start(this - 1))
+    }
+}
+
+// The name of the folder `path` is in, relative to `root`, for showing
+// which chapter an image belongs to: "2024/Birthday". None for an image
+// directly in `root`.
+pub fn chapter_name(path: &Path, root: &Path) -> Option<String> {
+    let relative = path.parent()?.strip_prefix(root).ok()?;
+    (!relative.as_os_str().is_empty()).then(|| relative.display().to_string())
+}
+
+fn is_hidden(entry: &std::fs::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    fn sorted(names: &[&str], order: NameOrder) -> Vec<PathBuf> {
+        let mut images = paths(names);
+        sort_images(&mut images, SortOrder::Name, order, false, &mut SortKeys::default());
+        images
+    }
+
+    #[test]
+    fn names_sort_ignoring_case_or_exactly() {
+        assert_eq!(sorted(&["B.jpg", "a.jpg", "c.jpg"], NameOrder::IgnoreCase), paths(&["a.jpg", "B.jpg", "c.jpg"]));
+        assert_eq!(sorted(&["B.jpg", "a.jpg", "c.jpg"], NameOrder::Exact), paths(&["B.jpg", "a.jpg", "c.jpg"]));
+    }
+
+    #[test]
+    fn names_differing_only_in_case_keep_one_order() {
+        // Whichever order the folder lists them in.
+        assert_eq!(sorted(&["a.jpg", "A.jpg"], NameOrder::IgnoreCase), paths(&["A.jpg", "a.jpg"]));
+        assert_eq!(sorted(&["A.jpg", "a.jpg"], NameOrder::IgnoreCase), paths(&["A.jpg", "a.jpg"]));
+    }
+
+    #[test]
+    fn names_compare_folder_by_folder() {
+        // As whole strings "a b/" would come before "a/", since ' ' < '/'.
+        for order in NameOrder::ALL {
+            assert_eq!(sorted(&["a b/x.jpg", "a/y.jpg", "a/x.jpg"], order), paths(&["a/x.jpg", "a/y.jpg", "a b/x.jpg"]));
+        }
+    }
+
+    #[test]
+    fn subfolders_follow_their_parent() {
+        let mut images = paths(&["p/b/1.jpg", "p/2.jpg", "p/a/x/3.jpg", "p/a/4.jpg", "p/1.jpg", "p/a b/5.jpg"]);
+        sort_images(&mut images, SortOrder::Name, NameOrder::IgnoreCase, true, &mut SortKeys::default());
+        assert_eq!(images, paths(&["p/1.jpg", "p/2.jpg", "p/a/4.jpg", "p/a/x/3.jpg", "p/a b/5.jpg", "p/b/1.jpg"]));
+    }
+
+    #[test]
+    fn chapters_are_runs_from_one_folder() {
+        let images = paths(&["a/1.jpg", "a/2.jpg", "b/1.jpg", "c/1.jpg", "c/2.jpg", "c/3.jpg"]);
+        let keys = SortKeys::default();
+        let next = |current| chapter_start(&images, current, true, SortOrder::Name, &keys);
+        let previous = |current| chapter_start(&images, current, false, SortOrder::Name, &keys);
+        assert_eq!(next(0), Some(2));
+        assert_eq!(next(1), Some(2));
+        assert_eq!(next(2), Some(3));
+        // From the middle of a chapter, back goes to the start of the one
+        // before, not of this one.
+        assert_eq!(previous(4), Some(2));
+        assert_eq!(previous(3), Some(2));
+        assert_eq!(previous(2), Some(0));
+        // Nowhere to go from the first or last chapter.
+        assert_eq!(previous(1), None);
+        assert_eq!(next(4), None);
+        assert_eq!(next(5), None);
+    }
+}
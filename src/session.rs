@@ -0,0 +1,53 @@
+// State about what the user was doing last time, as opposed to Settings,
+// which are preferences they chose. Stored under its own key in eframe's
+// storage so clearing one never wipes out the other.
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+const STORAGE_KEY: &str = "session";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    // Whether the window was fullscreen when the app last closed.
+    pub fullscreen: bool,
+
+    // Recently opened files, most recent first.
+    pub recent: Vec<PathBuf>,
+
+    // Whether the image info side panel is open.
+    pub show_info_panel: bool,
+
+    // Whether the thumbnail filmstrip is shown.
+    pub show_filmstrip: bool,
+
+    // Whether the position bar along the bottom of the image is shown.
+    pub show_position_bar: bool,
+
+    // Images flagged with F, for reviewing a selection later with Shift+F.
+    // Kept here rather than next to the images, so flagging never writes
+    // anything into the user's folders.
+    pub flagged: BTreeSet<PathBuf>,
+}
+
+impl Session {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|s| eframe::get_value(s, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    // Move `path` to the front of the recents list, dropping the oldest
+    // entries beyond `limit`.
+    pub fn remember(&mut self, path: PathBuf, limit: usize) {
+        self.recent.retain(|p| p != &path);
+        self.recent.insert(0, path);
+        self.recent.truncate(limit);
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, self);
+    }
+}
@@ -0,0 +1,744 @@
+// User preferences that survive between launches.
+//
+// eframe's "persistence" feature gives us a small key/value store (a RON file
+// in the platform's app data directory). We keep every preference in one
+// struct and save it under a single key, so adding a new option is just a
+// matter of adding a field here. #[serde(default)] means an older settings
+// file that is missing newer fields still loads, with the missing fields
+// falling back to their Default values.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::background::{Background, Border};
+use crate::color;
+use crate::commands::KeyCommand;
+use crate::edit::{Change, PngCompression, SaveQuality};
+use crate::motion::ReduceMotion;
+use crate::scan::{NameOrder, ScanOptions, SortOrder};
+use crate::smoothing::Smoothing;
+use crate::view::{Align, Alignment, FitMode};
+use crate::vignette::Vignette;
+
+// The key our settings are stored under in eframe's storage.
+pub const STORAGE_KEY: &str = "settings";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // How long each image stays on screen during a slideshow, in seconds.
+    pub slideshow_interval_secs: f32,
+
+    // Ask the OS not to blank the display or go to sleep while a slideshow
+    // is running. The request is released as soon as the slideshow stops.
+    pub keep_awake_during_slideshow: bool,
+
+    // Shrink images much larger than the window right after decoding,
+    // and decode them again in full only when zoomed in (see
+    // loader::shrink_to). Quicker for browsing huge scans and panoramas.
+    pub reduced_decode: bool,
+
+    // Recognise identical files by hashing their contents, and decode
+    // each only once however many folders it's in (see cache.rs).
+    pub share_identical_files: bool,
+
+    // Whether to convert images to sRGB by their color profile, or show
+    // the decoded values as they are (see color.rs). Toggled with K, to
+    // compare.
+    pub color_policy: color::Policy,
+
+    // Apply ordered dithering when reducing 16-bit or floating point images
+    // to the 8 bits per channel we display, to hide banding in gradients.
+    pub dither: bool,
+
+    // Font size, in points, for text drawn over the image (as opposed to
+    // the toolbar, which follows the normal UI style).
+    pub overlay_text_size: f32,
+
+    // Show "cat.jpg" in the title, rather than just "cat".
+    pub title_extension: bool,
+
+    // What's drawn behind the image, visible through transparent pixels
+    // and around the image's edges.
+    pub background: Background,
+
+    // An outline around the image (toggled with B).
+    pub border: Border,
+
+    // Darkened corners, for presenting (toggled with V; see vignette.rs).
+    pub vignette: Vignette,
+
+    // How images are sized at zoom 1.0, chosen separately for portrait
+    // (taller than wide) and landscape images.
+    pub portrait_fit: FitMode,
+    pub landscape_fit: FitMode,
+
+    // Fit images more than panorama_ratio times wider than they are tall
+    // to their height instead, whatever landscape_fit says, so they're
+    // seen at a useful size and panned sideways rather than shown as a
+    // thin strip. The same goes for very tall images and their width.
+    pub fit_panoramas: bool,
+    pub panorama_ratio: f32,
+
+    // How much of the window a fitted image fills, in percent (see
+    // view::fit_area). Below 100 leaves a margin around it.
+    pub fit_percent: f32,
+
+    // Where the image sits when it's smaller than the view, and which
+    // edge is shown first when it's larger.
+    pub alignment: Alignment,
+
+    // Go back to zoom 1.0 when either fit mode is changed. The zoom is a
+    // multiplier on the fit, so the old zoom under a new fit is usually an
+    // arbitrary size; with this off it's kept, within view::clamp_zoom's
+    // limits. Moving to another image always resets the zoom.
+    pub reset_zoom_on_fit_change: bool,
+
+    // At which zoom levels the image is drawn crisp, smooth, or from an
+    // averaged copy (see smoothing.rs).
+    pub smoothing: Smoothing,
+
+    // An image file to use as the window icon instead of the built-in one
+    // (see icon.rs). Read at startup, like vsync.
+    pub window_icon: Option<PathBuf>,
+
+    // The largest texture to upload, in pixels a side, or 0 for as large
+    // as the graphics card allows. Images past it are shrunk to fit before
+    // they're drawn. Only worth lowering for drivers that report a limit
+    // they can't actually manage.
+    pub max_texture_size: u32,
+
+    // Open the window maximized, rather than the size and where it was
+    // last left. Read at startup, like vsync.
+    pub start_maximized: bool,
+
+    // Wait for the display's refresh before presenting each frame, which
+    // prevents tearing. Read at startup, so changes apply after a restart.
+    pub vsync: bool,
+
+    // Upper limit on frames per second while something is animating, or 0
+    // for no limit. Nothing is redrawn while the view is still, so this
+    // only matters for spinners, slideshows and the like.
+    pub max_fps: u32,
+
+    // How many images may be decoded at once, or 0 for one per processor
+    // core (see workers.rs).
+    pub decode_threads: usize,
+
+    // Leave hidden files (dotfiles, and hidden/system files on Windows)
+    // out of the folder listing.
+    pub skip_hidden_files: bool,
+
+    // Recognise images by their contents instead of their extension.
+    pub sniff_file_contents: bool,
+
+    // What opening a file that isn't an image does, on the command line or
+    // from the recent files.
+    pub other_files: OtherFiles,
+
+    // Let "next folder" go into folders that are symlinks (see
+    // ScanOptions::follow_folder_links).
+    pub follow_folder_links: bool,
+
+    // List the images in subfolders too, each subfolder's together (see
+    // scan.rs).
+    pub include_subfolders: bool,
+
+    // The order images in a folder are shown in.
+    pub sort_order: SortOrder,
+
+    // Whether capitals sort among the lowercase letters, as in file
+    // managers, or all before them.
+    pub name_order: NameOrder,
+
+    // Encoder settings used whenever we write an image back to disk.
+    pub save_quality: SaveQuality,
+
+    // Which changes to files ask "are you sure?" first.
+    pub confirm: Confirm,
+
+    // How many times the window's resolution an exported view is drawn at
+    // (Ctrl+E, see export.rs).
+    pub export_scale: f32,
+
+    // How many recently opened files to remember.
+    pub recent_limit: usize,
+
+    // The folder the open dialogs start in. None starts them where the
+    // last image was opened from (see Gallerust::dialog_folder).
+    pub dialog_folder: Option<PathBuf>,
+
+    // Reverse which way the mouse wheel zooms, for people used to
+    // "natural" scrolling. Pinch-to-zoom is never affected.
+    pub invert_wheel_zoom: bool,
+
+    // Whether the zoom lock also blocks the +/- keys, not just the mouse
+    // wheel and pinch gestures.
+    pub zoom_lock_includes_keyboard: bool,
+
+    // Show a hand cursor over an image that's zoomed in far enough to pan.
+    pub grab_cursor: bool,
+
+    // Let a dragged image glide on after the mouse is let go (see
+    // glide.rs).
+    pub kinetic_panning: bool,
+
+    // For kiosks and presentations: the mouse can't zoom, pan or change
+    // the image, so a bumped mouse leaves the view alone. The toolbar and
+    // dialogs still take clicks, so this can be turned off again.
+    pub keyboard_only: bool,
+
+    // How many images Ctrl+Shift+Left and Right skip at once, for getting
+    // through big folders quickly.
+    pub jump_size: usize,
+
+    // Reading order goes right to left, as in manga: Left arrow and the
+    // left-hand toolbar button go to the next image, Right to the previous.
+    pub right_to_left: bool,
+
+    // Shell commands run on the current image at the press of a key (see
+    // commands.rs).
+    pub key_commands: Vec<KeyCommand>,
+
+    // Visit the folder in a random order (each image once per pass)
+    // instead of sort order.
+    pub shuffle: bool,
+
+    // What happens when moving past the last image, by hand or by the
+    // slideshow.
+    pub end_action: EndAction,
+
+    // Decode the images likely to be shown next in the background, so
+    // they appear at once (see prefetch.rs). Costs memory for up to two
+    // extra decoded images.
+    pub prefetch: bool,
+
+    // Nudge the image when Next or Previous can't go any further (see
+    // bounce.rs).
+    pub edge_bounce: bool,
+
+    // Make the interface's animations instant (see motion.rs).
+    pub reduce_motion: ReduceMotion,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndAction {
+    // Stay on the last image. A running slideshow stops.
+    Stop,
+    // Wrap around to the first image (and from the first back to the last).
+    Loop,
+    // Close the app, for kiosk-style slideshows that run once.
+    Exit,
+    // Move on to the next folder alongside this one that has images.
+    NextFolder,
+}
+
+impl EndAction {
+    pub const ALL: [EndAction; 4] = [EndAction::Stop, EndAction::Loop, EndAction::Exit, EndAction::NextFolder];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EndAction::Stop => "Stop",
+            EndAction::Loop => "Loop to the first image",
+            EndAction::Exit => "Exit Gallerust",
+            EndAction::NextFolder => "Open the next folder",
+        }
+    }
+}
+
+// What to do when asked to open a file that isn't an image we can show:
+// a video, a document, or an image in a format we don't read. Folders and
+// images always open as usual, and --quiet, which shows only the file
+// it's given, always refuses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtherFiles {
+    // Say so and open nothing. From the command line that's an error
+    // message and exit code 3, as for a folder without images.
+    Refuse,
+    // Open the folder it's in, at its first image, as if the folder had
+    // been given instead.
+    OpenFolder,
+}
+
+impl OtherFiles {
+    pub const ALL: [OtherFiles; 2] = [OtherFiles::Refuse, OtherFiles::OpenFolder];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OtherFiles::Refuse => "Say it can't be opened",
+            OtherFiles::OpenFolder => "Open its folder instead",
+        }
+    }
+}
+
+// When to ask before changing files on disk. Asking every time suits
+// careful edits; never asking suits getting through a big folder fast.
+// The question can be answered from the keyboard: Y or Enter, N or Esc.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Confirm {
+    Always,
+    // Only what Ctrl+Z can't take back, such as rotating a whole folder.
+    Irreversible,
+    Never,
+}
+
+impl Confirm {
+    pub const ALL: [Confirm; 3] = [Confirm::Always, Confirm::Irreversible, Confirm::Never];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Confirm::Always => "Before every change",
+            Confirm::Irreversible => "Before changes that can't be undone",
+            Confirm::Never => "Never",
+        }
+    }
+
+    pub fn asks(self, change: Change) -> bool {
+        match self {
+            Confirm::Always => true,
+            Confirm::Irreversible => !change.undoable(),
+            Confirm::Never => false,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            slideshow_interval_secs: 5.0,
+            keep_awake_during_slideshow: true,
+            dither: false,
+            color_policy: color::Policy::Srgb,
+            reduced_decode: false,
+            share_identical_files: false,
+            overlay_text_size: 14.0,
+            title_extension: true,
+            background: Background::Auto,
+            border: Border::default(),
+            vignette: Vignette::default(),
+            portrait_fit: FitMode::Window,
+            landscape_fit: FitMode::Window,
+            fit_panoramas: true,
+            panorama_ratio: 3.0,
+            fit_percent: 100.0,
+            reset_zoom_on_fit_change: true,
+            alignment: Alignment::CENTER,
+            smoothing: Smoothing::default(),
+            window_icon: None,
+            max_texture_size: 0,
+            start_maximized: true,
+            vsync: true,
+            max_fps: 0,
+            decode_threads: 0,
+            skip_hidden_files: true,
+            other_files: OtherFiles::Refuse,
+            sniff_file_contents: false,
+            follow_folder_links: false,
+            include_subfolders: false,
+            sort_order: SortOrder::Name,
+            name_order: NameOrder::IgnoreCase,
+            save_quality: SaveQuality::default(),
+            confirm: Confirm::Irreversible,
+            export_scale: 1.0,
+            recent_limit: 10,
+            dialog_folder: None,
+            invert_wheel_zoom: false,
+            zoom_lock_includes_keyboard: false,
+            grab_cursor: true,
+            kinetic_panning: false,
+            keyboard_only: false,
+            jump_size: 10,
+            right_to_left: false,
+            key_commands: Vec::new(),
+            shuffle: false,
+            end_action: EndAction::Loop,
+            edge_bounce: false,
+            reduce_motion: ReduceMotion::System,
+            prefetch: true,
+        }
+    }
+}
+
+impl Settings {
+    // Load settings from eframe's storage, falling back to defaults on the
+    // first launch or if the stored value can't be parsed.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|s| eframe::get_value(s, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            skip_hidden: self.skip_hidden_files,
+            sniff_content: self.sniff_file_contents,
+            follow_folder_links: self.follow_folder_links,
+            include_subfolders: self.include_subfolders,
+        }
+    }
+
+    // Read the settings straight from eframe's storage file, for the few
+    // options that have to be known before the window exists (eframe only
+    // hands us its storage once the window is up). This mirrors how eframe
+    // stores things: a RON map of key to RON-encoded value, in app.ron.
+    pub fn load_before_window(app_id: &str) -> Self {
+        let path = eframe::storage_dir(app_id).map(|dir| dir.join("app.ron"));
+        path.and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| ron::from_str::<HashMap<String, String>>(&text).ok())
+            .and_then(|values| ron::from_str(values.get(STORAGE_KEY)?).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, self);
+    }
+
+    // How an image of `img_size` is sized at zoom 1.0: by its shape, with
+    // panoramas treated specially.
+    pub fn fit_for(&self, img_size: egui::Vec2) -> FitMode {
+        if self.fit_panoramas {
+            if img_size.x >= img_size.y * self.panorama_ratio {
+                return FitMode::Height;
+            }
+            if img_size.y >= img_size.x * self.panorama_ratio {
+                return FitMode::Width;
+            }
+        }
+        if img_size.y > img_size.x { self.portrait_fit } else { self.landscape_fit }
+    }
+
+    // Draw the contents of the settings window.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Slideshow");
+        ui.horizontal(|ui| {
+            ui.label("Seconds per image:");
+            ui.add(
+                egui::DragValue::new(&mut self.slideshow_interval_secs)
+                    .clamp_range(1.0..=600.0)
+                    .speed(0.5)
+            );
+        });
+        ui.checkbox(
+            &mut self.keep_awake_during_slideshow,
+            "Keep the screen awake during slideshows",
+        ).on_hover_text("Takes effect the next time a slideshow starts");
+
+        ui.horizontal(|ui| {
+            ui.label("At the last image:");
+            egui::ComboBox::from_id_source("end_action")
+                .selected_text(self.end_action.label())
+                .show_ui(ui, |ui| {
+                    for action in EndAction::ALL {
+                        ui.selectable_value(&mut self.end_action, action, action.label());
+                    }
+                });
+        }).response.on_hover_text("Also applies to Next and the arrow keys");
+        ui.checkbox(&mut self.edge_bounce, "Bounce at the first and last image")
+            .on_hover_text("Nudges the image when there's nothing further to go to");
+
+        ui.separator();
+        ui.heading("Display");
+        ui.checkbox(&mut self.reduced_decode, "Shrink huge images to screen size while browsing")
+            .on_hover_text("Faster for very large images. Zooming in, cropping or the eyedropper loads the full resolution.");
+        ui.checkbox(&mut self.share_identical_files, "Decode identical files only once")
+            .on_hover_text("Reads each file through to recognise copies of the same image in other folders, so a duplicate shows instantly. The extra reading can slow down network drives.");
+        ui.horizontal(|ui| {
+            ui.label("Reduce motion:");
+            egui::ComboBox::from_id_source("reduce_motion")
+                .selected_text(self.reduce_motion.label())
+                .show_ui(ui, |ui| {
+                    for reduce in ReduceMotion::ALL {
+                        ui.selectable_value(&mut self.reduce_motion, reduce, reduce.label());
+                    }
+                });
+        }).response.on_hover_text("Skips the bounce at the ends of the folder, smooth scrolling and the interface's animations. Animated images still play.");
+        ui.checkbox(&mut self.prefetch, "Decode the next images ahead of time")
+            .on_hover_text("Makes stepping through a folder faster, at the cost of memory for two more images");
+        ui.horizontal(|ui| {
+            ui.label("Colors:");
+            egui::ComboBox::from_id_source("color_policy")
+                .selected_text(self.color_policy.label())
+                .show_ui(ui, |ui| {
+                    for policy in color::Policy::ALL {
+                        ui.selectable_value(&mut self.color_policy, policy, policy.label());
+                    }
+                });
+        }).response.on_hover_text("Converting applies embedded color profiles (Adobe RGB, Display P3, …) and gives floating point images the right gamma, so every format looks consistent. Raw shows the numbers in the file as they are. Toggle with K to compare.");
+        ui.checkbox(&mut self.dither, "Dither high bit depth images")
+            .on_hover_text("Reduces banding in smooth gradients of 16-bit and HDR images");
+        ui.horizontal(|ui| {
+            ui.label("Overlay text size:");
+            ui.add(egui::Slider::new(&mut self.overlay_text_size, 8.0..=32.0).fixed_decimals(0));
+        });
+        ui.checkbox(&mut self.title_extension, "Show the file extension in the title");
+        ui.horizontal(|ui| {
+            ui.label("Background:");
+            egui::ComboBox::from_id_source("background")
+                .selected_text(self.background.label())
+                .show_ui(ui, |ui| {
+                    for background in Background::ALL {
+                        ui.selectable_value(&mut self.background, background, background.label());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.border.show, "Border around the image");
+            ui.add_enabled_ui(self.border.show, |ui| {
+                ui.add(egui::DragValue::new(&mut self.border.width).clamp_range(1.0..=16.0).speed(0.1).suffix(" pt"));
+                ui.color_edit_button_srgb(&mut self.border.color);
+            });
+        }).response.on_hover_text("Toggle with B");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.vignette.show, "Darken the corners");
+            ui.add_enabled(self.vignette.show, egui::Slider::new(&mut self.vignette.strength, 0.05..=1.0).fixed_decimals(2));
+        }).response.on_hover_text("A vignette that draws the eye to the middle, for presenting. Toggle with V.");
+        for (label, id, fit) in [
+            ("Portrait images:", "portrait_fit", &mut self.portrait_fit),
+            ("Landscape images:", "landscape_fit", &mut self.landscape_fit),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                egui::ComboBox::from_id_source(id)
+                    .selected_text(fit.label())
+                    .show_ui(ui, |ui| {
+                        for mode in FitMode::ALL {
+                            ui.selectable_value(fit, mode, mode.label());
+                        }
+                    });
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.fit_panoramas, "Fit panoramas to their height when wider than");
+            ui.add_enabled(
+                self.fit_panoramas,
+                egui::DragValue::new(&mut self.panorama_ratio).clamp_range(1.5..=20.0).speed(0.05).fixed_decimals(1).suffix(":1"),
+            );
+        }).response.on_hover_text("And very tall images to their width. Takes effect on the next image.");
+        ui.horizontal(|ui| {
+            ui.label("Fit into:");
+            ui.add(egui::DragValue::new(&mut self.fit_percent).clamp_range(50.0..=100.0).speed(0.5).fixed_decimals(0).suffix("% of the window"));
+        }).response.on_hover_text("Less than 100% leaves some room around the image, e.g. 95% for presenting");
+        ui.checkbox(&mut self.reset_zoom_on_fit_change, "Reset zoom when the fit changes");
+        ui.horizontal(|ui| {
+            ui.label("Alignment:");
+            egui::ComboBox::from_id_source("align_x")
+                .selected_text(self.alignment.x.horizontal_label())
+                .show_ui(ui, |ui| {
+                    for align in Align::ALL {
+                        ui.selectable_value(&mut self.alignment.x, align, align.horizontal_label());
+                    }
+                });
+            egui::ComboBox::from_id_source("align_y")
+                .selected_text(self.alignment.y.vertical_label())
+                .show_ui(ui, |ui| {
+                    for align in Align::ALL {
+                        ui.selectable_value(&mut self.alignment.y, align, align.vertical_label());
+                    }
+                });
+        }).response.on_hover_text("Top left suits fit to width: tall images open at the top, ready to scroll down");
+        ui.horizontal(|ui| {
+            ui.label("Sharp pixels from");
+            ui.add(percent(&mut self.smoothing.nearest_from));
+            ui.label("to");
+            ui.add(percent(&mut self.smoothing.nearest_to));
+        }).response.on_hover_text("Zoom levels drawn without smoothing, so pixel edges and text stay crisp");
+        ui.horizontal(|ui| {
+            ui.label("Average pixels below");
+            ui.add(percent(&mut self.smoothing.average_below));
+        }).response.on_hover_text("Zoom levels drawn from a shrunk copy of the image, which avoids shimmering patterns in fine detail");
+        ui.horizontal(|ui| {
+            ui.label("Window icon:");
+            match &self.window_icon {
+                Some(path) => ui.label(path.file_name().unwrap_or_default().to_string_lossy()),
+                None => ui.weak("Default"),
+            };
+            if ui.button("Choose…").clicked()
+                && let Some(path) = rfd::FileDialog::new().add_filter("Images", crate::scan::EXTENSIONS).pick_file()
+            {
+                self.window_icon = Some(path);
+            }
+            if self.window_icon.is_some() && ui.button("Use default").clicked() {
+                self.window_icon = None;
+            }
+        }).response.on_hover_text("Takes effect after restarting Gallerust.");
+        ui.horizontal(|ui| {
+            ui.label("Largest texture:");
+            ui.add(
+                egui::DragValue::new(&mut self.max_texture_size)
+                    .clamp_range(0..=65536)
+                    .speed(64)
+                    .custom_formatter(|size, _| if size == 0.0 { "Graphics card's limit".to_string() } else { format!("{size} px") })
+            );
+        }).response.on_hover_text("Images larger than this on either side are shrunk before they're drawn. Lower it if very large images show up blank. Takes effect on the next image.");
+        ui.checkbox(&mut self.start_maximized, "Open the window maximized")
+            .on_hover_text("Otherwise it opens the size and in the place it was last closed. Takes effect after restarting Gallerust.");
+        ui.checkbox(&mut self.vsync, "Vertical sync")
+            .on_hover_text("Prevents tearing. Takes effect after restarting Gallerust.");
+        ui.horizontal(|ui| {
+            ui.label("Frame rate limit:");
+            ui.add(
+                egui::DragValue::new(&mut self.max_fps)
+                    .clamp_range(0..=240)
+                    .custom_formatter(|fps, _| if fps == 0.0 { "Unlimited".to_string() } else { format!("{fps} fps") })
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Decode threads:");
+            ui.add(
+                egui::DragValue::new(&mut self.decode_threads)
+                    .clamp_range(0..=64)
+                    .custom_formatter(|threads, _| if threads == 0.0 { "One per core".to_string() } else { format!("{threads}") })
+            );
+        }).response.on_hover_text("How many images are decoded at the same time. Fewer saves power and keeps other programs responsive; the image you asked for always goes first.");
+
+        ui.separator();
+        ui.heading("Folders");
+        ui.checkbox(&mut self.skip_hidden_files, "Skip hidden files")
+            .on_hover_text("Applies the next time a folder is opened");
+        ui.checkbox(&mut self.sniff_file_contents, "Recognise images by content, not extension")
+            .on_hover_text("Finds images with wrong or missing extensions. Reads every file, so big folders open more slowly.");
+        ui.horizontal(|ui| {
+            ui.label("Opening a file that isn't an image:");
+            egui::ComboBox::from_id_source("other_files")
+                .selected_text(self.other_files.label())
+                .show_ui(ui, |ui| {
+                    for other in OtherFiles::ALL {
+                        ui.selectable_value(&mut self.other_files, other, other.label());
+                    }
+                });
+        }).response.on_hover_text("A video or document given on the command line, for instance");
+        ui.checkbox(&mut self.include_subfolders, "Include subfolders")
+            .on_hover_text("Browse a whole tree of folders as one gallery, each subfolder's images together. Ctrl+Left and Ctrl+Right jump between subfolders.");
+        ui.checkbox(&mut self.follow_folder_links, "Follow folder links")
+            .on_hover_text("Include folders that are symlinks when moving on to the next folder or looking through subfolders. A link back up the tree can make the slideshow repeat folders.");
+        ui.horizontal(|ui| {
+            ui.label("Sort by:");
+            egui::ComboBox::from_id_source("sort_order")
+                .selected_text(self.sort_order.label())
+                .show_ui(ui, |ui| {
+                    for order in SortOrder::ALL {
+                        ui.selectable_value(&mut self.sort_order, order, order.label());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Compare names:");
+            egui::ComboBox::from_id_source("name_order")
+                .selected_text(self.name_order.label())
+                .show_ui(ui, |ui| {
+                    for order in NameOrder::ALL {
+                        ui.selectable_value(&mut self.name_order, order, order.label());
+                    }
+                });
+        }).response.on_hover_text("Ignoring case lists files the way Explorer and Finder do. Exact puts every capital letter first.");
+
+        ui.separator();
+        ui.heading("Saving");
+        ui.horizontal(|ui| {
+            ui.label("JPEG quality:");
+            ui.add(egui::Slider::new(&mut self.save_quality.jpeg_quality, 1..=100));
+        });
+        ui.horizontal(|ui| {
+            ui.label("PNG compression:");
+            egui::ComboBox::from_id_source("png_compression")
+                .selected_text(self.save_quality.png_compression.label())
+                .show_ui(ui, |ui| {
+                    for compression in PngCompression::ALL {
+                        ui.selectable_value(&mut self.save_quality.png_compression, compression, compression.label());
+                    }
+                });
+        });
+        ui.weak("WebP is always saved lossless.");
+        ui.horizontal(|ui| {
+            ui.label("Ask first:");
+            egui::ComboBox::from_id_source("confirm")
+                .selected_text(self.confirm.label())
+                .show_ui(ui, |ui| {
+                    for confirm in Confirm::ALL {
+                        ui.selectable_value(&mut self.confirm, confirm, confirm.label());
+                    }
+                });
+        }).response.on_hover_text("Before rotating or cropping an image and saving it over the original");
+        ui.horizontal(|ui| {
+            ui.label("Export the view (Ctrl+E) at");
+            ui.add(egui::DragValue::new(&mut self.export_scale).clamp_range(1.0..=4.0).speed(0.05).fixed_decimals(1).suffix("×"));
+            ui.label("the window's resolution");
+        }).response.on_hover_text("Higher makes a sharper copy of a zoomed-in view, and a larger file");
+
+        ui.separator();
+        ui.heading("Input");
+        ui.checkbox(&mut self.invert_wheel_zoom, "Invert mouse wheel zoom direction")
+            .on_hover_text("For natural scrolling. Pinch-to-zoom is unaffected.");
+        ui.checkbox(&mut self.zoom_lock_includes_keyboard, "Zoom lock also blocks the +/- keys");
+        ui.checkbox(&mut self.grab_cursor, "Hand cursor when the image can be dragged");
+        ui.checkbox(&mut self.kinetic_panning, "Keep the image moving after a drag is let go")
+            .on_hover_text("It glides on, slowing to a stop, like scrolling on a phone. Not with reduced motion.");
+        ui.checkbox(&mut self.keyboard_only, "Keyboard only: ignore the mouse over the image")
+            .on_hover_text("No wheel zoom, dragging, scrolling or clicking to change images, for kiosks and presentations. The toolbar still works.");
+        ui.horizontal(|ui| {
+            ui.label("Ctrl+Shift+arrows jump by");
+            ui.add(egui::DragValue::new(&mut self.jump_size).clamp_range(2..=1000));
+            ui.label("images");
+        });
+        ui.checkbox(&mut self.right_to_left, "Read right to left")
+            .on_hover_text("Left arrow goes to the next image and Right to the previous, as in manga.");
+
+        ui.separator();
+        ui.heading("Commands");
+        ui.weak("Run a program on the current image with a key. {path} is replaced by the image's path.");
+        let mut remove = None;
+        for (i, binding) in self.key_commands.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let key = ui.add(egui::TextEdit::singleline(&mut binding.key).hint_text("Ctrl+G").desired_width(80.0));
+                if !binding.key.trim().is_empty() && binding.shortcut().is_none() {
+                    key.on_hover_text("Not a key Gallerust knows, e.g. E, F2, Shift+Enter or Ctrl+Alt+Plus");
+                    ui.colored_label(ui.visuals().error_fg_color, "⚠");
+                }
+                ui.add(egui::TextEdit::singleline(&mut binding.command).hint_text("gimp {path}").desired_width(240.0));
+                if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.key_commands.remove(i);
+        }
+        if ui.button("Add command").clicked() {
+            self.key_commands.push(KeyCommand::default());
+        }
+
+        ui.separator();
+        ui.heading("History");
+        ui.horizontal(|ui| {
+            ui.label("Recent files to remember:");
+            ui.add(egui::DragValue::new(&mut self.recent_limit).clamp_range(1..=50));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Open dialogs start in:");
+            match &self.dialog_folder {
+                Some(folder) => ui.label(folder.display().to_string()),
+                None => ui.weak("The last folder opened"),
+            };
+            if ui.button("Choose…").clicked()
+                && let Some(folder) = rfd::FileDialog::new().pick_folder()
+            {
+                self.dialog_folder = Some(folder);
+            }
+            if self.dialog_folder.is_some() && ui.button("Use the last folder").clicked() {
+                self.dialog_folder = None;
+            }
+        }).response.on_hover_text("If the folder no longer exists, the dialogs open wherever your system puts them.");
+    }
+}
+
+// An editor for a scale such as a zoom threshold, shown and typed as a
+// percentage.
+fn percent(scale: &mut f32) -> egui::DragValue<'_> {
+    egui::DragValue::new(scale)
+        .speed(0.01)
+        .clamp_range(0.0..=8.0)
+        .custom_formatter(|scale, _| format!("{:.0}%", scale * 100.0))
+        .custom_parser(|text| text.trim().trim_end_matches('%').trim().parse::<f64>().ok().map(|percent| percent / 100.0))
+}
@@ -0,0 +1,65 @@
+// A random order through the folder, for shuffled viewing.
+//
+// Picking a random image each time would repeat some images and leave
+// others unseen for ages. Instead we shuffle the whole list of indices once
+// and walk through it, so every image is shown exactly once per pass, and
+// shuffle again for the next pass.
+pub struct Shuffle {
+    order: Vec<usize>,
+    // Index into `order` of the image being shown.
+    position: usize,
+}
+
+impl Shuffle {
+    // A shuffled pass over `len` images that starts from `current`, the
+    // image already on screen, so it isn't shown again later in the pass.
+    pub fn new(len: usize, current: usize) -> Self {
+        let mut order = shuffled(len);
+        if let Some(i) = order.iter().position(|&index| index == current) {
+            order.swap(0, i);
+        }
+        Self { order, position: 0 }
+    }
+
+    // The number of images this order was made for. The caller throws the
+    // shuffle away when the folder changes size.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    // The next image in this pass, or None once every image has been shown.
+    pub fn next(&mut self) -> Option<usize> {
+        if self.position + 1 >= self.order.len() {
+            return None;
+        }
+        self.position += 1;
+        Some(self.order[self.position])
+    }
+
+    pub fn prev(&mut self) -> Option<usize> {
+        self.position = self.position.checked_sub(1)?;
+        Some(self.order[self.position])
+    }
+
+    // Reshuffle for another pass and return its first image. That's never
+    // the image the last pass ended on, which would look like a stall.
+    pub fn new_pass(&mut self) -> usize {
+        let last = self.order[self.position];
+        self.order = shuffled(self.order.len());
+        if self.order.len() > 1 && self.order[0] == last {
+            let end = self.order.len() - 1;
+            self.order.swap(0, end);
+        }
+        self.position = 0;
+        self.order[0]
+    }
+}
+
+// 0..len in random order (Fisher–Yates).
+fn shuffled(len: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        order.swap(i, fastrand::usize(..=i));
+    }
+    order
+}
@@ -0,0 +1,60 @@
+// Timed, automatic advancement through the folder.
+//
+// The slideshow itself doesn't know how to change images — it only tracks
+// when the current image was shown so the app can ask "is it time to move
+// on?" each frame. That keeps all navigation going through the same
+// go_next() path the keyboard and buttons use.
+use std::time::{Duration, Instant};
+
+pub struct Slideshow {
+    // When the current image started being shown. Reset every time the
+    // image changes, including manual navigation, so pressing an arrow key
+    // mid-slideshow gives the new image a full interval.
+    shown_at: Instant,
+
+    // Held for as long as the slideshow runs. The OS inhibitor (display
+    // sleep, screensaver) is released when this is dropped, which happens
+    // automatically when the slideshow stops or the app exits.
+    _keep_awake: Option<keepawake::KeepAwake>,
+}
+
+impl Slideshow {
+    pub fn start(keep_awake: bool) -> Self {
+        // Failing to inhibit sleep isn't worth refusing to start the
+        // slideshow over (e.g. no D-Bus session on some Linux setups),
+        // so we report it and carry on without it.
+        let keep_awake = if keep_awake {
+            keepawake::Builder::default()
+                .display(true)
+                .idle(true)
+                .reason("Slideshow running")
+                .app_name("Gallerust")
+                .app_reverse_domain("io.github.drewbornt.gallerust")
+                .create()
+                .map_err(|e| log::warn!("failed to keep the screen awake: {e}"))
+                .ok()
+        } else {
+            None
+        };
+
+        Self {
+            shown_at: Instant::now(),
+            _keep_awake: keep_awake,
+        }
+    }
+
+    pub fn restart_timer(&mut self) {
+        self.shown_at = Instant::now();
+    }
+
+    // Take up where it left off after `paused` with the window out of
+    // sight, rather than moving straight on as if the time had passed.
+    pub fn resume_after(&mut self, paused: Duration) {
+        self.shown_at += paused;
+    }
+
+    // How long until the next image is due. Zero means advance now.
+    pub fn time_remaining(&self, interval: Duration) -> Duration {
+        interval.saturating_sub(self.shown_at.elapsed())
+    }
+}
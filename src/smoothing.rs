@@ -0,0 +1,85 @@
+// Choosing how the image is filtered, from the scale it's drawn at.
+//
+// No single filter looks right at every zoom:
+//
+// - Near 100% each image pixel lands on about one screen pixel, and
+//   bilinear filtering only blurs it a little for nothing. Nearest keeps
+//   text and pixel edges crisp.
+// - Enlarged well beyond that, nearest turns a photo into blocks, so
+//   bilinear takes over.
+// - Shrunk to a fraction of its size, the GPU's bilinear filter only looks
+//   at the 4 pixels nearest each sample and skips the rest, which makes
+//   fine detail (fabric, foliage, fences) shimmer into moiré. There we
+//   draw from a copy shrunk on the CPU by averaging whole blocks of pixels
+//   (the same idea as a GPU's mipmaps, which egui doesn't offer), which
+//   only needs a small adjustment on the GPU afterwards.
+//
+// The scale is physical screen pixels per image pixel, so 100% means the
+// same thing on a high-DPI display.
+use eframe::egui;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+// Where one filter hands over to the next, as scales (1.0 is 100%).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Smoothing {
+    // Nearest is used from nearest_from up to nearest_to.
+    pub nearest_from: f32,
+    pub nearest_to: f32,
+    // Below this, draw from a block-averaged copy.
+    pub average_below: f32,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self {
+            nearest_from: 0.9,
+            nearest_to: 1.5,
+            average_below: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+    // Drawn from a copy halved n times over, i.e. 1/2^n of the size.
+    Averaged(u32),
+}
+
+impl Smoothing {
+    pub fn filter(&self, scale: f32) -> Filter {
+        if scale < self.average_below && scale > 0.0 {
+            // The largest halving that still leaves at least one image
+            // pixel per screen pixel, so the GPU only ever shrinks the copy
+            // by less than half. Always at least one halving, since the
+            // threshold can be set above 0.5.
+            let halvings = (1.0 / scale).log2().floor().max(1.0) as u32;
+            Filter::Averaged(halvings.min(12))
+        } else if (self.nearest_from..=self.nearest_to).contains(&scale) {
+            Filter::Nearest
+        } else {
+            Filter::Linear
+        }
+    }
+}
+
+impl Filter {
+    pub fn texture_options(self) -> egui::TextureOptions {
+        match self {
+            Filter::Nearest => egui::TextureOptions::NEAREST,
+            Filter::Linear | Filter::Averaged(_) => egui::TextureOptions::LINEAR,
+        }
+    }
+}
+
+// `img` shrunk to 1/2^halvings of its size, each pixel the average of the
+// block it covers.
+pub fn averaged(img: &RgbaImage, halvings: u32) -> RgbaImage {
+    let width = (img.width() >> halvings).max(1);
+    let height = (img.height() >> halvings).max(1);
+    // thumbnail() is the image crate's area-averaging resize.
+    image::imageops::thumbnail(img, width, height)
+}
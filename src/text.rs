@@ -0,0 +1,240 @@
+// Text in any script: file names in Chinese, Japanese, Korean, Arabic,
+// Hebrew, Thai and so on, shown in the overlays and panels.
+//
+// The window title is drawn by the desktop, which copes with all of these.
+// Everything inside the window is drawn by egui, whose built-in fonts only
+// cover Latin, Greek, Cyrillic and emoji; anything else comes out as empty
+// boxes. So at startup we look for fonts on the system that cover the rest
+// and add them to the end of egui's list, where they're only used for
+// characters the fonts before them don't have. That means reading a few
+// large files (a CJK font is 20 MB or so), which is done on a thread of
+// its own: for the first moment an unusual name may show as boxes, rather
+// than every launch waiting for it.
+//
+// egui also lays text out strictly left to right, and doesn't join Arabic
+// letters the way they're written. visual_order puts right-to-left text
+// into the order it's read in, which makes Hebrew look right and Arabic
+// readable, if in its unjoined letter forms.
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use eframe::egui::{self, FontData, FontDefinitions, FontFamily};
+
+// Find fallback fonts and hand them to egui, in the background. They take
+// effect from the next frame after they're loaded.
+pub fn load_fallback_fonts(ctx: &egui::Context) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let mut fonts = FontDefinitions::default();
+        let mut added = 0;
+        for (path, index) in fallback_fonts() {
+            let Ok(bytes) = std::fs::read(&path) else { continue; };
+            log::debug!("fallback font {}", path.display());
+            let name = format!("fallback {}#{index}", path.display());
+            fonts.font_data.insert(name.clone(), FontData { index, ..FontData::from_owned(bytes) });
+            for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                fonts.families.entry(family).or_default().push(name.clone());
+            }
+            added += 1;
+        }
+        if added > 0 {
+            ctx.set_fonts(fonts);
+            ctx.request_repaint();
+        }
+    });
+}
+
+// The font files (and the face within each, for collections) to fall back
+// on, best first. The same file is never listed twice.
+#[cfg(target_os = "windows")]
+fn fallback_fonts() -> Vec<(PathBuf, u32)> {
+    let fonts = PathBuf::from(std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into())).join("Fonts");
+    // One for each group of scripts, taking the first that's installed:
+    // Chinese and Japanese, Korean, then Arabic, Hebrew and the rest of
+    // what Segoe UI covers, Indian scripts, and Thai.
+    let groups: [&[&str]; 5] = [
+        &["msyh.ttc", "YuGothM.ttc", "msgothic.ttc", "simsun.ttc"],
+        &["malgun.ttf", "gulim.ttc"],
+        &["segoeui.ttf", "arial.ttf"],
+        &["Nirmala.ttf", "mangal.ttf"],
+        &["LeelawUI.ttf", "tahoma.ttf"],
+    ];
+    groups.iter()
+        .filter_map(|group| group.iter().map(|name| fonts.join(name)).find(|path| path.is_file()))
+        .map(|path| (path, 0))
+        .collect()
+}
+
+// fontconfig knows which installed font is best for each language, so we
+// ask it rather than guessing at paths, which differ between
+// distributions. It answers with something for every language, covering
+// it or not, but a font that doesn't cover it does no harm further down
+// the list.
+#[cfg(not(target_os = "windows"))]
+fn fallback_fonts() -> Vec<(PathBuf, u32)> {
+    let mut fonts: Vec<(PathBuf, u32)> = Vec::new();
+    for lang in ["zh-cn", "ja", "ko", "ar", "he", "hi", "th"] {
+        let Ok(output) = std::process::Command::new("fc-match")
+            .args(["--format", "%{file}\n%{index}", &format!(":lang={lang}")])
+            .stderr(std::process::Stdio::null())
+            .output()
+        else {
+            // No fontconfig at all.
+            break;
+        };
+        let text = String::from_utf8_lossy(&output.stdout);
+        let mut lines = text.lines();
+        let (Some(file), index) = (lines.next(), lines.next().and_then(|index| index.trim().parse().ok())) else { continue; };
+        let font = (PathBuf::from(file), index.unwrap_or(0));
+        if output.status.success() && !file.is_empty() && !fonts.contains(&font) {
+            fonts.push(font);
+        }
+    }
+    fonts
+}
+
+// `text` rearranged so that, drawn left to right, right-to-left parts read
+// correctly: "שלום.jpg" becomes "jpg.םולש", as a Hebrew desktop shows it.
+// Text without any right-to-left characters comes back as it is.
+//
+// This is a small part of Unicode's bidirectional algorithm, which is
+// plenty for a file name or a note: each character gets an embedding
+// level from its own direction and its neighbours', and then every run at
+// each level, from the deepest out, is reversed.
+pub fn visual_order(text: &str) -> Cow<'_, str> {
+    let chars: Vec<char> = text.chars().collect();
+    let directions: Vec<Direction> = chars.iter().map(|&c| direction(c)).collect();
+    if !directions.contains(&Direction::Rtl) {
+        return Cow::Borrowed(text);
+    }
+
+    // The paragraph goes the way its first letter does.
+    let rtl = directions.iter().find(|&&d| d == Direction::Ltr || d == Direction::Rtl) == Some(&Direction::Rtl);
+    let base = if rtl { 1 } else { 0 };
+
+    // Digits read left to right, but count as right-to-left when it comes
+    // to the spaces and punctuation around them, and take the level of
+    // the nearest letter before them.
+    let mut strong_before = vec![None; chars.len()];
+    let mut last = if rtl { Direction::Rtl } else { Direction::Ltr };
+    for (i, &d) in directions.iter().enumerate() {
+        strong_before[i] = Some(last);
+        if d == Direction::Ltr || d == Direction::Rtl {
+            last = d;
+        }
+    }
+    let resolved: Vec<Direction> = directions.iter().enumerate().map(|(i, &d)| match d {
+        Direction::Digit if strong_before[i] == Some(Direction::Rtl) => Direction::Rtl,
+        Direction::Digit => Direction::Ltr,
+        other => other,
+    }).collect();
+    let mut levels: Vec<u8> = directions.iter().enumerate().map(|(i, &d)| match (d, rtl) {
+        (Direction::Rtl, _) => 1,
+        (Direction::Ltr, false) => 0,
+        (Direction::Ltr, true) => 2,
+        (Direction::Digit, _) => if strong_before[i] == Some(Direction::Rtl) || rtl { 2 } else { 0 },
+        (Direction::Neutral, _) => base,
+    }).collect();
+
+    // Spaces and punctuation between two letters going the same way go
+    // that way too; between opposite directions they take the paragraph's.
+    let mut i = 0;
+    while i < chars.len() {
+        if directions[i] != Direction::Neutral {
+            i += 1;
+            continue;
+        }
+        let end = (i..chars.len()).find(|&j| directions[j] != Direction::Neutral).unwrap_or(chars.len());
+        let before = if i == 0 { if rtl { Direction::Rtl } else { Direction::Ltr } } else { resolved[i - 1] };
+        let after = if end == chars.len() { if rtl { Direction::Rtl } else { Direction::Ltr } } else { resolved[end] };
+        let level = match (before, after) {
+            (Direction::Rtl, Direction::Rtl) => 1,
+            (Direction::Ltr, Direction::Ltr) if rtl => 2,
+            (Direction::Ltr, Direction::Ltr) => 0,
+            _ => base,
+        };
+        levels[i..end].fill(level);
+        i = end;
+    }
+
+    let mut visual: Vec<char> = chars.iter().zip(&levels).map(|(&c, &level)| if level % 2 == 1 { mirrored(c) } else { c }).collect();
+    let deepest = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=deepest).rev() {
+        let mut start = 0;
+        while start < visual.len() {
+            if levels[start] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..visual.len()).find(|&j| levels[j] < level).unwrap_or(visual.len());
+            visual[start..end].reverse();
+            levels[start..end].reverse();
+            start = end;
+        }
+    }
+    Cow::Owned(visual.into_iter().collect())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Ltr,
+    Rtl,
+    Digit,
+    Neutral,
+}
+
+fn direction(c: char) -> Direction {
+    match c {
+        // Hebrew, Arabic, Syriac, Thaana, N'Ko and friends, and their
+        // presentation forms.
+        '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' => Direction::Rtl,
+        c if c.is_numeric() => Direction::Digit,
+        c if c.is_alphabetic() => Direction::Ltr,
+        _ => Direction::Neutral,
+    }
+}
+
+// Brackets point the other way in right-to-left text.
+fn mirrored(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hebrew_name_reads_right_to_left() {
+        assert_eq!(visual_order("שלום.jpg"), "jpg.םולש");
+    }
+
+    #[test]
+    fn left_to_right_text_is_left_alone() {
+        assert!(matches!(visual_order("photo 12 (copy).jpg"), Cow::Borrowed("photo 12 (copy).jpg")));
+    }
+
+    #[test]
+    fn digits_keep_their_order_inside_right_to_left_text() {
+        assert_eq!(visual_order("שלום 123 עולם"), "םלוע 123 םולש");
+        assert_eq!(visual_order("[1] שלום"), "םולש [1]");
+    }
+
+    #[test]
+    fn brackets_are_mirrored_only_at_a_right_to_left_level() {
+        assert_eq!(visual_order("(שלום)"), "(םולש)");
+        // In a line that starts left to right, brackets at the edge of the
+        // Hebrew take the line's direction and stay as they are.
+        assert_eq!(visual_order("a (שלום) b"), "a (םולש) b");
+        assert_eq!(visual_order("שלום abc"), "abc םולש");
+    }
+}
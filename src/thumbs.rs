@@ -0,0 +1,208 @@
+// Small previews of every image in the folder, for the filmstrip.
+//
+// Decoding a few hundred full size photos just to shrink them is slow, so
+// each thumbnail is also written to a cache on disk (in the platform's
+// cache directory, e.g. ~/.cache/gallerust/thumbnails on Linux) and the
+// next launch reads the tiny cached file instead. Cache files are named
+// after a hash of the source's path, modification time and size, so
+// editing or replacing a photo gives it a new name and the stale
+// thumbnail is simply never looked at again.
+//
+// Thumbnails are made one at a time on a single background thread, in the
+// order the filmstrip asks for them, so scrolling through a huge folder
+// never spawns hundreds of decoders at once.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Instant, UNIX_EPOCH};
+
+use eframe::egui;
+use image::{ImageFormat, RgbaImage};
+
+use crate::decode;
+use crate::progress::Progress;
+use crate::workers::{self, Priority};
+
+// Longest edge of a thumbnail, in pixels. The aspect ratio is preserved,
+// so a panorama comes out 256x64 rather than squashed into a square.
+const THUMB_SIZE: u32 = 256;
+
+// Part of every cache key. Bump it when the way thumbnails are made
+// changes, so thumbnails made the old way are never reused.
+const CACHE_VERSION: u32 = 2;
+
+enum Thumb {
+    // Queued or being made on the worker thread.
+    Pending,
+    Ready(egui::TextureHandle),
+    // The source couldn't be decoded; don't keep retrying.
+    Failed,
+}
+
+pub struct Thumbnails {
+    // Bumped whenever the folder changes. The worker skips queued jobs from
+    // an older generation, so leaving a big folder doesn't make the new one
+    // wait behind thumbnails nobody will see.
+    generation: Arc<AtomicU64>,
+    jobs: Sender<(u64, PathBuf)>,
+    results: Receiver<(u64, PathBuf, Option<RgbaImage>)>,
+    thumbs: HashMap<PathBuf, Thumb>,
+    // How many thumbnails have been made of how many asked for, since the
+    // worker last caught up, for the progress bar.
+    round: Progress,
+}
+
+impl Thumbnails {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<(u64, PathBuf)>();
+        let (result_sender, results) = mpsc::channel();
+        let generation = Arc::new(AtomicU64::new(0));
+
+        let current = Arc::clone(&generation);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            // Ends when the Thumbnails (and so the job sender) is dropped.
+            for (job_generation, path) in job_receiver {
+                if job_generation != current.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let turn = workers::turn(Priority::Ahead);
+                let thumb = load_or_make(&path);
+                drop(turn);
+                if result_sender.send((job_generation, path, thumb)).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Self {
+            generation,
+            jobs,
+            results,
+            thumbs: HashMap::new(),
+            round: Progress::default(),
+        }
+    }
+
+    // Forget every thumbnail, e.g. because a different folder was opened.
+    pub fn clear(&mut self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.thumbs.clear();
+        self.round = Progress::default();
+    }
+
+    // Forget one thumbnail so it's remade next time, e.g. after rotating
+    // the file.
+    pub fn forget(&mut self, path: &Path) {
+        self.thumbs.remove(path);
+    }
+
+    // The thumbnail for `path` if it's ready. Otherwise it's queued (once)
+    // and None is returned until it arrives.
+    pub fn get(&mut self, path: &Path) -> Option<&egui::TextureHandle> {
+        if !self.thumbs.contains_key(path) {
+            self.thumbs.insert(path.to_path_buf(), Thumb::Pending);
+            let _ = self.jobs.send((self.generation.load(Ordering::Relaxed), path.to_path_buf()));
+            self.round.total += 1;
+        }
+        match self.thumbs.get(path) {
+            Some(Thumb::Ready(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    // Upload any thumbnails the worker has finished. Call once per frame.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        while let Ok((job_generation, path, thumb)) = self.results.try_recv() {
+            if job_generation != generation {
+                continue;
+            }
+            self.round.done += 1;
+            let thumb = match thumb {
+                Some(img) => {
+                    let size = [img.width() as usize, img.height() as usize];
+                    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, img.as_raw());
+                    let name = format!("thumb:{}", path.display());
+                    Thumb::Ready(ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR))
+                }
+                None => Thumb::Failed,
+            };
+            self.thumbs.insert(path, thumb);
+        }
+        if !self.round.running() {
+            self.round = Progress::default();
+        }
+    }
+
+    // How far the thumbnails asked for so far have got.
+    pub fn progress(&self) -> Progress {
+        self.round
+    }
+}
+
+// Read the cached thumbnail for `path`, or make one and cache it.
+fn load_or_make(path: &Path) -> Option<RgbaImage> {
+    let cached = cache_path(path);
+
+    if let Some(cached) = &cached
+        && let Ok(img) = image::open(cached)
+    {
+        log::trace!("thumbnail for {} read from cache", path.display());
+        return Some(img.to_rgba8());
+    }
+
+    let started = Instant::now();
+    let thumb = match decode::open(path) {
+        Ok(img) => img.thumbnail(THUMB_SIZE, THUMB_SIZE).to_rgba8(),
+        Err(e) => {
+            log::debug!("no thumbnail for {}: {e}", path.display());
+            return None;
+        }
+    };
+    log::debug!("made thumbnail for {} in {:.1?}", path.display(), started.elapsed());
+
+    // A cache we can't write to just means slower launches, not an error
+    // worth bothering anyone about. Written to a temporary name and renamed,
+    // so a half-written file can never be mistaken for a thumbnail.
+    if let Some(cached) = &cached
+        && let Some(dir) = cached.parent()
+        && std::fs::create_dir_all(dir).is_ok()
+    {
+        let tmp = cached.with_extension("tmp");
+        if thumb.save_with_format(&tmp, ImageFormat::Png).is_ok() {
+            let _ = std::fs::rename(&tmp, cached);
+        } else {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+
+    Some(thumb)
+}
+
+// Where the thumbnail for `path` is cached, or None if there's no cache
+// directory on this platform or the file can't be stat'ed.
+fn cache_path(path: &Path) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    // FNV-1a rather than std's DefaultHasher, whose output is allowed to
+    // change between Rust releases and would silently empty the cache.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    feed(path.as_os_str().as_encoded_bytes());
+    feed(&modified.as_nanos().to_le_bytes());
+    feed(&metadata.len().to_le_bytes());
+    feed(&THUMB_SIZE.to_le_bytes());
+    feed(&CACHE_VERSION.to_le_bytes());
+
+    Some(dirs::cache_dir()?.join("gallerust").join("thumbnails").join(format!("{hash:016x}.png")))
+}
@@ -0,0 +1,369 @@
+// Images too big to hold in memory whole: gigapixel scans of maps,
+// microscope slides and the like, which as 8-bit RGBA would take several
+// gigabytes, more than the image crate will even try to allocate.
+//
+// These are never decoded whole. The loader reads the file a strip or tile
+// at a time (the pieces a TIFF is stored in) and averages it down into a
+// small overview as it goes, which is what's shown at fit and stands in as
+// the image's pixels everywhere else, like a copy shrunk for browsing (see
+// loader::shrink_to). Zoomed in past the overview's detail, the part of
+// the image on screen is read again, at the detail it's drawn at, in
+// square tiles on a thread of their own, and drawn over the overview as
+// they arrive. Tiles come in levels, each half the detail of the one
+// before, so a tile is always about as many pixels as it covers on screen
+// and memory stays about the same however far in or out the view is.
+//
+// Only TIFFs (and BigTIFFs) are read this way: it's the format such images
+// come in, and the only one the decoders we have can read a piece of
+// without decoding everything before it. A TIFF stored in tiles is much
+// quicker to move around in than one stored in strips, since a strip
+// spans the image's whole width. Anything else that large still goes
+// through the normal decode, and fails with the image crate's memory
+// limit if it's too much.
+//
+// These files are shown as stored, ignoring any Orientation tag, and the
+// eyedropper, which needs real pixels, stays off for them. So do rotating
+// and cropping, which would decode the whole file to save it again (and
+// first copy all of it for undo).
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+
+use eframe::egui;
+use image::{Rgba, RgbaImage};
+use tiff::decoder::Decoder;
+use tiff::tags::Tag;
+
+use crate::color;
+use crate::loader::DecodeOptions;
+use crate::pages;
+use crate::workers::{self, Priority};
+
+// Above this many pixels an image is read in pieces. 400 MB as RGBA.
+pub const MIN_PIXELS: u64 = 100_000_000;
+
+// The longest side of the overview.
+const OVERVIEW_SIZE: u32 = 2048;
+
+// The side of a tile, in the pixels it's drawn with.
+const TILE_SIZE: u32 = 512;
+
+// Tile textures kept on the GPU at once, a megabyte each. Enough for a
+// few screenfuls, so panning back shows them at once.
+const MAX_TILES: usize = 160;
+
+// A huge TIFF open for reading a piece at a time.
+struct Source {
+    decoder: Decoder<BufReader<File>>,
+    size: [u32; 2],
+    // The size of the pieces the file is stored in, and how many there
+    // are across the image. Strips are as wide as the image.
+    chunk: [u32; 2],
+    across: u32,
+    profile: Option<Vec<u8>>,
+    name: String,
+}
+
+impl Source {
+    // `path`, if it's a TIFF big enough to need reading in pieces and laid
+    // out in a way we can.
+    fn open(path: &Path) -> Option<Self> {
+        if !pages::is_tiff(path) {
+            return None;
+        }
+        let mut decoder = Decoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+        let (width, height) = decoder.dimensions().ok()?;
+        if (width as u64) * (height as u64) < MIN_PIXELS {
+            return None;
+        }
+        // Planar files keep each channel in pieces of its own, which the
+        // tiff crate can only read the first of.
+        if decoder.find_tag_unsigned::<u16>(Tag::PlanarConfiguration).ok().flatten().unwrap_or(1) != 1 {
+            return None;
+        }
+        let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+        let profile = decoder.get_tag_u8_vec(Tag::IccProfile).ok();
+        let mut source = Self {
+            decoder,
+            size: [width, height],
+            chunk: [chunk_width, chunk_height],
+            across: width.div_ceil(chunk_width.max(1)),
+            profile,
+            name: path.display().to_string(),
+        };
+        // A pixel format pages.rs can't convert fails here rather than on
+        // every tile.
+        source.read_chunk(0, DecodeOptions { dither: false, max_size: None, color: color::Policy::Raw }).ok()?;
+        Some(source)
+    }
+
+    fn chunk_count(&self) -> u32 {
+        self.across * self.size[1].div_ceil(self.chunk[1].max(1))
+    }
+
+    // Where chunk `index` starts in the image.
+    fn chunk_origin(&self, index: u32) -> [u32; 2] {
+        [(index % self.across) * self.chunk[0], (index / self.across) * self.chunk[1]]
+    }
+
+    fn read_chunk(&mut self, index: u32, options: DecodeOptions) -> Result<RgbaImage, String> {
+        let (width, height) = self.decoder.chunk_data_dimensions(index);
+        let color_type = self.decoder.colortype().map_err(|e| e.to_string())?;
+        let data = self.decoder.read_chunk(index).map_err(|e| e.to_string())?;
+        let img = pages::to_image(width, height, color_type, data).ok_or_else(|| format!("unsupported pixel format ({color_type:?})"))?;
+        Ok(color::to_display(&img, self.profile.as_deref(), options.color, options.dither, &self.name).0)
+    }
+
+    // The part of the image from `min` to `max`, averaged down by `scale`
+    // on each axis. Only the pieces of the file it overlaps are read, one
+    // at a time.
+    fn read_region(&mut self, min: [u32; 2], max: [u32; 2], scale: u32, options: DecodeOptions) -> Result<RgbaImage, String> {
+        let out = [(max[0] - min[0]).div_ceil(scale), (max[1] - min[1]).div_ceil(scale)];
+        let mut sums = vec![[0u32; 4]; out[0] as usize * out[1] as usize];
+        let mut counts = vec![0u32; sums.len()];
+        for index in 0..self.chunk_count() {
+            let origin = self.chunk_origin(index);
+            let (width, height) = self.decoder.chunk_data_dimensions(index);
+            let (x0, x1) = (min[0].max(origin[0]), max[0].min(origin[0] + width));
+            let (y0, y1) = (min[1].max(origin[1]), max[1].min(origin[1] + height));
+            if x0 >= x1 || y0 >= y1 {
+                continue;
+            }
+            let chunk = self.read_chunk(index, options)?;
+            for y in y0..y1 {
+                let row = ((y - min[1]) / scale * out[0]) as usize;
+                for x in x0..x1 {
+                    let cell = row + ((x - min[0]) / scale) as usize;
+                    let pixel = chunk.get_pixel(x - origin[0], y - origin[1]).0;
+                    for (sum, value) in sums[cell].iter_mut().zip(pixel) {
+                        *sum = sum.saturating_add(value as u32);
+                    }
+                    counts[cell] += 1;
+                }
+            }
+        }
+        Ok(RgbaImage::from_fn(out[0], out[1], |x, y| {
+            let cell = (y * out[0] + x) as usize;
+            let count = counts[cell].max(1);
+            Rgba(sums[cell].map(|sum| (sum / count) as u8))
+        }))
+    }
+}
+
+// The overview of `path`, and its real size, if it's an image to be shown
+// in tiles. Reads the whole file, a piece at a time, so it takes a while;
+// the loader calls it on its thread.
+pub fn overview(path: &Path, options: DecodeOptions) -> Option<Result<(RgbaImage, [u32; 2]), String>> {
+    let mut source = Source::open(path)?;
+    let size = source.size;
+    let scale = size[0].max(size[1]).div_ceil(OVERVIEW_SIZE);
+    Some(source.read_region([0, 0], size, scale, options).map(|pixels| (pixels, size)))
+}
+
+// A tile: its level (each halving the detail, from 0 for full detail),
+// then its column and row at that level.
+type Key = (u32, u32, u32);
+
+// Tiles for the worker to read, most wanted first, replaced every frame
+// with what the view is still missing.
+#[derive(Default)]
+struct Queue {
+    wanted: Vec<Key>,
+    closed: bool,
+}
+
+// The tiles of the image on screen: the textures read so far and the
+// thread reading more.
+pub struct Tiles {
+    size: [u32; 2],
+    // How many of the image's pixels each overview pixel stands for. Tiles
+    // are only needed for more detail than that.
+    overview_scale: u32,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    results: Receiver<(Key, Result<RgbaImage, String>)>,
+    // Each tile's texture, and the frame it was last drawn in.
+    textures: HashMap<Key, (egui::TextureHandle, u64)>,
+    frame: u64,
+}
+
+impl Tiles {
+    // Start reading tiles of `path`, whose overview `overview` is, as they're
+    // asked for.
+    pub fn new(ctx: &egui::Context, path: &Path, size: [u32; 2], overview: [u32; 2], options: DecodeOptions) -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (sender, results) = mpsc::channel();
+        let worker_queue = Arc::clone(&queue);
+        let worker_path = path.to_path_buf();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let Some(mut source) = Source::open(&worker_path) else { return; };
+            let (lock, wake) = &*worker_queue;
+            loop {
+                let key = {
+                    let mut queue = lock.lock().unwrap();
+                    while queue.wanted.is_empty() && !queue.closed {
+                        queue = wake.wait(queue).unwrap();
+                    }
+                    if queue.closed {
+                        return;
+                    }
+                    queue.wanted.remove(0)
+                };
+                let (min, max, scale) = tile_region(key, source.size);
+                let turn = workers::turn(Priority::Wanted);
+                let tile = source.read_region(min, max, scale, options);
+                drop(turn);
+                if let Err(e) = &tile {
+                    log::warn!("{}: couldn't read a tile: {e}", worker_path.display());
+                }
+                if sender.send((key, tile)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+        Self {
+            size,
+            overview_scale: (size[0] as f32 / overview[0].max(1) as f32).ceil() as u32,
+            queue,
+            results,
+            textures: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    // Draw, over the overview at `image_rect`, the tiles covering the part
+    // of it within `view`, and ask for any that haven't been read yet.
+    // Until they arrive, a coarser tile of the same area is drawn if there
+    // is one, and otherwise the overview shows through.
+    pub fn paint(&mut self, ctx: &egui::Context, painter: &egui::Painter, image_rect: egui::Rect, view: egui::Rect) {
+        self.frame += 1;
+        while let Ok((key, tile)) = self.results.try_recv() {
+            let Ok(tile) = tile else { continue; };
+            let pixels = egui::ColorImage::from_rgba_unmultiplied([tile.width() as usize, tile.height() as usize], tile.as_raw());
+            let texture = ctx.load_texture(format!("tile {key:?}"), pixels, egui::TextureOptions::LINEAR);
+            self.textures.insert(key, (texture, self.frame));
+        }
+
+        // The level whose detail is at least the screen's.
+        let image_per_screen = self.size[0] as f32 / (image_rect.width() * ctx.pixels_per_point());
+        let level = image_per_screen.max(1.0).log2().floor() as u32;
+        let visible = image_rect.intersect(view);
+        if 1 << level >= self.overview_scale || !visible.is_positive() {
+            self.want(Vec::new());
+            return;
+        }
+
+        // The visible part of the image, in its pixels, and the tiles over it.
+        let to_image = |pos: egui::Pos2| {
+            let uv = (pos - image_rect.min) / image_rect.size();
+            [uv.x * self.size[0] as f32, uv.y * self.size[1] as f32]
+        };
+        let (min, max) = (to_image(visible.min), to_image(visible.max));
+        let span = (TILE_SIZE << level) as f32;
+        let columns = (min[0] / span) as u32..=((max[0] / span) as u32).min((self.size[0] - 1) / (TILE_SIZE << level));
+        let rows = (min[1] / span) as u32..=((max[1] / span) as u32).min((self.size[1] - 1) / (TILE_SIZE << level));
+        let center = visible.center();
+
+        let mut missing = Vec::new();
+        for row in rows {
+            for column in columns.clone() {
+                let key = (level, column, row);
+                let rect = self.screen_rect(key, image_rect);
+                match self.drawable(key) {
+                    Some((texture, uv)) => {
+                        painter.image(texture, rect, uv, egui::Color32::WHITE);
+                    }
+                    None => missing.push((key, rect.center().distance_sq(center))),
+                }
+            }
+        }
+        // From the middle of the view outwards.
+        missing.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let missing: Vec<Key> = missing.into_iter().map(|(key, _)| key).collect();
+        for key in &missing {
+            // What's drawn in its place meanwhile.
+            if let Some((texture, uv)) = self.coarser(*key) {
+                painter.image(texture, self.screen_rect(*key, image_rect), uv, egui::Color32::WHITE);
+            }
+        }
+        self.want(missing);
+        self.evict();
+    }
+
+    // Where tile `key` goes on screen.
+    fn screen_rect(&self, key: Key, image_rect: egui::Rect) -> egui::Rect {
+        let (min, max, _) = tile_region(key, self.size);
+        let to_screen = |[x, y]: [u32; 2]| image_rect.min + egui::vec2(x as f32 / self.size[0] as f32, y as f32 / self.size[1] as f32) * image_rect.size();
+        egui::Rect::from_min_max(to_screen(min), to_screen(max))
+    }
+
+    // Tile `key`'s texture, if it's been read, marked as used this frame.
+    fn drawable(&mut self, key: Key) -> Option<(egui::TextureId, egui::Rect)> {
+        let (texture, used) = self.textures.get_mut(&key)?;
+        *used = self.frame;
+        Some((texture.id(), egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0))))
+    }
+
+    // The part of the nearest coarser tile that's been read covering the
+    // same area as `key`.
+    fn coarser(&mut self, (level, column, row): Key) -> Option<(egui::TextureId, egui::Rect)> {
+        for up in 1..8 {
+            let parent = (level + up, column >> up, row >> up);
+            if (1 << parent.0) >= self.overview_scale {
+                return None;
+            }
+            if !self.textures.contains_key(&parent) {
+                continue;
+            }
+            // Where `key` lies within the parent, as a fraction of the part
+            // of the image the parent covers.
+            let (parent_min, parent_max, _) = tile_region(parent, self.size);
+            let (min, max, _) = tile_region((level, column, row), self.size);
+            let fraction = |value: u32, axis: usize| (value - parent_min[axis]) as f32 / (parent_max[axis] - parent_min[axis]) as f32;
+            let uv = egui::Rect::from_min_max(egui::pos2(fraction(min[0], 0), fraction(min[1], 1)), egui::pos2(fraction(max[0], 0), fraction(max[1], 1)));
+            return self.drawable(parent).map(|(texture, _)| (texture, uv));
+        }
+        None
+    }
+
+    fn want(&self, wanted: Vec<Key>) {
+        let (lock, wake) = &*self.queue;
+        lock.lock().unwrap().wanted = wanted;
+        wake.notify_one();
+    }
+
+    // Drop the tiles that have gone longest without being drawn, down to
+    // MAX_TILES.
+    fn evict(&mut self) {
+        if self.textures.len() <= MAX_TILES {
+            return;
+        }
+        let mut by_use: Vec<(Key, u64)> = self.textures.iter().map(|(&key, &(_, used))| (key, used)).collect();
+        by_use.sort_by_key(|&(_, used)| used);
+        for (key, _) in by_use.into_iter().take(self.textures.len() - MAX_TILES) {
+            self.textures.remove(&key);
+        }
+    }
+}
+
+impl Drop for Tiles {
+    // Let the worker thread finish.
+    fn drop(&mut self) {
+        let (lock, wake) = &*self.queue;
+        lock.lock().unwrap().closed = true;
+        wake.notify_one();
+    }
+}
+
+// The part of an image of `size` tile `key` covers, and how far it's
+// shrunk.
+fn tile_region((level, column, row): Key, size: [u32; 2]) -> ([u32; 2], [u32; 2], u32) {
+    let span = TILE_SIZE << level;
+    let min = [column * span, row * span];
+    let max = [(min[0] + span).min(size[0]), (min[1] + span).min(size[1])];
+    (min, max, 1 << level)
+}
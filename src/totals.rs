@@ -0,0 +1,61 @@
+// How many images are open and how much disk space they take up, for the
+// info panel: "243 images, 1.2 GB".
+//
+// Adding up the sizes means asking the file system about every file, which
+// on a network share with thousands of photos can take a while, so it's
+// done on a background thread each time the listing changes.
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use eframe::egui;
+
+pub struct Totals {
+    // Bumped with every refresh, so a slow count for an old listing can't
+    // overwrite the one for the current listing.
+    generation: u64,
+    sender: Sender<(u64, u64)>,
+    receiver: Receiver<(u64, u64)>,
+    count: usize,
+    // None until the sizes have been added up.
+    bytes: Option<u64>,
+}
+
+impl Totals {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { generation: 0, sender, receiver, count: 0, bytes: None }
+    }
+
+    // Start counting `images` afresh. Call whenever the listing changes.
+    pub fn refresh(&mut self, ctx: &egui::Context, images: &[PathBuf]) {
+        self.generation += 1;
+        self.count = images.len();
+        self.bytes = None;
+        let generation = self.generation;
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        let images = images.to_vec();
+        std::thread::spawn(move || {
+            // Files that vanished since the scan just count as nothing.
+            let bytes = images.iter().filter_map(|path| std::fs::metadata(path).ok()).map(|metadata| metadata.len()).sum();
+            let _ = sender.send((generation, bytes));
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn poll(&mut self) {
+        while let Ok((generation, bytes)) = self.receiver.try_recv() {
+            if generation == self.generation {
+                self.bytes = Some(bytes);
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn bytes(&self) -> Option<u64> {
+        self.bytes
+    }
+}
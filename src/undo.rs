@@ -0,0 +1,102 @@
+// Undo for edits that overwrite an image file (rotating and cropping).
+//
+// Re-applying the opposite edit isn't an undo: rotating a JPEG back
+// re-encodes it a second time, and a crop throws pixels away for good. So
+// before each edit the file is copied aside, byte for byte, and undoing
+// copies it back. The copies live in a folder of their own per running
+// instance, which is removed again on exit, and only the last few are
+// kept.
+//
+// Rotating a whole folder isn't covered: that's a copy of every image in
+// it. Unless told never to ask (see settings::Confirm), it asks for
+// confirmation first instead.
+use std::path::{Path, PathBuf};
+
+// Edits beyond this many back are forgotten, and their copies deleted.
+const MAX_ENTRIES: usize = 10;
+
+pub struct Undo {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    // For naming the copies; never reused, so a copy can't be overwritten
+    // by a later one.
+    next_id: u64,
+}
+
+struct Entry {
+    path: PathBuf,
+    copy: PathBuf,
+    // What was done, e.g. "rotate", for "Undo rotate".
+    action: String,
+}
+
+impl Undo {
+    pub fn new() -> Self {
+        let cache = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        Self {
+            dir: cache.join("gallerust").join("undo").join(std::process::id().to_string()),
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    // Run `edit`, which overwrites `path`, so that it can be undone later.
+    // If the original can't be copied aside first, the edit isn't made.
+    pub fn record(&mut self, path: &Path, action: &str, edit: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| format!("couldn't keep a copy to undo with: {e}"))?;
+        let copy = self.dir.join(format!("{}.bak", self.next_id));
+        self.next_id += 1;
+        std::fs::copy(path, &copy).map_err(|e| format!("couldn't keep a copy to undo with: {e}"))?;
+
+        if let Err(e) = edit() {
+            let _ = std::fs::remove_file(&copy);
+            return Err(e);
+        }
+
+        self.entries.push(Entry { path: path.to_path_buf(), copy, action: action.to_string() });
+        if self.entries.len() > MAX_ENTRIES {
+            let oldest = self.entries.remove(0);
+            let _ = std::fs::remove_file(oldest.copy);
+        }
+        Ok(())
+    }
+
+    // What the next undo would undo, e.g. "rotate".
+    pub fn last_action(&self) -> Option<&str> {
+        self.entries.last().map(|entry| entry.action.as_str())
+    }
+
+    // Put back the file changed by the most recent edit. Returns its path
+    // and what was undone, or None if there's nothing left to undo.
+    //
+    // Like the edits themselves, the copy goes to a temporary name first
+    // and is renamed over the file, so a failure halfway never leaves a
+    // truncated image behind. On failure the entry is kept, so it can be
+    // tried again.
+    pub fn undo(&mut self) -> Option<Result<(PathBuf, String), String>> {
+        let entry = self.entries.pop()?;
+        let mut tmp_name = entry.path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".gallerust-tmp");
+        let tmp = entry.path.with_file_name(tmp_name);
+
+        let restored = std::fs::copy(&entry.copy, &tmp)
+            .and_then(|_| std::fs::rename(&tmp, &entry.path));
+        Some(match restored {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&entry.copy);
+                Ok((entry.path, entry.action))
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                self.entries.push(entry);
+                Err(e.to_string())
+            }
+        })
+    }
+}
+
+impl Drop for Undo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
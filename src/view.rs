@@ -0,0 +1,395 @@
+// Where the image goes on screen.
+//
+// All of the fit/zoom/pan math lives here as plain functions of sizes and
+// offsets, with no egui state, so the central panel, keyboard panning and
+// the eyedropper all agree on exactly where the image is.
+//
+// Pan is measured in screen points from the centered position: (0, 0) is
+// centered, positive x moves the image right, positive y moves it down.
+use eframe::egui::{Color32, Pos2, Rect, Vec2};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// How an image is sized at zoom 1.0. The aspect ratio is kept in every
+// mode but Stretch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FitMode {
+    // The whole image fits in the view.
+    Window,
+    // The image is as wide as the view; a tall image overflows and can be
+    // panned up and down.
+    Width,
+    // The image is as tall as the view; a wide image overflows sideways.
+    Height,
+    // The image fills the view exactly, squashed or stretched to fit. For
+    // textures and the like, where the bars would be more of a distraction
+    // than the distortion.
+    Stretch,
+}
+
+impl FitMode {
+    pub const ALL: [FitMode; 4] = [FitMode::Window, FitMode::Width, FitMode::Height, FitMode::Stretch];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FitMode::Window => "Fit to window",
+            FitMode::Width => "Fit to width",
+            FitMode::Height => "Fit to height",
+            FitMode::Stretch => "Stretch to fill",
+        }
+    }
+}
+
+// Where the image sits on each axis when it doesn't fill the view, and
+// which edge is shown first when it overflows (so with Start on a tall
+// image fitted to its width, reading starts at the top).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+}
+
+impl Align {
+    pub const ALL: [Align; 3] = [Align::Start, Align::Center, Align::End];
+
+    pub fn horizontal_label(self) -> &'static str {
+        match self {
+            Align::Start => "Left",
+            Align::Center => "Center",
+            Align::End => "Right",
+        }
+    }
+
+    pub fn vertical_label(self) -> &'static str {
+        match self {
+            Align::Start => "Top",
+            Align::Center => "Middle",
+            Align::End => "Bottom",
+        }
+    }
+
+    fn factor(self) -> f32 {
+        match self {
+            Align::Start => -1.0,
+            Align::Center => 0.0,
+            Align::End => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alignment {
+    pub x: Align,
+    pub y: Align,
+}
+
+impl Alignment {
+    pub const CENTER: Alignment = Alignment { x: Align::Center, y: Align::Center };
+
+    fn factors(self) -> Vec2 {
+        Vec2::new(self.x.factor(), self.y.factor())
+    }
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Alignment::CENTER
+    }
+}
+
+// The range of the zoom slider, and of zooming with the wheel or keys.
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 5.0;
+
+// Limits on the size actually drawn, whatever the zoom and fit say: no
+// more than this many screen points per source pixel, and no smaller than
+// this many points along the image's longer side.
+const MAX_POINTS_PER_PIXEL: f32 = 32.0;
+const MIN_DISPLAY_POINTS: f32 = 32.0;
+
+// The part of a `view` that images are fitted into, when they're to fill
+// only `percent` of it and leave a margin around. The pan and alignment
+// still use the whole view, so a zoomed image can use the margin too.
+pub fn fit_area(view: Vec2, percent: f32) -> Vec2 {
+    view * (percent / 100.0).clamp(0.1, 1.0)
+}
+
+// Points per source pixel at zoom 1.0 on each axis: the scale that fits
+// `img_size` into `available` as `fit` says. The two are the same except
+// when stretching.
+pub fn fit_scale(available: Vec2, img_size: Vec2, fit: FitMode) -> Vec2 {
+    let scale_x = available.x / img_size.x;
+    let scale_y = available.y / img_size.y;
+    match fit {
+        FitMode::Window => Vec2::splat(scale_x.min(scale_y)),
+        FitMode::Width => Vec2::splat(scale_x),
+        FitMode::Height => Vec2::splat(scale_y),
+        FitMode::Stretch => Vec2::new(scale_x, scale_y),
+    }
+}
+
+// The size the image is drawn at: fitted to `available` as `fit` says,
+// then multiplied by the user's zoom.
+pub fn display_size(available: Vec2, img_size: Vec2, zoom: f32, fit: FitMode) -> Vec2 {
+    img_size * fit_scale(available, img_size, fit) * zoom
+}
+
+// The zoom is a multiplier on the fit scale, not an absolute scale, so the
+// same zoom means very different sizes as the fit scale changes: 5x on a
+// small photo fitted to the window is reasonable, but 5x on a long
+// panorama fitted to its height can be hundreds of screen pixels per
+// image pixel, and 0.1x on a tiny icon shrinks it to a speck.
+//
+// So on top of the slider's range, the zoom is kept where the drawn image
+// is at most MAX_POINTS_PER_PIXEL per pixel and at least
+// MIN_DISPLAY_POINTS long. Those limits only ever narrow the range
+// towards zoom 1.0, which is always allowed: the fit itself is never
+// overridden, even for an icon that fits at more than MAX_POINTS_PER_PIXEL.
+//
+// This is applied every frame rather than when zooming, because the fit
+// scale also changes with the window size, the fit mode and the image.
+pub fn clamp_zoom(available: Vec2, img_size: Vec2, zoom: f32, fit: FitMode) -> f32 {
+    let scale = fit_scale(available, img_size, fit);
+    if !scale.is_finite() || scale.min_elem() <= 0.0 {
+        return 1.0;
+    }
+    let max = (MAX_POINTS_PER_PIXEL / scale.max_elem()).max(1.0);
+    let min = (MIN_DISPLAY_POINTS / (img_size * scale).max_elem()).min(1.0);
+    zoom.clamp(MIN_ZOOM, MAX_ZOOM).clamp(min, max)
+}
+
+// How far the image may be panned from center on each axis. Where the image
+// is larger than the view you can pan until its edge meets the view's edge;
+// where it's smaller there's nothing to reveal, so it stays centered.
+pub fn pan_limit(view: Vec2, display: Vec2) -> Vec2 {
+    ((display - view) / 2.0).max(Vec2::ZERO)
+}
+
+pub fn clamp_pan(view: Vec2, display: Vec2, pan: Vec2) -> Vec2 {
+    let limit = pan_limit(view, display);
+    pan.clamp(-limit, limit)
+}
+
+// The pan to start an image at: centered, or up against the edges the
+// alignment asks for on the axes where it overflows the view.
+pub fn initial_pan(view: Vec2, display: Vec2, alignment: Alignment) -> Vec2 {
+    -alignment.factors() * pan_limit(view, display)
+}
+
+// The screen rectangle the image occupies. On an axis where the image is
+// smaller than the view, the alignment moves it from the middle to an
+// edge; there's no pan on that axis to add to it.
+pub fn image_rect(view: Rect, display: Vec2, pan: Vec2, alignment: Alignment) -> Rect {
+    let slack = ((view.size() - display) / 2.0).max(Vec2::ZERO);
+    Rect::from_center_size(view.center() + alignment.factors() * slack + pan, display)
+}
+
+// The zoom and pan that show `region` of the image (as fractions of its
+// size, from 0 to 1) as large as it fits in `view`, in the middle. The
+// zoom is limited as clamp_zoom says, so a tiny region only gets as large
+// as the zoom allows. On an axis where the image then overflows the view
+// the pan is limited as clamp_pan does, so a region at an edge of the
+// image stays against that edge; on one where it doesn't, the image is
+// moved off its `alignment` to put the region in the middle.
+pub fn zoom_to_region(view: Vec2, available: Vec2, img_size: Vec2, fit: FitMode, alignment: Alignment, region: Rect) -> (f32, Vec2) {
+    let at_fit = region.size() * img_size * fit_scale(available, img_size, fit);
+    let zoom = clamp_zoom(available, img_size, (view / at_fit).min_elem(), fit);
+    let display = display_size(available, img_size, zoom, fit);
+    // image_rect moves the image by the alignment's share of the slack,
+    // which the pan has to make up.
+    let slack = ((view - display) / 2.0).max(Vec2::ZERO);
+    let pan = (Vec2::splat(0.5) - region.center().to_vec2()) * display - alignment.factors() * slack;
+    let limit = pan_limit(view, display);
+    let clamp = |pan: f32, limit: f32| if limit > 0.0 { pan.clamp(-limit, limit) } else { pan };
+    (zoom, Vec2::new(clamp(pan.x, limit.x), clamp(pan.y, limit.y)))
+}
+
+// The source pixel shown at screen position `pos`, for an image of
+// `img_size` pixels drawn into `image_rect`. None outside the image.
+pub fn source_pixel(image_rect: Rect, img_size: [u32; 2], pos: Pos2) -> Option<[u32; 2]> {
+    let uv = (pos - image_rect.min) / image_rect.size();
+    if !(0.0..1.0).contains(&uv.x) || !(0.0..1.0).contains(&uv.y) {
+        return None;
+    }
+    let x = ((uv.x * img_size[0] as f32) as u32).min(img_size[0] - 1);
+    let y = ((uv.y * img_size[1] as f32) as u32).min(img_size[1] - 1);
+    Some([x, y])
+}
+
+// The pixel boundary nearest screen position `pos`, in source pixels:
+// [0, 0] is the image's top-left corner and `img_size` its bottom-right.
+// Unlike source_pixel this is clamped rather than None outside the image,
+// so a drag that runs off the edge stops at the edge.
+pub fn source_edge(image_rect: Rect, img_size: [u32; 2], pos: Pos2) -> [u32; 2] {
+    let uv = (pos - image_rect.min) / image_rect.size();
+    let x = (uv.x * img_size[0] as f32).round().clamp(0.0, img_size[0] as f32);
+    let y = (uv.y * img_size[1] as f32).round().clamp(0.0, img_size[1] as f32);
+    [x as u32, y as u32]
+}
+
+// Where the source pixel boundary `point` is on screen; the inverse of
+// source_edge.
+pub fn screen_point(image_rect: Rect, img_size: [u32; 2], point: [u32; 2]) -> Pos2 {
+    let uv = Vec2::new(point[0] as f32 / img_size[0] as f32, point[1] as f32 / img_size[1] as f32);
+    image_rect.min + uv * image_rect.size()
+}
+
+// Draw `source` into a `view_size` pixel buffer the way the central panel
+// draws it on screen, without a window or GPU: same fit, zoom, pan and
+// clipping, on a black background. Sampling is nearest-neighbour (the GPU
+// filters linearly), so the output is exact and reproducible, which is
+// what the golden tests below need.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn render(source: &RgbaImage, view_size: [u32; 2], zoom: f32, fit: FitMode, pan: Vec2, alignment: Alignment) -> RgbaImage {
+    let view = Rect::from_min_size(Pos2::ZERO, Vec2::new(view_size[0] as f32, view_size[1] as f32));
+    let img_size = Vec2::new(source.width() as f32, source.height() as f32);
+    let display = display_size(view.size(), img_size, zoom, fit);
+    let rect = image_rect(view, display, clamp_pan(view.size(), display, pan), alignment);
+
+    let background = Rgba(Color32::BLACK.to_array());
+    RgbaImage::from_fn(view_size[0], view_size[1], |x, y| {
+        // Sample at the center of each output pixel.
+        let pos = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+        match source_pixel(rect, [source.width(), source.height()], pos) {
+            Some([sx, sy]) => *source.get_pixel(sx, sy),
+            None => background,
+        }
+    })
+}
+
+// Golden tests: render a small known image through render() and compare
+// it byte for byte with committed reference PNGs in tests/golden/. After an
+// intentional change to the output, regenerate the references with
+//
+//     GALLERUST_BLESS=1 cargo test golden
+//
+// and look over the new PNGs before committing them.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // 4x2 source with a different color in every pixel, so a wrong
+    // sample position anywhere shows up as a wrong color.
+    fn source() -> RgbaImage {
+        RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8 * 60 + 40, y as u8 * 200 + 20, 255 - x as u8 * 60, 255]))
+    }
+
+    fn check_golden(name: &str, rendered: &RgbaImage) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", &format!("{name}.png")].iter().collect();
+
+        if std::env::var_os("GALLERUST_BLESS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            rendered.save(&path).unwrap();
+            return;
+        }
+
+        let golden = image::open(&path)
+            .unwrap_or_else(|e| panic!("{}: {e} (run with GALLERUST_BLESS=1 to create it)", path.display()))
+            .to_rgba8();
+        assert_eq!(golden.dimensions(), rendered.dimensions(), "{name}: size differs from golden");
+        let mismatches = golden.pixels().zip(rendered.pixels()).filter(|(a, b)| a != b).count();
+        assert_eq!(mismatches, 0, "{name}: {mismatches} pixels differ from {}", path.display());
+    }
+
+    #[test]
+    fn zoom_limits_follow_fit_scale() {
+        let view = Vec2::new(1000.0, 500.0);
+        // A photo at 1 point per pixel can use the slider's full range.
+        assert_eq!(clamp_zoom(view, Vec2::new(1000.0, 500.0), 5.0, FitMode::Window), 5.0);
+        assert_eq!(clamp_zoom(view, Vec2::new(1000.0, 500.0), 0.1, FitMode::Window), 0.1);
+        // A 10x1 strip fitted to its height is already at 500 points per
+        // pixel, so it can't be zoomed in any further.
+        assert_eq!(clamp_zoom(view, Vec2::new(10.0, 1.0), 5.0, FitMode::Height), 1.0);
+        // In a small window, zooming right out would leave a speck.
+        let small = Vec2::new(200.0, 100.0);
+        let zoom = clamp_zoom(small, Vec2::new(2000.0, 1000.0), 0.1, FitMode::Window);
+        assert_eq!(display_size(small, Vec2::new(2000.0, 1000.0), zoom, FitMode::Window).x, MIN_DISPLAY_POINTS);
+    }
+
+    #[test]
+    fn zoom_to_region_centers_it() {
+        let view = Vec2::new(1000.0, 500.0);
+        let img = Vec2::new(1000.0, 500.0);
+        // A quarter of each side, starting from the middle: 4x bigger, and
+        // panned so its center is in the middle of the view.
+        let region = Rect::from_min_max(Pos2::new(0.5, 0.5), Pos2::new(0.75, 0.75));
+        let (zoom, pan) = zoom_to_region(view, view, img, FitMode::Window, Alignment::CENTER, region);
+        assert_eq!(zoom, 4.0);
+        let rect = image_rect(Rect::from_min_size(Pos2::ZERO, view), display_size(view, img, zoom, FitMode::Window), pan, Alignment::CENTER);
+        assert_eq!(rect.min + region.center().to_vec2() * rect.size(), Pos2::new(500.0, 250.0));
+        // Too small to fill the view at the largest zoom.
+        let speck = Rect::from_min_max(Pos2::new(0.5, 0.5), Pos2::new(0.51, 0.51));
+        assert_eq!(zoom_to_region(view, view, img, FitMode::Window, Alignment::CENTER, speck).0, MAX_ZOOM);
+
+        // A tall region of a square image in a wide view: the image doesn't
+        // fill the view's width, so it's moved off to the left, where it's
+        // aligned, to put the region in the middle.
+        let square = Vec2::new(500.0, 500.0);
+        let left = Alignment { x: Align::Start, y: Align::Center };
+        let region = Rect::from_min_max(Pos2::new(0.25, 0.0), Pos2::new(0.5, 1.0));
+        let (zoom, pan) = zoom_to_region(view, view, square, FitMode::Window, left, region);
+        let rect = image_rect(Rect::from_min_size(Pos2::ZERO, view), display_size(view, square, zoom, FitMode::Window), pan, left);
+        assert_eq!(rect.min + region.center().to_vec2() * rect.size(), Pos2::new(500.0, 250.0));
+    }
+
+    #[test]
+    fn golden_fit_exact() {
+        // Same aspect ratio as the view: fills it with no bars.
+        check_golden("fit_exact", &render(&source(), [16, 8], 1.0, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_pillarbox() {
+        // View wider than the image's 2:1: black bars at the sides.
+        check_golden("pillarbox", &render(&source(), [24, 6], 1.0, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_letterbox() {
+        // View taller than the image: black bars above and below.
+        check_golden("letterbox", &render(&source(), [8, 12], 1.0, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_zoomed_in_clipped() {
+        // Twice the fit size: only the middle of the image is visible.
+        check_golden("zoomed_clipped", &render(&source(), [16, 8], 2.0, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_zoomed_in_panned_past_edge() {
+        // A pan far beyond the limit is clamped so the image's left edge
+        // meets the view's left edge, never revealing background.
+        check_golden("zoomed_panned", &render(&source(), [16, 8], 2.0, FitMode::Window, Vec2::new(1000.0, 0.0), Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_fit_height_clipped() {
+        // The wide image made as tall as a tall view: its sides are cut off.
+        check_golden("fit_height_clipped", &render(&source(), [8, 12], 1.0, FitMode::Height, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_stretch() {
+        // A 2:1 image stretched into a square view: no bars, squashed.
+        check_golden("stretch", &render(&source(), [8, 8], 1.0, FitMode::Stretch, Vec2::ZERO, Alignment::CENTER));
+    }
+
+    #[test]
+    fn golden_aligned_top_left() {
+        // Pillarboxed against the left edge, and at twice the size the
+        // top left corner is what shows.
+        let top_left = Alignment { x: Align::Start, y: Align::Start };
+        check_golden("aligned_left", &render(&source(), [24, 6], 1.0, FitMode::Window, Vec2::ZERO, top_left));
+        let view = Vec2::new(16.0, 8.0);
+        let pan = initial_pan(view, display_size(view, Vec2::new(4.0, 2.0), 2.0, FitMode::Window), top_left);
+        check_golden("aligned_top_left_zoomed", &render(&source(), [16, 8], 2.0, FitMode::Window, pan, top_left));
+    }
+
+    #[test]
+    fn golden_zoomed_out() {
+        check_golden("zoomed_out", &render(&source(), [16, 8], 0.5, FitMode::Window, Vec2::ZERO, Alignment::CENTER));
+    }
+}
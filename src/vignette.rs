@@ -0,0 +1,74 @@
+// A vignette: the corners of the view shaded slightly darker, which draws
+// the eye toward the middle, the way a print is often presented. Off by
+// default, and for showing photos rather than inspecting them, so it's a
+// quick toggle (V).
+//
+// It's drawn on top of everything in the view, image and surround alike,
+// and only ever on screen: the image's pixels (and with them the
+// eyedropper and anything saved back to disk) are untouched. The one
+// exception is exporting the view, which saves what's on screen and so
+// includes it.
+use eframe::egui::{self, Color32, Pos2, Rect};
+use serde::{Deserialize, Serialize};
+
+// The cells the view is divided into to draw the shading. egui blends the
+// color across each one, so the falloff only needs sampling this often to
+// look smooth.
+const CELLS: usize = 16;
+
+// How far from the middle, as a fraction of the way to a corner, the
+// darkening starts.
+const START: f32 = 0.35;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Vignette {
+    pub show: bool,
+    // How dark the very corners get, from 0 (not at all) to 1 (black).
+    pub strength: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self { show: false, strength: 0.4 }
+    }
+}
+
+impl Vignette {
+    // How much to darken the point `uv` (0 to 1 across the view on each
+    // axis): 0 leaves it alone, 1 makes it black.
+    pub fn darkness(self, uv: egui::Vec2) -> f32 {
+        if !self.show {
+            return 0.0;
+        }
+        // 1 at the corners, whatever the view's shape.
+        let distance = ((uv - egui::Vec2::splat(0.5)) * 2.0).length() / std::f32::consts::SQRT_2;
+        let t = ((distance - START) / (1.0 - START)).clamp(0.0, 1.0);
+        // Smoothstep, so the darkening eases in with no visible edge.
+        self.strength.clamp(0.0, 1.0) * t * t * (3.0 - 2.0 * t)
+    }
+
+    pub fn paint(self, painter: &egui::Painter, view: Rect) {
+        if !self.show || self.strength <= 0.0 {
+            return;
+        }
+        let mut mesh = egui::Mesh::default();
+        for row in 0..=CELLS {
+            for column in 0..=CELLS {
+                let uv = egui::vec2(column as f32, row as f32) / CELLS as f32;
+                let darkness = self.darkness(uv);
+                let pos = Pos2::new(view.min.x + uv.x * view.width(), view.min.y + uv.y * view.height());
+                mesh.colored_vertex(pos, Color32::from_black_alpha((darkness * 255.0).round() as u8));
+            }
+        }
+        let stride = CELLS as u32 + 1;
+        for row in 0..CELLS as u32 {
+            for column in 0..CELLS as u32 {
+                let top_left = row * stride + column;
+                mesh.add_triangle(top_left, top_left + 1, top_left + stride);
+                mesh.add_triangle(top_left + 1, top_left + stride + 1, top_left + stride);
+            }
+        }
+        painter.add(egui::Shape::mesh(mesh));
+    }
+}
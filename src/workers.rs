@@ -0,0 +1,71 @@
+// How many images are decoded at the same time.
+//
+// Every decode runs on a thread of its own: the image asked for, the ones
+// decoded ahead of time (see prefetch.rs), the other cells of the grid,
+// filmstrip thumbnails and the tiles of a huge TIFF. Each waits here for
+// a turn first, so no more than Settings::decode_threads of them are busy
+// at once: one keeps a laptop cool and quiet, a workstation can have one
+// per core. The image someone is waiting to see always gets the next turn
+// ahead of work that's only done in case it's wanted later.
+use std::sync::{Condvar, Mutex};
+
+static STATE: Mutex<State> = Mutex::new(State { limit: 1, busy: 0, wanted_waiting: 0 });
+static TURN_FREED: Condvar = Condvar::new();
+
+struct State {
+    limit: usize,
+    busy: usize,
+    // Threads with Priority::Wanted waiting for a turn, which the others
+    // let go first.
+    wanted_waiting: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    // Someone is looking at it, or is about to.
+    Wanted,
+    // Decoded ahead of time, or for a thumbnail.
+    Ahead,
+}
+
+// How many decodes may run at once, from the settings: 0 for one per
+// processor core.
+pub fn set_limit(threads: usize) {
+    let limit = match threads {
+        0 => std::thread::available_parallelism().map_or(2, |cores| cores.get()),
+        threads => threads,
+    };
+    let mut state = STATE.lock().unwrap();
+    if state.limit != limit {
+        log::debug!("decoding up to {limit} images at once");
+        state.limit = limit;
+        TURN_FREED.notify_all();
+    }
+}
+
+// A turn to decode, held until it's dropped.
+pub struct Turn(());
+
+// Wait for a turn to decode. Blocks the calling thread, so call it from
+// the thread that does the decoding, never the UI's.
+pub fn turn(priority: Priority) -> Turn {
+    let mut state = STATE.lock().unwrap();
+    if priority == Priority::Wanted {
+        state.wanted_waiting += 1;
+    }
+    while state.busy >= state.limit || (priority == Priority::Ahead && state.wanted_waiting > 0) {
+        state = TURN_FREED.wait(state).unwrap();
+    }
+    if priority == Priority::Wanted {
+        state.wanted_waiting -= 1;
+    }
+    state.busy += 1;
+    Turn(())
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        STATE.lock().unwrap().busy -= 1;
+        TURN_FREED.notify_all();
+    }
+}